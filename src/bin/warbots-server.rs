@@ -0,0 +1,198 @@
+//! Relays networked matches between browsers.
+//!
+//! ```text
+//! warbots-server [OPTIONS]
+//! ```
+//!
+//! Browsers connect over a WebSocket and take seats in rooms, or watch
+//! them; everything about rooms and moves is up to `sim::Lobby`, and this
+//! only moves its messages between sockets, with a thread for each
+//! connection. The address it listens on is printed once it is ready.
+
+use std::collections::HashMap;
+use std::io::{self, ErrorKind, Write};
+use std::net::{TcpListener, TcpStream};
+use std::process;
+use std::sync::mpsc::{self, Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use tungstenite::{Message, WebSocket};
+use warbots::sim::{ClientMessage, ConnectionId, Lobby, ServerMessage};
+
+const USAGE: &str = "\
+Relays networked matches between browsers.
+
+Usage: warbots-server [OPTIONS]
+
+Options:
+  --host HOST    Address to listen on (default 127.0.0.1); 0.0.0.0 lets other
+                 computers on the network connect
+  --port PORT    Port to listen on (default 9001); 0 picks a free one
+  --seed N       Seed the rooms' match seeds are drawn from (default random)
+  -h, --help     Show this and exit";
+
+// how long a connection has to finish the WebSocket handshake
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+// how often each connection checks for messages to send it
+const POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+// the lobby, and a way to reach every open connection
+struct Relay {
+    lobby: Lobby,
+    outboxes: HashMap<ConnectionId, Sender<ServerMessage>>,
+}
+
+impl Relay {
+    fn dispatch(&self, sends: Vec<(ConnectionId, ServerMessage)>) {
+        for (connection, message) in sends {
+            if let Some(outbox) = self.outboxes.get(&connection) {
+                // a connection that has just closed won't read it anyway
+                let _ = outbox.send(message);
+            }
+        }
+    }
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let (address, seed) = match parse(&args) {
+        Ok(Some(options)) => options,
+        Ok(None) => return,
+        Err(message) => {
+            eprintln!("warbots-server: {}\n\n{}", message, USAGE);
+            process::exit(2);
+        }
+    };
+    if let Err(error) = serve(&address, seed) {
+        eprintln!("warbots-server: {}: {}", address, error);
+        process::exit(1);
+    }
+}
+
+// where to listen and the lobby's seed, or `None` if there is nothing to do
+fn parse(args: &[String]) -> Result<Option<(String, u64)>, String> {
+    let mut host = "127.0.0.1".to_string();
+    let mut port: u16 = 9001;
+    let mut seed = rand::random();
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{} needs a value", arg));
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return Ok(None);
+            }
+            "--host" => host = value()?.clone(),
+            "--port" => port = number(arg, value()?)?,
+            "--seed" => seed = number(arg, value()?)?,
+            _ => return Err(format!("unknown argument {}", arg)),
+        }
+    }
+    Ok(Some((format!("{}:{}", host, port), seed)))
+}
+
+fn number<T: std::str::FromStr>(option: &str, value: &str) -> Result<T, String> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| format!("{} expects a number, not {:?}", option, value))
+}
+
+fn serve(address: &str, seed: u64) -> io::Result<()> {
+    let listener = TcpListener::bind(address)?;
+    println!("listening on ws://{}", listener.local_addr()?);
+    io::stdout().flush()?;
+
+    let relay = Arc::new(Mutex::new(Relay {
+        lobby: Lobby::new(seed),
+        outboxes: HashMap::new(),
+    }));
+    for (id, stream) in (0..).zip(listener.incoming()) {
+        let stream = match stream {
+            Ok(stream) => stream,
+            Err(error) => {
+                eprintln!("warbots-server: can't accept a connection: {}", error);
+                continue;
+            }
+        };
+        let relay = relay.clone();
+        thread::spawn(move || {
+            if let Err(error) = connect(id, stream, relay) {
+                eprintln!("warbots-server: connection {}: {}", id, error);
+            }
+        });
+    }
+    Ok(())
+}
+
+// runs connection `id` until it closes
+fn connect(id: ConnectionId, stream: TcpStream, relay: Arc<Mutex<Relay>>) -> Result<(), String> {
+    stream
+        .set_read_timeout(Some(HANDSHAKE_TIMEOUT))
+        .map_err(|error| error.to_string())?;
+    let mut socket = tungstenite::accept(stream).map_err(|error| error.to_string())?;
+    socket
+        .get_ref()
+        .set_read_timeout(Some(POLL_INTERVAL))
+        .map_err(|error| error.to_string())?;
+
+    let (outbox, inbox) = mpsc::channel();
+    relay.lock().unwrap().outboxes.insert(id, outbox);
+    let result = relay_messages(id, &mut socket, &inbox, &relay);
+
+    let mut relay = relay.lock().unwrap();
+    relay.outboxes.remove(&id);
+    let sends = relay.lobby.disconnect(id);
+    relay.dispatch(sends);
+    result
+}
+
+// passes what the socket says to the lobby, and what the lobby has for it
+// to the socket
+fn relay_messages(
+    id: ConnectionId,
+    socket: &mut WebSocket<TcpStream>,
+    inbox: &Receiver<ServerMessage>,
+    relay: &Mutex<Relay>,
+) -> Result<(), String> {
+    loop {
+        for message in inbox.try_iter() {
+            send(socket, &message)?;
+        }
+        let text = match socket.read() {
+            Ok(Message::Text(text)) => text,
+            Ok(Message::Binary(_)) => {
+                let message = "messages must be text".to_string();
+                send(socket, &ServerMessage::Error { message })?;
+                continue;
+            }
+            Ok(Message::Close(_)) | Err(tungstenite::Error::ConnectionClosed) => return Ok(()),
+            Ok(_) => continue,
+            Err(tungstenite::Error::Io(error))
+                if error.kind() == ErrorKind::WouldBlock || error.kind() == ErrorKind::TimedOut =>
+            {
+                continue
+            }
+            Err(error) => return Err(error.to_string()),
+        };
+        match ClientMessage::from_json(&text) {
+            Ok(message) => {
+                let mut relay = relay.lock().unwrap();
+                let sends = relay.lobby.handle(id, message);
+                relay.dispatch(sends);
+            }
+            Err(error) => {
+                let message = format!("invalid message: {}", error);
+                send(socket, &ServerMessage::Error { message })?;
+            }
+        }
+    }
+}
+
+fn send(socket: &mut WebSocket<TcpStream>, message: &ServerMessage) -> Result<(), String> {
+    socket
+        .send(Message::Text(message.to_json()))
+        .map_err(|error| error.to_string())
+}
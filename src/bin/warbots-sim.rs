@@ -0,0 +1,327 @@
+//! Plays matches between bots from the terminal and reports how they went.
+//!
+//! ```text
+//! warbots-sim [OPTIONS] BOT BOT...
+//! ```
+//!
+//! Each match is printed as it finishes, followed by how often each seat won.
+//! With `--tournament`, the bots are ranked against each other instead. See
+//! `--help` for the options.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::str::FromStr;
+use warbots::sim::{
+    match_rng, play_match, BotRegistry, Format, Map, MatchSettings, PlayerId, Plugin, PluginLimits,
+    Stream, Tournament,
+};
+
+const USAGE: &str = "\
+Plays matches between bots and reports how they went.
+
+Usage: warbots-sim [OPTIONS] BOT BOT...
+
+Each BOT takes a seat, in turn order, by the name of a built-in bot or a
+plugin. In a tournament, the BOTs are the entrants, best seeded first.
+
+Options:
+  --matches N        Matches to play (default 1)
+  --seed N           Seed of the first match (default 0); each match after it
+                     is seeded one higher
+  --seeds A,B,...    Play one match on each of these seeds instead
+  --settings FILE    Match settings, as .toml or .json
+  --map NAME|FILE    Play on a library map, or a map file
+  --replays DIR      Write each match's replay to DIR/match-SEED.json
+  --tournament FORMAT
+                     Rank the bots in a round-robin, swiss, swiss:ROUNDS or
+                     elimination tournament, where each pairing plays on
+                     every seed from both sides
+  --standings FILE   Write the tournament standings to FILE as JSON
+  --plugin FILE      Load a WebAssembly bot, named after the file; may be
+                     given more than once
+  --bots             List the bots and exit
+  -h, --help         Show this and exit";
+
+struct Options {
+    registry: BotRegistry,
+    bots: Vec<String>,
+    seeds: Vec<u64>,
+    settings: MatchSettings,
+    map: Option<Map>,
+    replays: Option<PathBuf>,
+    tournament: Option<Format>,
+    standings: Option<PathBuf>,
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let options = match parse(&args) {
+        Ok(Some(options)) => options,
+        Ok(None) => return,
+        Err(message) => {
+            eprintln!("warbots-sim: {}\n\n{}", message, USAGE);
+            process::exit(2);
+        }
+    };
+    let result = match options.tournament {
+        Some(format) => run_tournament(&options, format),
+        None => run(&options),
+    };
+    if let Err(message) = result {
+        eprintln!("warbots-sim: {}", message);
+        process::exit(1);
+    }
+}
+
+// the options in `args`, or `None` if there is nothing to play
+fn parse(args: &[String]) -> Result<Option<Options>, String> {
+    let mut registry = BotRegistry::new();
+    let mut bots = Vec::new();
+    let mut list = false;
+    let mut matches: u64 = 1;
+    let mut first_seed: u64 = 0;
+    let mut seeds = None;
+    let mut settings = None;
+    let mut map = None;
+    let mut replays = None;
+    let mut tournament = None;
+    let mut standings = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or_else(|| format!("{} needs a value", arg));
+        match arg.as_str() {
+            "-h" | "--help" => {
+                println!("{}", USAGE);
+                return Ok(None);
+            }
+            "--bots" => list = true,
+            "--matches" => matches = number(arg, value()?)?,
+            "--seed" => first_seed = number(arg, value()?)?,
+            "--seeds" => {
+                let list = value()?
+                    .split(',')
+                    .map(|seed| number(arg, seed))
+                    .collect::<Result<Vec<u64>, String>>()?;
+                // each seed names a replay, so a second would overwrite the first
+                if let Some(seed) = list
+                    .iter()
+                    .enumerate()
+                    .find_map(|(i, seed)| list[..i].contains(seed).then_some(seed))
+                {
+                    return Err(format!("--seeds lists {} more than once", seed));
+                }
+                seeds = Some(list);
+            }
+            "--settings" => settings = Some(read_settings(Path::new(value()?))?),
+            "--map" => map = Some(read_map(value()?)?),
+            "--replays" => replays = Some(PathBuf::from(value()?)),
+            "--tournament" => tournament = Some(value()?.clone()),
+            "--standings" => standings = Some(PathBuf::from(value()?)),
+            "--plugin" => registry.register_plugin(read_plugin(Path::new(value()?))?),
+            _ if arg.starts_with('-') => return Err(format!("unknown option {}", arg)),
+            name => bots.push(name.to_string()),
+        }
+    }
+
+    // after every plugin is loaded, wherever it came in the arguments
+    if list {
+        println!("{}", registry.names().join("\n"));
+        return Ok(None);
+    }
+    let names = registry.names();
+    if let Some(name) = bots.iter().find(|name| !names.contains(&name.as_str())) {
+        return Err(format!(
+            "unknown bot {:?}; expected one of {}",
+            name,
+            names.join(", ")
+        ));
+    }
+
+    if bots.len() < 2 {
+        return Err("a match needs at least two bots".to_string());
+    }
+    let tournament = match tournament {
+        Some(name) => Some(format(&name, bots.len())?),
+        None => None,
+    };
+    if tournament.is_some() && replays.is_some() {
+        return Err("--replays is for matches, not tournaments".to_string());
+    }
+    if tournament.is_none() && standings.is_some() {
+        return Err("--standings needs a --tournament".to_string());
+    }
+    // without a settings file, the defaults for these bots on this map
+    let settings = match settings {
+        Some(settings) => settings,
+        None => {
+            let mut settings = MatchSettings {
+                players: if tournament.is_some() {
+                    2
+                } else {
+                    bots.len() as u8
+                },
+                ..MatchSettings::default()
+            };
+            if let Some(map) = &map {
+                settings.width = map.width;
+                settings.height = map.height;
+            }
+            settings
+        }
+    };
+    let seeds = seeds.unwrap_or_else(|| (0..matches).map(|i| first_seed.wrapping_add(i)).collect());
+    Ok(Some(Options {
+        registry,
+        bots,
+        seeds,
+        settings,
+        map,
+        replays,
+        tournament,
+        standings,
+    }))
+}
+
+fn run(options: &Options) -> Result<(), String> {
+    let registry = &options.registry;
+    // match wins for each seat, and drawn matches
+    let mut wins = vec![0u32; options.bots.len()];
+    let mut draws = 0u32;
+
+    if let Some(dir) = &options.replays {
+        fs::create_dir_all(dir).map_err(|error| format!("{}: {}", dir.display(), error))?;
+    }
+    for (i, &seed) in options.seeds.iter().enumerate() {
+        let mut bots = options
+            .bots
+            .iter()
+            .enumerate()
+            .map(|(seat, name)| {
+                let rng = match_rng(seed, Stream::Player(seat as u8));
+                registry.create(name, rng).expect("bot names were checked")
+            })
+            .collect::<Vec<_>>();
+        let report = play_match(&options.settings, seed, options.map.as_ref(), &mut bots)
+            .map_err(|error| error.to_string())?;
+
+        let scores: Vec<String> = report.wins.iter().map(u32::to_string).collect();
+        let outcome = match report.winner() {
+            Some(player) => {
+                wins[player.index()] += 1;
+                format!("{} wins", seat_name(options, player))
+            }
+            None => {
+                draws += 1;
+                "draw".to_string()
+            }
+        };
+        println!(
+            "match {} (seed {}): {} {} in {} turns",
+            i + 1,
+            seed,
+            outcome,
+            scores.join("-"),
+            report.turns
+        );
+
+        if let Some(dir) = &options.replays {
+            let path = dir.join(format!("match-{}.json", seed));
+            fs::write(&path, report.replay.to_json())
+                .map_err(|error| format!("{}: {}", path.display(), error))?;
+        }
+    }
+
+    let played = options.seeds.len().max(1) as f64;
+    println!();
+    for (seat, &won) in wins.iter().enumerate() {
+        println!(
+            "{}: {} wins ({:.1}%)",
+            seat_name(options, PlayerId(seat as u8)),
+            won,
+            won as f64 / played * 100.0
+        );
+    }
+    println!("draws: {} ({:.1}%)", draws, draws as f64 / played * 100.0);
+    Ok(())
+}
+
+fn run_tournament(options: &Options, format: Format) -> Result<(), String> {
+    let tournament = Tournament {
+        format,
+        entrants: options.bots.clone(),
+        seeds: options.seeds.clone(),
+        settings: options.settings.clone(),
+        map: options.map.clone(),
+    };
+    let standings = tournament
+        .run(&options.registry)
+        .map_err(|error| error.to_string())?;
+    println!("{}", standings);
+    if let Some(path) = &options.standings {
+        fs::write(path, standings.to_json())
+            .map_err(|error| format!("{}: {}", path.display(), error))?;
+    }
+    Ok(())
+}
+
+// the bot in `player`'s seat, with the seat to tell apart two of the same bot
+fn seat_name(options: &Options, player: PlayerId) -> String {
+    format!("{} ({})", options.bots[player.index()], player.index() + 1)
+}
+
+// a tournament format by name, for `entrants`
+fn format(name: &str, entrants: usize) -> Result<Format, String> {
+    match name {
+        "round-robin" => Ok(Format::RoundRobin),
+        "swiss" => Ok(Format::swiss(entrants)),
+        "elimination" => Ok(Format::Elimination),
+        _ => match name.strip_prefix("swiss:") {
+            Some(rounds) => Ok(Format::Swiss {
+                rounds: number("--tournament", rounds)?,
+            }),
+            None => Err(format!(
+                "unknown tournament {:?}; expected round-robin, swiss or elimination",
+                name
+            )),
+        },
+    }
+}
+
+fn number<T: FromStr>(option: &str, value: &str) -> Result<T, String> {
+    value
+        .trim()
+        .parse()
+        .map_err(|_| format!("{} expects a number, not {:?}", option, value))
+}
+
+fn read_settings(path: &Path) -> Result<MatchSettings, String> {
+    let text =
+        fs::read_to_string(path).map_err(|error| format!("{}: {}", path.display(), error))?;
+    let settings = match path.extension().and_then(|extension| extension.to_str()) {
+        Some("json") => MatchSettings::from_json(&text),
+        _ => MatchSettings::from_toml(&text),
+    };
+    settings.map_err(|error| format!("{}: {}", path.display(), error))
+}
+
+// a plugin named after its file
+fn read_plugin(path: &Path) -> Result<Plugin, String> {
+    let wasm = fs::read(path).map_err(|error| format!("{}: {}", path.display(), error))?;
+    let name = path
+        .file_stem()
+        .and_then(|stem| stem.to_str())
+        .ok_or_else(|| format!("{}: can't name a bot after this file", path.display()))?;
+    Plugin::new(name, &wasm, PluginLimits::default())
+        .map_err(|error| format!("{}: {}", path.display(), error))
+}
+
+// a library map by name, or else a map file
+fn read_map(name: &str) -> Result<Map, String> {
+    if let Some(map) = Map::from_library(name) {
+        return Ok(map);
+    }
+    let text = fs::read_to_string(name).map_err(|error| format!("{}: {}", name, error))?;
+    Map::from_json(&text).map_err(|error| format!("{}: {}", name, error))
+}
@@ -0,0 +1,219 @@
+//! The built-in computer opponent.
+//!
+//! `ComputerBot` aims by simulating candidate shots over the terrain it
+//! observes, then fumbles the result according to its `Difficulty`: weaker
+//! bots misjudge how hard they hit, shake more on the trigger and are slower
+//! to correct themselves from where their earlier shots landed.
+
+use super::{Action, Bot, MatchRng, Observation, Weapon};
+use rand::Rng;
+
+// candidate tilts, in degrees away from straight up, most forgiving first:
+// near 45° a small error in angle moves the landing spot the least
+const TILTS: [f64; 9] = [45.0, 35.0, 55.0, 25.0, 65.0, 15.0, 75.0, 5.0, 85.0];
+const POWER_SEARCH_STEPS: usize = 18;
+// a predicted miss (px) small enough to stop looking at steeper or flatter shots
+const GOOD_ENOUGH: f64 = 2.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Difficulty {
+    Easy,
+    Medium,
+    Hard,
+}
+
+struct Skill {
+    // standard deviations of the error added to every shot
+    angle_error: f64,
+    power_error: f64,
+    // how wrong the bot's sense of its own shot power is, as a fraction
+    misjudgement: f64,
+    // share of its estimated aiming error corrected after each shot, 0 to 1
+    learning: f64,
+}
+
+impl Difficulty {
+    pub const ALL: [Difficulty; 3] = [Difficulty::Easy, Difficulty::Medium, Difficulty::Hard];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Difficulty::Easy => "easy",
+            Difficulty::Medium => "medium",
+            Difficulty::Hard => "hard",
+        }
+    }
+
+    fn skill(self) -> Skill {
+        match self {
+            Difficulty::Easy => Skill {
+                angle_error: 3.0,
+                power_error: 0.05,
+                misjudgement: 0.08,
+                learning: 0.0,
+            },
+            Difficulty::Medium => Skill {
+                angle_error: 1.0,
+                power_error: 0.02,
+                misjudgement: 0.05,
+                learning: 0.5,
+            },
+            Difficulty::Hard => Skill {
+                angle_error: 0.3,
+                power_error: 0.005,
+                misjudgement: 0.03,
+                learning: 1.0,
+            },
+        }
+    }
+}
+
+pub struct ComputerBot {
+    difficulty: Difficulty,
+    skill: Skill,
+    rng: MatchRng,
+    // what the bot believes its shot power is multiplied by; 1.0 is the truth
+    power_scale: f64,
+    // what the bot saw when it last fired, to learn from once it has landed
+    last_view: Option<Observation>,
+}
+
+impl ComputerBot {
+    pub fn new(difficulty: Difficulty, mut rng: MatchRng) -> ComputerBot {
+        let skill = difficulty.skill();
+        let sign = if rng.gen::<bool>() { 1.0 } else { -1.0 };
+        let power_scale = 1.0 + sign * skill.misjudgement;
+        ComputerBot {
+            difficulty,
+            skill,
+            rng,
+            power_scale,
+            last_view: None,
+        }
+    }
+
+    pub fn difficulty(&self) -> Difficulty {
+        self.difficulty
+    }
+
+    // where the bot thinks `action` will land, which is off by its misjudgement
+    fn predict_x(&self, observation: &Observation, action: &Action) -> Option<f64> {
+        let believed = Action {
+            power: action.power * self.power_scale,
+            ..*action
+        };
+        observation.predict_impact(&believed).map(|point| point.x())
+    }
+
+    fn learn(&mut self, observation: &Observation) {
+        let view = match self.last_view.take() {
+            Some(view) => view,
+            None => return,
+        };
+        // a round started since then if the shots seen then aren't the
+        // first of these, and the bot's shot is lost with it
+        let seen = view.shots.len();
+        if observation.shots.len() <= seen || observation.shots[..seen] != view.shots[..] {
+            return;
+        }
+        // the shot fired straight after that decision
+        let shot = &observation.shots[seen];
+        if shot.player != view.player {
+            return;
+        }
+        let actual = match shot.impact {
+            Some(point) => point.x(),
+            None => return,
+        };
+        // The power that the bot's model says lands where the shot did, over
+        // the ground as it was before the crater. If the model were right this
+        // would be the power actually fired.
+        let (modelled, miss) = self.search_power(&view, shot.action.angle, actual);
+        if miss <= GOOD_ENOUGH && shot.action.power > 0.0 {
+            let implied = self.power_scale * modelled / shot.action.power;
+            self.power_scale += self.skill.learning * (implied - self.power_scale);
+        }
+    }
+
+    // signed distance, in the direction of the target, that `power` lands past it
+    fn overshoot(&self, observation: &Observation, angle: f64, power: f64, target: f64) -> f64 {
+        let own_x = observation.own_tank.turret_base().x();
+        let direction = (target - own_x).signum();
+        let action = Action {
+            power,
+            angle,
+            weapon: Weapon::Missile,
+        };
+        match self.predict_x(observation, &action) {
+            Some(x) => (x - target) * direction,
+            // the only way to leave the field when aiming at the target is to
+            // fly past it
+            None => f64::INFINITY,
+        }
+    }
+
+    // the power that lands closest to `target` at `angle`, and how far off it is
+    fn search_power(&self, observation: &Observation, angle: f64, target: f64) -> (f64, f64) {
+        let config = &observation.config;
+        let mut low = config.min_power() as f64;
+        let mut high = config.max_power() as f64;
+        for _ in 0..POWER_SEARCH_STEPS {
+            let mid = (low + high) / 2.0;
+            if self.overshoot(observation, angle, mid, target) > 0.0 {
+                high = mid;
+            } else {
+                low = mid;
+            }
+        }
+        let power = (low + high) / 2.0;
+        (
+            power,
+            self.overshoot(observation, angle, power, target).abs(),
+        )
+    }
+
+    // a normally distributed sample with the given standard deviation
+    fn jitter(&mut self, deviation: f64) -> f64 {
+        let u1: f64 = 1.0 - self.rng.gen::<f64>();
+        let u2: f64 = self.rng.gen();
+        deviation * (-2.0 * libm::log(u1)).sqrt() * libm::cos(2.0 * std::f64::consts::PI * u2)
+    }
+}
+
+impl Bot for ComputerBot {
+    fn name(&self) -> &str {
+        self.difficulty.name()
+    }
+
+    fn decide(&mut self, observation: &Observation) -> Option<Action> {
+        self.learn(observation);
+
+        // with nobody left to aim at, it passes
+        let enemy = observation.nearest_enemy()?;
+        let enemy_x = enemy.location().x() + enemy.width() / 2.0;
+        let own_x = observation.own_tank.turret_base().x();
+        // angles past 90° fire the way the tank faces
+        let forwards = (enemy_x - own_x) * observation.own_tank.facing() > 0.0;
+
+        let mut best = (90.0, observation.config.min_power() as f64, f64::INFINITY);
+        for &tilt in TILTS.iter() {
+            let angle = if forwards { 90.0 + tilt } else { 90.0 - tilt };
+            let (power, miss) = self.search_power(observation, angle, enemy_x);
+            if miss < best.2 {
+                best = (angle, power, miss);
+            }
+            if miss <= GOOD_ENOUGH {
+                break;
+            }
+        }
+
+        self.last_view = Some(observation.clone());
+        let (angle, power, _) = best;
+        let angle_error = self.jitter(self.skill.angle_error);
+        let power_error = self.jitter(self.skill.power_error);
+        Some(Action {
+            power: power * (1.0 + power_error),
+            angle: angle + angle_error,
+            weapon: Weapon::Missile,
+        })
+    }
+}
@@ -0,0 +1,130 @@
+//! Matches between bots, played out with no one watching.
+
+use super::{
+    play_bot_turn, round_seed, Bot, Game, Map, MapError, MatchSettings, PlayerId, Replay,
+    SettingsError,
+};
+use std::error::Error;
+use std::fmt;
+
+/// Turns a round can last before it is called a draw, so that bots that
+/// never hit anything can't stall a run.
+pub const MAX_TURNS: u32 = 400;
+
+/// How a match between bots went.
+#[derive(Clone, Debug)]
+pub struct MatchReport {
+    /// Who won each round, or `None` for a draw.
+    pub rounds: Vec<Option<PlayerId>>,
+    /// Rounds won by each player, by `PlayerId`.
+    pub wins: Vec<u32>,
+    /// Turns taken over the whole match.
+    pub turns: u32,
+    /// Everything needed to watch the match again.
+    pub replay: Replay,
+}
+
+impl MatchReport {
+    /// The player who won the most rounds, or `None` if the lead is shared.
+    pub fn winner(&self) -> Option<PlayerId> {
+        let most = self.wins.iter().copied().max()?;
+        let mut leaders = (0..self.wins.len() as u8)
+            .map(PlayerId)
+            .filter(|player| self.wins[player.index()] == most);
+        match (leaders.next(), leaders.next()) {
+            (Some(player), None) => Some(player),
+            _ => None,
+        }
+    }
+}
+
+/// Why a match can't be played.
+#[derive(Clone, Debug, PartialEq)]
+pub enum MatchError {
+    Settings(SettingsError),
+    Map(MapError),
+    /// There must be one bot for each player in the settings.
+    Seats {
+        players: u8,
+        bots: usize,
+    },
+}
+
+impl fmt::Display for MatchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MatchError::Settings(error) => error.fmt(f),
+            MatchError::Map(error) => error.fmt(f),
+            MatchError::Seats { players, bots } => write!(
+                f,
+                "the settings are for {} players, but there are {} bots",
+                players, bots
+            ),
+        }
+    }
+}
+
+impl Error for MatchError {}
+
+impl From<SettingsError> for MatchError {
+    fn from(error: SettingsError) -> MatchError {
+        MatchError::Settings(error)
+    }
+}
+
+impl From<MapError> for MatchError {
+    fn from(error: MapError) -> MatchError {
+        MatchError::Map(error)
+    }
+}
+
+/// Plays every round of a match with `settings`, seeded with `seed`, between
+/// `bots` in turn order, on `map` if there is one. A bot whose move is
+/// refused, e.g. for a weapon it has run out of, loses the turn as if it had
+/// passed.
+pub fn play_match(
+    settings: &MatchSettings,
+    seed: u64,
+    map: Option<&Map>,
+    bots: &mut [Box<dyn Bot>],
+) -> Result<MatchReport, MatchError> {
+    let config = settings.config()?;
+    if bots.len() != config.players() as usize {
+        return Err(MatchError::Seats {
+            players: config.players(),
+            bots: bots.len(),
+        });
+    }
+    let mut report = MatchReport {
+        rounds: Vec::new(),
+        wins: vec![0; bots.len()],
+        turns: 0,
+        replay: Replay::new(seed, settings.clone(), map.cloned()),
+    };
+    for round in 0..config.rounds() {
+        let seed = round_seed(seed, round);
+        let mut game = match map {
+            Some(map) => Game::with_map(config.clone(), map, seed)?,
+            None => Game::new(config.clone(), seed),
+        };
+        for _ in 0..MAX_TURNS {
+            if game.is_over() {
+                break;
+            }
+            let player = game.active_player();
+            // a bot that can't fire what it chose loses the turn
+            if play_bot_turn(&mut game, bots[player.index()].as_mut()).is_err() {
+                game.skip_turn(player).expect("it is the player's turn");
+                game.run_to_next_turn();
+            }
+            report.turns += 1;
+        }
+        let winner = game.result().and_then(|result| result.winner());
+        if let Some(player) = winner {
+            report.wins[player.index()] += 1;
+        }
+        report.rounds.push(winner);
+        report.replay.record(&game);
+    }
+    Ok(report)
+}
@@ -0,0 +1,153 @@
+use super::{Config, Point, TerrainMask};
+
+/// Downward acceleration of projectiles unless the match settings choose
+/// otherwise, in px per unit of flight time squared.
+pub const GRAVITY: f64 = 0.001;
+
+// Far more ticks than any shot on the field can take.
+const MAX_TICKS: usize = 100_000;
+
+/// Length of one simulation tick, in ms of game time. The projectile only
+/// ever moves in whole ticks, so its path doesn't depend on the frame rate.
+pub const TICK_MS: f64 = 4.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Projectile {
+    position: Point,
+    // px per unit of flight time, with y pointing down like the canvas
+    vx: f64,
+    vy: f64,
+    // accelerations, in px per unit of flight time squared
+    gravity: f64,
+    wind: f64,
+}
+
+impl Projectile {
+    /// `speed` is the normalized launch power and `angle` is in radians,
+    /// measured the same way as the turret: 0 points backwards, 180° points at
+    /// the enemy. `facing` is 1.0 for a tank facing right and -1.0 for one
+    /// facing left. `gravity` pulls it down and `wind` pushes it sideways for
+    /// the whole flight; positive wind blows right.
+    pub fn launch(
+        origin: Point,
+        speed: f64,
+        angle: f64,
+        facing: f64,
+        gravity: f64,
+        wind: f64,
+    ) -> Projectile {
+        Projectile {
+            position: origin,
+            // negate to get the correct horizontal direction
+            vx: -facing * speed * libm::cos(angle),
+            vy: -speed * libm::sin(angle),
+            gravity,
+            wind,
+        }
+    }
+
+    pub fn position(&self) -> Point {
+        self.position
+    }
+
+    /// Speed in px per unit of flight time, as `(x, y)` with y pointing
+    /// down.
+    pub fn velocity(&self) -> (f64, f64) {
+        (self.vx, self.vy)
+    }
+
+    /// A copy flying `dvx` faster to the right, e.g. one of a MIRV's
+    /// warheads.
+    pub fn split(&self, dvx: f64) -> Projectile {
+        Projectile {
+            vx: self.vx + dvx,
+            ..*self
+        }
+    }
+
+    /// Puts the projectile at `position` without changing its velocity.
+    pub(crate) fn set_position(&mut self, position: Point) {
+        self.position = position;
+    }
+
+    /// Advances the projectile by `dt` units of flight time. The update is
+    /// exact for constant acceleration, so only the tick count matters.
+    pub fn step(&mut self, dt: f64) {
+        self.position.x += self.vx * dt + 0.5 * self.wind * dt * dt;
+        self.vx += self.wind * dt;
+        self.position.y += self.vy * dt + 0.5 * self.gravity * dt * dt;
+        self.vy += self.gravity * dt;
+    }
+}
+
+/// The path a projectile would take, as `trajectory` samples it.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Trajectory {
+    /// From the launch point on, the last being where it lands, where it
+    /// falls into the sea or the last point on the field before it leaves.
+    pub points: Vec<Point>,
+    /// Where it would land, or `None` if it would leave the field or fall
+    /// into the sea.
+    pub impact: Option<Point>,
+}
+
+/// Flies `projectile` tick by tick, exactly as a match would, over ground
+/// described by `mask` and any sea at `sea_level`. Returns where it would
+/// land, or `None` if it would leave the field or fall into the sea.
+pub fn predict_impact(
+    projectile: Projectile,
+    config: &Config,
+    mask: &TerrainMask,
+    sea_level: Option<f64>,
+) -> Option<Point> {
+    fly(projectile, config, mask, sea_level, |_, _| ())
+}
+
+/// Flies `projectile` as `predict_impact` does, noting where it is every
+/// `every` ticks along the way.
+pub fn trajectory(
+    projectile: Projectile,
+    config: &Config,
+    mask: &TerrainMask,
+    sea_level: Option<f64>,
+    every: usize,
+) -> Trajectory {
+    let mut points = vec![projectile.position()];
+    let mut last = projectile.position();
+    let impact = fly(projectile, config, mask, sea_level, |tick, point| {
+        if config.in_bounds(point) {
+            last = point;
+            if tick % every.max(1) == 0 {
+                points.push(point);
+            }
+        }
+    });
+    if points.last() != Some(&last) {
+        points.push(last);
+    }
+    Trajectory { points, impact }
+}
+
+// steps `projectile` until it lands, leaves the field or falls into the sea,
+// showing `visit` each tick's number, from 1, and position
+fn fly(
+    mut projectile: Projectile,
+    config: &Config,
+    mask: &TerrainMask,
+    sea_level: Option<f64>,
+    mut visit: impl FnMut(usize, Point),
+) -> Option<Point> {
+    let dt = config.projectile_speed_modifier() * TICK_MS;
+    for tick in 1..=MAX_TICKS {
+        projectile.step(dt);
+        let point = projectile.position();
+        visit(tick, point);
+        if !config.in_bounds(point) || sea_level.is_some_and(|sea_level| point.y > sea_level) {
+            return None;
+        }
+        if mask.is_solid(point) {
+            return Some(point);
+        }
+    }
+    None
+}
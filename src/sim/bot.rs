@@ -0,0 +1,213 @@
+//! Programmable players.
+//!
+//! A `Bot` looks at an `Observation` of the match and decides on an `Action`,
+//! or to pass.
+//! Each player in a match is driven by a `Controller`, which is either a human
+//! at the keyboard, a bot created by name from a `BotRegistry`, or a player
+//! at another computer.
+
+use super::{
+    predict_impact, trajectory, ComputerBot, Config, Difficulty, Game, GameError, MatchRng,
+    PlayerId, Point, ShotUpdate, Tank, TerrainMask, Trajectory, Weapon,
+};
+use std::fmt;
+
+/// A decision to fire: `power` in the units of the power box, `angle` in
+/// degrees measured the same way as the turret.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Action {
+    pub power: f64,
+    pub angle: f64,
+    pub weapon: Weapon,
+}
+
+/// A shot that has already been fired this match.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ShotResult {
+    pub player: PlayerId,
+    pub action: Action,
+    /// Where the projectile hit the ground, or `None` if it left the field.
+    pub impact: Option<Point>,
+}
+
+/// Everything a bot is allowed to know when it is its turn.
+#[derive(Clone, Debug)]
+pub struct Observation {
+    pub player: PlayerId,
+    pub config: Config,
+    /// The top of the ground in each column.
+    pub heights: Vec<f64>,
+    /// All of the ground, including caves under the surface.
+    pub mask: TerrainMask,
+    pub own_tank: Tank,
+    /// The other tanks still in the match.
+    pub enemies: Vec<Tank>,
+    /// The surface of the sea, in px down from the top, if there is one.
+    /// Shots that fall into it are lost.
+    pub sea_level: Option<f64>,
+    /// Horizontal force on projectiles, in px per unit of flight time
+    /// squared; positive blows right.
+    pub wind: f64,
+    /// Every shot so far, oldest first, by every player.
+    pub shots: Vec<ShotResult>,
+}
+
+impl Observation {
+    /// Where `action` would land if fired now from this player's tank, using
+    /// the same physics as the match.
+    pub fn predict_impact(&self, action: &Action) -> Option<Point> {
+        let projectile = self.own_tank.launch(action, &self.config, self.wind);
+        predict_impact(projectile, &self.config, &self.mask, self.sea_level)
+    }
+
+    /// The path `action` would take if fired now, sampled every `every`
+    /// ticks, as `predict_impact` flies it.
+    pub fn trajectory(&self, action: &Action, every: usize) -> Trajectory {
+        let projectile = self.own_tank.launch(action, &self.config, self.wind);
+        trajectory(projectile, &self.config, &self.mask, self.sea_level, every)
+    }
+
+    /// The living enemy closest to this player's tank, across the field.
+    pub fn nearest_enemy(&self) -> Option<&Tank> {
+        let own_x = self.own_tank.location().x();
+        self.enemies.iter().min_by(|a, b| {
+            let a = (a.location().x() - own_x).abs();
+            let b = (b.location().x() - own_x).abs();
+            a.total_cmp(&b)
+        })
+    }
+}
+
+pub trait Bot {
+    fn name(&self) -> &str;
+
+    /// The shot to fire on this turn, or `None` to pass it. This is how every
+    /// caller asks a bot for its move, so a bot that passes is never made to
+    /// fire.
+    fn decide(&mut self, observation: &Observation) -> Option<Action>;
+}
+
+/// Who drives one player's tank.
+pub enum Controller {
+    Human,
+    Bot(Box<dyn Bot>),
+    /// Plays over the network; its moves arrive through a `Lockstep`.
+    Remote,
+}
+
+impl Controller {
+    pub fn is_human(&self) -> bool {
+        match self {
+            Controller::Human => true,
+            Controller::Bot(_) | Controller::Remote => false,
+        }
+    }
+}
+
+impl fmt::Debug for Controller {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Controller::Human => write!(f, "Human"),
+            Controller::Bot(bot) => write!(f, "Bot({})", bot.name()),
+            Controller::Remote => write!(f, "Remote"),
+        }
+    }
+}
+
+type BotFactory = Box<dyn Fn(MatchRng) -> Box<dyn Bot>>;
+
+/// Named bots that a player can be assigned to.
+pub struct BotRegistry {
+    bots: Vec<(String, BotFactory)>,
+}
+
+impl BotRegistry {
+    /// A registry holding the built-in bots.
+    pub fn new() -> BotRegistry {
+        let mut registry = BotRegistry { bots: Vec::new() };
+        registry.register("lobber", |_| Box::new(Lobber));
+        for &difficulty in Difficulty::ALL.iter() {
+            registry.register(difficulty.name(), move |rng| {
+                Box::new(ComputerBot::new(difficulty, rng))
+            });
+        }
+        registry
+    }
+
+    /// Adds a bot, replacing any existing one of the same name. The factory
+    /// is handed the random stream the bot must draw from, so that its
+    /// choices are reproducible from the match seed.
+    pub fn register<F>(&mut self, name: &str, factory: F)
+    where
+        F: Fn(MatchRng) -> Box<dyn Bot> + 'static,
+    {
+        self.bots.retain(|(existing, _)| existing != name);
+        self.bots.push((name.to_string(), Box::new(factory)));
+    }
+
+    pub fn names(&self) -> Vec<&str> {
+        self.bots.iter().map(|(name, _)| name.as_str()).collect()
+    }
+
+    pub fn create(&self, name: &str, rng: MatchRng) -> Option<Box<dyn Bot>> {
+        self.bots
+            .iter()
+            .find(|(existing, _)| existing == name)
+            .map(|(_, factory)| factory(rng))
+    }
+
+    /// `"human"` or the name of a registered bot.
+    pub fn controller(&self, name: &str, rng: MatchRng) -> Option<Controller> {
+        if name == "human" {
+            return Some(Controller::Human);
+        }
+        self.create(name, rng).map(Controller::Bot)
+    }
+}
+
+impl Default for BotRegistry {
+    fn default() -> BotRegistry {
+        BotRegistry::new()
+    }
+}
+
+/// Asks `bot` for the active player's move, fires it and runs the match until
+/// the next turn starts. Returns how the shot ended; a bot that passes ends
+/// its turn as a shot off the field would, having hit nothing.
+pub fn play_bot_turn(game: &mut Game, bot: &mut dyn Bot) -> Result<ShotUpdate, GameError> {
+    let player = game.active_player();
+    match bot.decide(&game.observation(player)) {
+        Some(action) => game.fire(player, action)?,
+        None => game.skip_turn(player)?,
+    }
+    Ok(game.run_to_next_turn().unwrap_or(ShotUpdate::OutOfBounds))
+}
+
+// Always fires at 45° with the power that would reach the nearest enemy over
+// flat ground.
+struct Lobber;
+
+impl Bot for Lobber {
+    fn name(&self) -> &str {
+        "lobber"
+    }
+
+    fn decide(&mut self, observation: &Observation) -> Option<Action> {
+        let config = &observation.config;
+        let own = &observation.own_tank;
+        let enemy = match observation.nearest_enemy() {
+            Some(enemy) => enemy,
+            None => own,
+        };
+        let distance = enemy.location().x() - own.location().x();
+        // range = v² / g for a 45° shot, with time scaled by the speed modifier
+        let speed = (distance.abs() * config.gravity()).sqrt();
+        // angles past 90° fire the way the tank faces
+        let forwards = distance * own.facing() > 0.0;
+        Some(Action {
+            power: speed * config.power_normalizer(),
+            angle: if forwards { 135.0 } else { 45.0 },
+            weapon: Weapon::Missile,
+        })
+    }
+}
@@ -0,0 +1,159 @@
+//! Watching a match without playing in it.
+//!
+//! A relay follows each of its rooms with a `Broadcast`, playing every move
+//! into its own copy of the match, so that a spectator arriving mid-match can
+//! be handed a `Snapshot` of it as it stands rather than every move since
+//! the start. From there a `Spectator` plays the moves that follow into the
+//! spectator's copy, which emits the same `Event`s (turns starting, shots,
+//! impacts, damage and the end of each round) as the players' copies do.
+
+use super::{
+    net, round_seed, Game, Map, MatchSettings, Move, NetError, Phase, Replay, ServerMessage,
+    Snapshot, SnapshotError, Turn,
+};
+
+/// A match followed move by move from the start, all of its rounds, ready
+/// to be snapshotted between any two moves.
+#[derive(Clone, Debug)]
+pub struct Broadcast {
+    players: Vec<String>,
+    // the rounds before the one in `game`
+    replay: Replay,
+    wins: Vec<u32>,
+    game: Game,
+    turns: usize,
+}
+
+impl Broadcast {
+    /// The first round of the match `seed`, `settings` and `map` make,
+    /// between `players` as `Snapshot::players` names them.
+    pub fn new(
+        seed: u64,
+        settings: MatchSettings,
+        map: Option<Map>,
+        players: Vec<String>,
+    ) -> Result<Broadcast, SnapshotError> {
+        let config = settings.config()?;
+        let game = match &map {
+            Some(map) => Game::with_map(config, map, round_seed(seed, 0))?,
+            None => Game::new(config, round_seed(seed, 0)),
+        };
+        Ok(Broadcast {
+            wins: vec![0; players.len()],
+            players,
+            replay: Replay::new(seed, settings, map),
+            game,
+            turns: 0,
+        })
+    }
+
+    /// Moves played so far, counting every round's.
+    pub fn turns(&self) -> usize {
+        self.turns
+    }
+
+    /// The round in play, between turns.
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    /// Plays the next move of the match, moving on to the next round first
+    /// if this one is over. A move that doesn't fit the match is refused,
+    /// and the broadcast can't go on.
+    pub fn play(&mut self, turn: &Turn) -> Result<(), NetError> {
+        if self.game.is_over()
+            && self.replay.rounds.len() + 1 < self.game.config().rounds() as usize
+        {
+            self.replay.record(&self.game);
+            let config = self.game.config().clone();
+            let seed = round_seed(self.replay.seed, self.replay.rounds.len() as u32);
+            self.game = match &self.replay.map {
+                Some(map) => {
+                    Game::with_map(config, map, seed).expect("the map fitted the first round")
+                }
+                None => Game::new(config, seed),
+            };
+        }
+        net::play_turn(&mut self.game, self.turns, turn)?;
+        self.game.run_to_next_turn();
+        // nobody is watching this copy's events
+        self.game.drain_events();
+        self.turns += 1;
+        if let Some(player) = self.game.result().and_then(|result| result.winner()) {
+            self.wins[player.index()] += 1;
+        }
+        Ok(())
+    }
+
+    /// The match as it stands, for a spectator to start from.
+    pub fn snapshot(&self) -> Snapshot {
+        Snapshot::new(
+            self.players.clone(),
+            self.replay.clone(),
+            self.wins.clone(),
+            &self.game,
+        )
+        .expect("moves are played to the next turn")
+    }
+}
+
+/// A spectator's side of a networked match: the moves made since the
+/// snapshot it started from, played into its copy of the match one at a
+/// time. It never moves itself.
+///
+/// As with a `Lockstep`, when a round ends with moves still to come, the
+/// caller starts the next round.
+#[derive(Clone, Debug)]
+pub struct Spectator {
+    // moves in the snapshot, which come before `turns`
+    since: usize,
+    turns: Vec<Turn>,
+    // how many of `turns` are in the game
+    played: usize,
+}
+
+impl Spectator {
+    /// Follows the match from a snapshot taken after its first `since`
+    /// moves.
+    pub fn new(since: usize) -> Spectator {
+        Spectator {
+            since,
+            turns: Vec::new(),
+            played: 0,
+        }
+    }
+
+    /// Moves of the match so far, whether played yet or not.
+    pub fn turns(&self) -> usize {
+        self.since + self.turns.len()
+    }
+
+    /// Whether there are moves from the relay waiting for `apply`.
+    pub fn has_pending(&self) -> bool {
+        self.played < self.turns.len()
+    }
+
+    /// Takes in a message from the relay.
+    pub fn receive(&mut self, message: &ServerMessage) {
+        if let ServerMessage::Moves { from, moves } = message {
+            for (turn, &relayed) in (*from..).zip(moves.iter()) {
+                if turn == self.turns() {
+                    self.turns.push(relayed);
+                }
+            }
+        }
+    }
+
+    /// Plays the next move from the relay into `game`, if there is one and
+    /// the game is waiting on a player. Returns the move, or the reason the
+    /// match can't be followed any further.
+    pub fn apply(&mut self, game: &mut Game) -> Result<Option<Move>, NetError> {
+        let pending = match self.turns.get(self.played) {
+            Some(pending) if game.phase() == Phase::Aiming => *pending,
+            _ => return Ok(None),
+        };
+        net::play_turn(game, self.since + self.played, &pending)?;
+        self.played += 1;
+        Ok(Some(pending.made))
+    }
+}
@@ -0,0 +1,157 @@
+use super::{Inventory, MatchSettings, Point, TerrainSettings};
+use wasm_bindgen::prelude::*;
+
+/// Width of every tank, in px.
+pub const TANK_WIDTH: f64 = 10.0;
+
+#[wasm_bindgen]
+#[derive(Clone, Debug)]
+pub struct Config {
+    width: f64,
+    height: f64,
+    players: u8,
+    tank_width: f64,
+    tank_height: f64,
+    max_power: u16,
+    min_power: u16,
+    max_angle: u8,
+    min_angle: u8,
+    projectile_speed_modifier: f64,
+    projectile_size: f64,
+    power_normalizer: f64,
+    turret_length: f64,
+    tank_health: f64,
+    gravity: f64,
+    max_wind: f64,
+    angle_of_repose: f64,
+    terrain: TerrainSettings,
+    starting_inventory: Inventory,
+    rounds: u32,
+    aim_preview: bool,
+}
+
+#[wasm_bindgen]
+impl Config {
+    /// The config for `MatchSettings::default()`.
+    pub fn new() -> Config {
+        MatchSettings::default()
+            .config()
+            .expect("default settings are valid")
+    }
+
+    pub fn height(&self) -> f64 {
+        self.height
+    }
+    pub fn width(&self) -> f64 {
+        self.width
+    }
+    pub fn tank_width(&self) -> f64 {
+        self.tank_width
+    }
+    pub fn tank_height(&self) -> f64 {
+        self.tank_height
+    }
+    pub fn players(&self) -> u8 {
+        self.players
+    }
+    pub fn max_power(&self) -> u16 {
+        self.max_power
+    }
+    pub fn min_power(&self) -> u16 {
+        self.min_power
+    }
+    pub fn max_angle(&self) -> u8 {
+        self.max_angle
+    }
+    pub fn min_angle(&self) -> u8 {
+        self.min_angle
+    }
+    pub fn projectile_speed_modifier(&self) -> f64 {
+        self.projectile_speed_modifier
+    }
+    pub fn projectile_size(&self) -> f64 {
+        self.projectile_size
+    }
+    pub fn power_normalizer(&self) -> f64 {
+        self.power_normalizer
+    }
+    pub fn turret_length(&self) -> f64 {
+        self.turret_length
+    }
+    pub fn tank_health(&self) -> f64 {
+        self.tank_health
+    }
+    /// Downward acceleration of projectiles, in px per unit of flight time
+    /// squared.
+    pub fn gravity(&self) -> f64 {
+        self.gravity
+    }
+    /// Strongest wind a turn can have, in either direction, in px per unit
+    /// of flight time squared.
+    pub fn max_wind(&self) -> f64 {
+        self.max_wind
+    }
+    /// Steepest slope, in degrees, that loose earth comes to rest at.
+    pub fn angle_of_repose(&self) -> f64 {
+        self.angle_of_repose
+    }
+    pub fn rounds(&self) -> u32 {
+        self.rounds
+    }
+}
+
+impl Config {
+    // `settings` must have been validated
+    pub(crate) fn from_settings(settings: &MatchSettings, starting_inventory: Inventory) -> Config {
+        Config {
+            width: settings.width as f64,
+            height: settings.height as f64,
+            players: settings.players,
+            tank_height: 10.0,
+            tank_width: TANK_WIDTH,
+            max_power: 1000,
+            min_power: 0,
+            max_angle: 180,
+            min_angle: 0,
+            projectile_speed_modifier: 0.75,
+            projectile_size: 3.0,
+            power_normalizer: 200.0,
+            turret_length: 8.0,
+            tank_health: settings.tank_health,
+            gravity: settings.gravity,
+            max_wind: settings.max_wind,
+            angle_of_repose: settings.angle_of_repose,
+            terrain: settings.terrain.clone(),
+            starting_inventory,
+            rounds: settings.rounds,
+            aim_preview: settings.aim_preview,
+        }
+    }
+
+    /// How the ground is shaped at the start of each round.
+    pub fn terrain(&self) -> &TerrainSettings {
+        &self.terrain
+    }
+
+    /// Whether `Game::aim_preview` shows players where their shots will go.
+    pub fn aim_preview(&self) -> bool {
+        self.aim_preview
+    }
+
+    /// What each tank has to fire when the match starts.
+    pub fn starting_inventory(&self) -> &Inventory {
+        &self.starting_inventory
+    }
+
+    /// Whether a projectile at `point` is still in play. Anything above the
+    /// top edge is, since it will come back down.
+    pub fn in_bounds(&self, point: Point) -> bool {
+        point.y <= self.height && point.x > 0.0 && point.x < self.width
+    }
+}
+
+impl Default for Config {
+    fn default() -> Config {
+        Config::new()
+    }
+}
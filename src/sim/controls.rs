@@ -0,0 +1,281 @@
+//! Controls for humans taking turns on one computer.
+//!
+//! Each player has a `Keymap` from keys to `Command`s and an `Aim` the
+//! commands adjust, kept between their turns. Keys are named as the
+//! browser's `KeyboardEvent.key` names them, e.g. `"ArrowLeft"`, `"a"` or
+//! `" "` for the space bar, and keymaps are stored as JSON objects from key
+//! to command:
+//!
+//! ```json
+//! {"a": "angle-down", "d": "angle-up", "w": "power-up", "s": "power-down",
+//!  "q": "next-weapon", "f": "fire"}
+//! ```
+//!
+//! An `Aim` can also be set with a mouse or on a touch screen, by a `Drag`
+//! across the field.
+
+use super::{Action, Config, Inventory, Point, Weapon};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+
+/// Something a player can do with a key on their turn.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Command {
+    AngleUp,
+    AngleDown,
+    PowerUp,
+    PowerDown,
+    /// Picks the next weapon the tank has left.
+    NextWeapon,
+    /// Fires, or once a round is over starts the next one.
+    Fire,
+}
+
+impl Command {
+    pub const ALL: [Command; 6] = [
+        Command::AngleUp,
+        Command::AngleDown,
+        Command::PowerUp,
+        Command::PowerDown,
+        Command::NextWeapon,
+        Command::Fire,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Command::AngleUp => "angle-up",
+            Command::AngleDown => "angle-down",
+            Command::PowerUp => "power-up",
+            Command::PowerDown => "power-down",
+            Command::NextWeapon => "next-weapon",
+            Command::Fire => "fire",
+        }
+    }
+}
+
+/// Which keys do what for one player. Every command needs at least one key.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct Keymap {
+    keys: BTreeMap<String, Command>,
+}
+
+/// Why a `Keymap` can't be used.
+#[derive(Clone, Debug, PartialEq)]
+pub enum KeymapError {
+    /// The JSON couldn't be read.
+    Parse(String),
+    /// No key is bound to the command, so the player couldn't do it.
+    Unbound(Command),
+}
+
+impl fmt::Display for KeymapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            KeymapError::Parse(message) => write!(f, "invalid keymap: {}", message),
+            KeymapError::Unbound(command) => {
+                write!(f, "the keymap has no key for {}", command.name())
+            }
+        }
+    }
+}
+
+impl Error for KeymapError {}
+
+impl Keymap {
+    /// `bindings` as a keymap, if it binds every command.
+    pub fn new(bindings: &[(&str, Command)]) -> Result<Keymap, KeymapError> {
+        let keymap = Keymap {
+            keys: bindings
+                .iter()
+                .map(|&(key, command)| (key.to_string(), command))
+                .collect(),
+        };
+        keymap.validate()?;
+        Ok(keymap)
+    }
+
+    /// The arrow keys to aim, Tab to change weapon and the space bar to fire,
+    /// which every player starts with.
+    pub fn arrows() -> Keymap {
+        Keymap::new(&[
+            ("ArrowRight", Command::AngleUp),
+            ("ArrowLeft", Command::AngleDown),
+            ("ArrowUp", Command::PowerUp),
+            ("ArrowDown", Command::PowerDown),
+            ("Tab", Command::NextWeapon),
+            (" ", Command::Fire),
+        ])
+        .expect("every command is bound")
+    }
+
+    /// W, A, S and D to aim, Q to change weapon and F to fire.
+    pub fn letters() -> Keymap {
+        Keymap::new(&[
+            ("d", Command::AngleUp),
+            ("a", Command::AngleDown),
+            ("w", Command::PowerUp),
+            ("s", Command::PowerDown),
+            ("q", Command::NextWeapon),
+            ("f", Command::Fire),
+        ])
+        .expect("every command is bound")
+    }
+
+    pub fn from_json(text: &str) -> Result<Keymap, KeymapError> {
+        let keymap: Keymap =
+            serde_json::from_str(text).map_err(|error| KeymapError::Parse(error.to_string()))?;
+        keymap.validate()?;
+        Ok(keymap)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("keymaps are always valid JSON")
+    }
+
+    fn validate(&self) -> Result<(), KeymapError> {
+        match Command::ALL
+            .iter()
+            .find(|command| !self.keys.values().any(|bound| bound == *command))
+        {
+            Some(&command) => Err(KeymapError::Unbound(command)),
+            None => Ok(()),
+        }
+    }
+
+    /// What pressing `key` does. Letters work with Shift or Caps Lock too.
+    pub fn command(&self, key: &str) -> Option<Command> {
+        self.keys.get(key).copied().or_else(|| {
+            let mut chars = key.chars();
+            match (chars.next(), chars.next()) {
+                (Some(letter), None) => self.keys.get(&letter.to_lowercase().to_string()).copied(),
+                _ => None,
+            }
+        })
+    }
+
+    /// The keys bound to `command`, in order.
+    pub fn keys(&self, command: Command) -> Vec<&str> {
+        self.keys
+            .iter()
+            .filter(|(_, &bound)| bound == command)
+            .map(|(key, _)| key.as_str())
+            .collect()
+    }
+}
+
+impl Default for Keymap {
+    fn default() -> Keymap {
+        Keymap::arrows()
+    }
+}
+
+/// Where a player is aiming, and with what, kept from one of their turns to
+/// the next.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aim {
+    pub power: f64,
+    /// In degrees, as `Action::angle`.
+    pub angle: f64,
+    pub weapon: Weapon,
+}
+
+impl Default for Aim {
+    fn default() -> Aim {
+        Aim {
+            power: 200.0,
+            angle: 100.0,
+            weapon: Weapon::Missile,
+        }
+    }
+}
+
+impl Aim {
+    /// Carries out `command`, keeping power and angle within the limits in
+    /// `config` and picking weapons from those `inventory` has left. Firing
+    /// doesn't change the aim.
+    pub fn apply(&mut self, command: Command, config: &Config, inventory: &Inventory) {
+        let (min_power, max_power) = (config.min_power() as f64, config.max_power() as f64);
+        let (min_angle, max_angle) = (config.min_angle() as f64, config.max_angle() as f64);
+        match command {
+            Command::AngleUp => self.angle = (self.angle + 1.0).min(max_angle),
+            Command::AngleDown => self.angle = (self.angle - 1.0).max(min_angle),
+            Command::PowerUp => self.power = (self.power + 1.0).min(max_power),
+            Command::PowerDown => self.power = (self.power - 1.0).max(min_power),
+            Command::NextWeapon => {
+                let current = Weapon::ALL
+                    .iter()
+                    .position(|&weapon| weapon == self.weapon)
+                    .unwrap_or(0);
+                // the missile never runs out, so there is always one
+                self.weapon = (1..=Weapon::ALL.len())
+                    .map(|step| Weapon::ALL[(current + step) % Weapon::ALL.len()])
+                    .find(|&weapon| inventory.has(weapon))
+                    .unwrap_or_default();
+            }
+            Command::Fire => (),
+        }
+    }
+
+    pub fn action(&self) -> Action {
+        Action {
+            power: self.power,
+            angle: self.angle,
+            weapon: self.weapon,
+        }
+    }
+}
+
+/// Aiming by dragging a mouse or finger across the field from wherever it
+/// went down: the turret points the way it has been dragged, and the
+/// further the drag, the harder the shot.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Drag {
+    pub from: Point,
+}
+
+impl Drag {
+    /// How far to drag, in pixels, for a shot at full power.
+    pub const FULL_POWER: f64 = 200.0;
+    /// Drags no longer than this, in pixels, don't aim, so that a tap
+    /// doesn't fire.
+    pub const DEAD_ZONE: f64 = 6.0;
+
+    pub fn new(from: Point) -> Drag {
+        Drag { from }
+    }
+
+    /// Whether the pointer at `to` has been dragged far enough to aim.
+    pub fn is_aiming(&self, to: Point) -> bool {
+        self.length(to) > Drag::DEAD_ZONE
+    }
+
+    /// Points and powers `aim` for the pointer at `to`, for a tank facing
+    /// `facing` (as `Tank::facing`), keeping within the limits in `config`.
+    /// Leaves it as it was if the drag is too short to aim.
+    pub fn apply(&self, to: Point, facing: f64, config: &Config, aim: &mut Aim) {
+        if !self.is_aiming(to) {
+            return;
+        }
+        let (min_power, max_power) = (config.min_power() as f64, config.max_power() as f64);
+        let (min_angle, max_angle) = (config.min_angle() as f64, config.max_angle() as f64);
+        // the turret's tip is `facing` times the angle's cosine behind its
+        // base, and up is towards the top of the field
+        let (dx, dy) = (to.x - self.from.x, to.y - self.from.y);
+        let mut angle = libm::atan2(-dy, -dx * facing).to_degrees();
+        // dragging down and back points as far back as the turret goes
+        if angle < -90.0 {
+            angle += 360.0;
+        }
+        aim.angle = angle.round().max(min_angle).min(max_angle);
+        let power = self.length(to) / Drag::FULL_POWER * max_power;
+        aim.power = power.round().max(min_power).min(max_power);
+    }
+
+    fn length(&self, to: Point) -> f64 {
+        libm::hypot(to.x - self.from.x, to.y - self.from.y)
+    }
+}
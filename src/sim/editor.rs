@@ -0,0 +1,162 @@
+//! Building maps by hand.
+
+use super::{
+    Config, Game, Hazard, Map, MapError, MatchSettings, PlayerId, Point, Terrain, SKY_COLORS,
+    TERRAIN_COLORS,
+};
+
+// what a mine placed in the editor does when it goes off
+const MINE_RADIUS: f64 = 25.0;
+const MINE_DAMAGE: f64 = 30.0;
+// how close, in px, a click has to be to a mine or a tank to pick it up
+const PICK_DISTANCE: f64 = 8.0;
+
+/// A map being edited: its ground, colors, spawns and mines.
+///
+/// It is kept as the `Terrain` the map makes, so that it can be drawn like a
+/// match and tried out with `test_game` at any point. Each edit returns the
+/// range of columns that need redrawing.
+#[derive(Clone, Debug)]
+pub struct MapEditor {
+    settings: MatchSettings,
+    config: Config,
+    terrain: Terrain,
+}
+
+impl MapEditor {
+    /// Starts editing `map`, with a tank on each of its spawns.
+    pub fn new(map: &Map) -> Result<MapEditor, MapError> {
+        map.validate()?;
+        let settings = MatchSettings {
+            width: map.width,
+            height: map.height,
+            players: map.spawns.len() as u8,
+            ..MatchSettings::default()
+        };
+        let config = settings
+            .config()
+            .map_err(|error| MapError::Invalid(error.to_string()))?;
+        map.fits(&config)?;
+        Ok(MapEditor {
+            settings,
+            terrain: Terrain::from_map(&config, map),
+            config,
+        })
+    }
+
+    /// The settings `test_game` plays with: the defaults, for the map's
+    /// field and players.
+    pub fn settings(&self) -> &MatchSettings {
+        &self.settings
+    }
+
+    /// A match config for the map's field and players.
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn terrain(&self) -> &Terrain {
+        &self.terrain
+    }
+
+    /// Fills a disc of earth of `radius` centered on `point`.
+    pub fn paint(&mut self, point: Point, radius: f64) -> (usize, usize) {
+        self.terrain.add_dirt(point, radius)
+    }
+
+    /// Clears a disc of earth of `radius` centered on `point`.
+    pub fn erase(&mut self, point: Point, radius: f64) -> (usize, usize) {
+        self.terrain.blast(point, radius)
+    }
+
+    /// The player whose tank is at `point`, to drag its spawn around.
+    pub fn spawn_at(&self, point: Point) -> Option<PlayerId> {
+        self.terrain
+            .tanks()
+            .iter()
+            .find(|tank| {
+                let center = tank.center();
+                (center.x - point.x).hypot(center.y - point.y) <= PICK_DISTANCE
+            })
+            .map(|tank| tank.player())
+    }
+
+    /// Moves `player`'s spawn to have the left edge of its tank at `x`, or as
+    /// close as fits on the field. The tank sits on top of the ground there.
+    pub fn move_spawn(&mut self, player: PlayerId, x: f64) -> (usize, usize) {
+        let tank = self.terrain.tank(player);
+        let from = tank.location().x;
+        let x = x.round().clamp(0.0, self.config.width() - tank.width());
+        self.terrain.move_tank(player, x);
+        self.around(from.min(x), from.max(x) + self.config.tank_width())
+    }
+
+    /// Places a mine at `point`, or takes away the one already there. Mines
+    /// can't be placed off the field.
+    pub fn toggle_mine(&mut self, point: Point) -> Result<(usize, usize), MapError> {
+        let existing = self.terrain.hazards().iter().position(|hazard| {
+            let at = hazard.position();
+            (at.x - point.x).hypot(at.y - point.y) <= PICK_DISTANCE
+        });
+        let mine = match existing {
+            Some(index) => self.terrain.remove_hazard(index),
+            None => {
+                let on_field = point.x >= 0.0
+                    && point.x < self.config.width()
+                    && point.y >= 0.0
+                    && point.y < self.config.height();
+                if !on_field {
+                    return Err(MapError::Invalid(format!(
+                        "a mine at ({}, {}) would be off the field",
+                        point.x, point.y
+                    )));
+                }
+                let mine = Hazard::Mine {
+                    x: point.x,
+                    y: point.y,
+                    radius: MINE_RADIUS,
+                    damage: MINE_DAMAGE,
+                };
+                self.terrain.add_hazard(mine);
+                mine
+            }
+        };
+        let x = mine.position().x;
+        Ok(self.around(x, x))
+    }
+
+    /// Colors the ground and the sky, each picked from `TERRAIN_COLORS` and
+    /// `SKY_COLORS`.
+    pub fn set_colors(&mut self, color: &str, sky_color: &str) -> Result<(), MapError> {
+        if !TERRAIN_COLORS.contains(&color) || !SKY_COLORS.contains(&sky_color) {
+            return Err(MapError::Invalid(format!(
+                "{} on {} isn't from the palette",
+                color, sky_color
+            )));
+        }
+        self.terrain.set_colors(color, sky_color);
+        Ok(())
+    }
+
+    /// The map as it is now, called `name`, checked as `Map::from_json`
+    /// would check it.
+    pub fn to_map(&self, name: &str) -> Result<Map, MapError> {
+        let map = Map::from_terrain(name, &self.terrain);
+        map.validate()?;
+        Ok(map)
+    }
+
+    /// A match on the map as it is now, to try shots out on.
+    pub fn test_game(&self, seed: u64) -> Result<Game, MapError> {
+        Game::with_map(self.config.clone(), &self.to_map("test")?, seed)
+    }
+
+    // the columns from `min` to `max`, with room for a turret or mine marker
+    // either side
+    fn around(&self, min: f64, max: f64) -> (usize, usize) {
+        let margin = self.config.turret_length() + 2.0;
+        let min = (min - margin).max(0.0) as usize;
+        let max = ((max + margin).max(0.0) as usize).min(self.terrain.width());
+        (min, max)
+    }
+}
@@ -0,0 +1,807 @@
+use super::{
+    match_rng, trajectory, Action, Config, Effect, Event, GameError, Hazard, Map, MapError,
+    MatchRng, Move, Observation, Phase, Point, SavedRound, SavedTank, ShotResult, SnapshotError,
+    Step, Stream, Tank, Terrain, Trajectory, Warhead, Weapon, TICK_MS,
+};
+use rand::Rng;
+use wasm_bindgen::prelude::*;
+
+// width of the tunnel a digger leaves behind it
+const TRENCH_WIDTH: f64 = 6.0;
+// px a tank can drop when the ground under it is blown away without harm
+const SAFE_FALL: f64 = 5.0;
+const FALL_DAMAGE_PER_PX: f64 = 0.5;
+
+/// One of the players in a match, numbered from 0 in turn order.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PlayerId(pub u8);
+
+impl PlayerId {
+    pub fn index(self) -> usize {
+        self.0 as usize
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DamageCause {
+    Blast,
+    /// The ground under the tank was blown away and it dropped.
+    Fall,
+    /// Napalm reached it.
+    Fire,
+    /// It dropped into the sea.
+    Sunk,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Damage {
+    pub player: PlayerId,
+    pub amount: f64,
+    pub cause: DamageCause,
+}
+
+/// Where a shot hit, which terrain columns it changed (or, for napalm, set
+/// alight) and who it hurt.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Impact {
+    pub weapon: Weapon,
+    pub point: Point,
+    pub min_index: usize,
+    pub max_index: usize,
+    pub damage: Vec<Damage>,
+}
+
+/// How a finished match ended.
+#[wasm_bindgen]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MatchResult {
+    winner: Option<PlayerId>,
+}
+
+#[wasm_bindgen]
+impl MatchResult {
+    /// The player left standing, or `None` if the last tanks were destroyed
+    /// by the same shot.
+    pub fn winner(&self) -> Option<PlayerId> {
+        self.winner
+    }
+
+    pub fn is_draw(&self) -> bool {
+        self.winner.is_none()
+    }
+}
+
+/// What happened to the shot during one call to `Game::step`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ShotUpdate {
+    /// Still going; where its first warhead is.
+    Flying(Point),
+    Impact(Impact),
+    OutOfBounds,
+}
+
+/// A match in progress: the world plus whose turn it is and any shot in the
+/// air.
+///
+/// Play moves through the `Phase`s in order: the active player fires from
+/// `Aiming`, then `step` carries the match through the flight, the explosion
+/// and the hand-over to the next player still alive. Every transition is
+/// recorded as an `Event`, and actions that are not allowed in the current
+/// phase are refused with a `GameError`.
+///
+/// Everything random is drawn from `seed`, and the projectile moves in fixed
+/// ticks, so the same seed and the same inputs always play out identically.
+#[derive(Clone, Debug)]
+pub struct Game {
+    config: Config,
+    seed: u64,
+    rng: MatchRng,
+    terrain: Terrain,
+    active_player: PlayerId,
+    wind: f64,
+    phase: Phase,
+    warheads: Vec<Warhead>,
+    // columns where the ground may still be moving after the shot
+    unsettled: Option<(usize, usize)>,
+    // where each tank stood when the current landslide started
+    landslide_from: Option<Vec<f64>>,
+    shots: Vec<ShotResult>,
+    moves: Vec<Move>,
+    result: Option<MatchResult>,
+    events: Vec<Event>,
+}
+
+impl Game {
+    pub fn new(config: Config, seed: u64) -> Game {
+        let terrain = Terrain::new(
+            &config,
+            &mut match_rng(seed, Stream::Terrain),
+            &mut match_rng(seed, Stream::Spawn),
+        );
+        Game::start(config, seed, terrain)
+    }
+
+    /// A match on `map` instead of terrain generated from `seed`, which still
+    /// decides everything else. The map must fit the config; see
+    /// `Map::fits`.
+    pub fn with_map(config: Config, map: &Map, seed: u64) -> Result<Game, MapError> {
+        map.fits(&config)?;
+        let terrain = Terrain::from_map(&config, map);
+        Ok(Game::start(config, seed, terrain))
+    }
+
+    fn start(config: Config, seed: u64, terrain: Terrain) -> Game {
+        let mut game = Game {
+            config,
+            seed,
+            rng: match_rng(seed, Stream::Events),
+            terrain,
+            active_player: PlayerId(0),
+            wind: 0.0,
+            phase: Phase::Aiming,
+            warheads: Vec::new(),
+            unsettled: None,
+            landslide_from: None,
+            shots: Vec::new(),
+            moves: Vec::new(),
+            result: None,
+            events: Vec::new(),
+        };
+        game.start_turn();
+        game
+    }
+
+    /// The round a `Snapshot` saved, exactly as it was. `seed` is the one
+    /// the round was started with.
+    pub(crate) fn restore(
+        config: Config,
+        seed: u64,
+        saved: &SavedRound,
+    ) -> Result<Game, SnapshotError> {
+        let invalid = |message: String| Err(SnapshotError::Invalid(message));
+        saved.field.fits(&config)?;
+        if saved.tanks.len() != config.players() as usize {
+            return invalid(format!(
+                "{} tanks for {} players",
+                saved.tanks.len(),
+                config.players()
+            ));
+        }
+        let fired = saved
+            .moves
+            .iter()
+            .filter(|made| matches!(made, Move::Fire { .. }))
+            .count();
+        if saved.impacts.len() != fired {
+            return invalid(format!(
+                "{} impacts for {} shots",
+                saved.impacts.len(),
+                fired
+            ));
+        }
+
+        let mut terrain = Terrain::from_map(&config, &saved.field);
+        for (i, tank) in saved.tanks.iter().enumerate() {
+            let inventory = tank.inventory()?;
+            let restored = terrain.tank_mut(PlayerId(i as u8));
+            restored.set_ground(tank.y);
+            restored.set_health(tank.health.max(0.0).min(restored.max_health()));
+            *restored.inventory_mut() = inventory;
+        }
+        let mut impacts = saved.impacts.iter();
+        let mut shots = Vec::new();
+        for made in saved.moves.iter() {
+            saved.player(made.player().0)?;
+            if let Move::Fire { player, action } = *made {
+                let impact = impacts.next().copied().flatten();
+                shots.push(ShotResult {
+                    player,
+                    action,
+                    impact: impact.map(|[x, y]| Point::new(x, y)),
+                });
+            }
+        }
+        let active_player = saved.player(saved.active_player)?;
+        let result = match (saved.over, saved.winner) {
+            (true, winner) => Some(MatchResult {
+                winner: winner.map(|index| saved.player(index)).transpose()?,
+            }),
+            (false, None) => None,
+            (false, Some(_)) => return invalid("a winner of a round not over".to_string()),
+        };
+        let mut rng = match_rng(seed, Stream::Events);
+        rng.set_word_pos(saved.events_drawn as u128);
+
+        let mut game = Game {
+            config,
+            seed,
+            rng,
+            terrain,
+            active_player,
+            wind: saved.wind,
+            phase: if saved.over {
+                Phase::Over
+            } else {
+                Phase::Aiming
+            },
+            warheads: Vec::new(),
+            unsettled: None,
+            landslide_from: None,
+            shots,
+            moves: saved.moves.clone(),
+            result,
+            events: Vec::new(),
+        };
+        // so that a front-end shows the turn as it would a new one
+        if !saved.over {
+            game.events.push(Event::TurnStarted {
+                player: game.active_player,
+                wind: game.wind,
+            });
+        }
+        Ok(game)
+    }
+
+    // the round as a `Snapshot` stores it, if it is between turns
+    pub(crate) fn save(&self) -> Option<SavedRound> {
+        if self.phase != Phase::Aiming && self.phase != Phase::Over {
+            return None;
+        }
+        let tanks = self
+            .terrain
+            .tanks()
+            .iter()
+            .map(|tank| SavedTank {
+                y: tank.location().y,
+                health: tank.health(),
+                ammo: Weapon::ALL
+                    .iter()
+                    .filter_map(|&weapon| {
+                        let count = tank.inventory().count(weapon)?;
+                        Some((weapon.name().to_string(), count))
+                    })
+                    .collect(),
+            })
+            .collect();
+        Some(SavedRound {
+            field: Map::from_terrain("saved", &self.terrain),
+            tanks,
+            active_player: self.active_player.0,
+            wind: self.wind,
+            events_drawn: self.rng.get_word_pos() as u64,
+            moves: self.moves.clone(),
+            impacts: self
+                .shots
+                .iter()
+                .map(|shot| shot.impact.map(|point| [point.x, point.y]))
+                .collect(),
+            over: self.phase == Phase::Over,
+            winner: self
+                .result
+                .and_then(|result| result.winner)
+                .map(|player| player.0),
+        })
+    }
+
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    pub fn config(&self) -> &Config {
+        &self.config
+    }
+
+    pub fn terrain(&self) -> &Terrain {
+        &self.terrain
+    }
+
+    pub fn active_player(&self) -> PlayerId {
+        self.active_player
+    }
+
+    pub fn active_tank(&self) -> &Tank {
+        self.terrain.tank(self.active_player)
+    }
+
+    /// Every player in the match, in turn order, including any knocked out.
+    pub fn players(&self) -> impl Iterator<Item = PlayerId> {
+        (0..self.terrain.tanks().len() as u8).map(PlayerId)
+    }
+
+    /// Horizontal force on projectiles fired this turn, in px per unit of
+    /// flight time squared; positive blows right. It changes every turn,
+    /// within `Config::max_wind`.
+    pub fn wind(&self) -> f64 {
+        self.wind
+    }
+
+    pub fn phase(&self) -> Phase {
+        self.phase
+    }
+
+    pub fn projectile_in_flight(&self) -> bool {
+        self.phase == Phase::InFlight
+    }
+
+    /// Where each live piece of the current shot is.
+    pub fn projectile_positions(&self) -> Vec<Point> {
+        self.warheads.iter().map(Warhead::position).collect()
+    }
+
+    /// How the match ended, once at most one tank is left.
+    pub fn result(&self) -> Option<MatchResult> {
+        self.result
+    }
+
+    pub fn is_over(&self) -> bool {
+        self.phase == Phase::Over
+    }
+
+    /// Takes the events emitted since the last call, oldest first.
+    pub fn drain_events(&mut self) -> Vec<Event> {
+        std::mem::take(&mut self.events)
+    }
+
+    /// Every shot fired so far, oldest first.
+    pub fn shots(&self) -> &[ShotResult] {
+        &self.shots
+    }
+
+    /// Every move made so far, oldest first, as a `Replay` records them.
+    pub fn moves(&self) -> &[Move] {
+        &self.moves
+    }
+
+    /// A fingerprint of everything the rest of the match depends on: the
+    /// ground, the tanks, whose turn it is, the wind and the random draws
+    /// made so far. Two games that will play a move the same way have the
+    /// same checksum, so comparing them between computers catches a match
+    /// that has diverged.
+    pub fn checksum(&self) -> u64 {
+        // 64-bit FNV-1a, which is specified, unlike `std`'s hasher
+        let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+        let mut feed = |value: u64| {
+            for byte in value.to_le_bytes().iter() {
+                hash = (hash ^ *byte as u64).wrapping_mul(0x0100_0000_01b3);
+            }
+        };
+        let mask = self.terrain.mask();
+        for x in 0..mask.width() {
+            for (start, end, solid) in mask.runs(x) {
+                feed(start as u64);
+                feed(end as u64);
+                feed(solid as u64);
+            }
+        }
+        for tank in self.terrain.tanks() {
+            feed(tank.location().x.to_bits());
+            feed(tank.location().y.to_bits());
+            feed(tank.health().to_bits());
+            for &weapon in Weapon::ALL.iter() {
+                feed(tank.inventory().count(weapon).map_or(u64::MAX, u64::from));
+            }
+        }
+        feed(self.active_player.0 as u64);
+        feed(self.wind.to_bits());
+        feed(self.phase as u64);
+        feed(self.rng.get_word_pos() as u64);
+        hash
+    }
+
+    /// What `player` gets to see when deciding its next shot.
+    pub fn observation(&self, player: PlayerId) -> Observation {
+        Observation {
+            player,
+            config: self.config.clone(),
+            heights: self.terrain.heights().to_vec(),
+            mask: self.terrain.mask().clone(),
+            sea_level: self.terrain.sea_level(),
+            own_tank: self.terrain.tank(player).clone(),
+            enemies: self
+                .terrain
+                .tanks()
+                .iter()
+                .filter(|tank| tank.player() != player && !tank.is_destroyed())
+                .cloned()
+                .collect(),
+            wind: self.wind,
+            shots: self.shots.clone(),
+        }
+    }
+
+    /// Launches a projectile from `player`'s turret, if it is that player's
+    /// turn to aim and it has the weapon left. Power and angle are clamped to
+    /// the limits in the config.
+    pub fn fire(&mut self, player: PlayerId, action: Action) -> Result<(), GameError> {
+        self.check_can_act(player)?;
+        if !self.terrain.tank(player).inventory().has(action.weapon) {
+            return Err(GameError::OutOfAmmo {
+                weapon: action.weapon,
+            });
+        }
+        let action = self.clamp(action);
+        self.shots.push(ShotResult {
+            player,
+            action,
+            impact: None,
+        });
+        self.moves.push(Move::Fire { player, action });
+
+        self.terrain
+            .tank_mut(player)
+            .inventory_mut()
+            .take(action.weapon);
+        let projectile = self.active_tank().launch(&action, &self.config, self.wind);
+        self.warheads = vec![Warhead::new(action.weapon, projectile)];
+        self.phase = Phase::InFlight;
+        self.events.push(Event::ShotFired { player, action });
+        Ok(())
+    }
+
+    /// Where `action` would go if the active player fired it now, sampled
+    /// every `every` ticks. Only the shot's flight up to the first thing it
+    /// hits is followed, not what its weapon does after. `None` unless the
+    /// settings allow aim previews and the player is aiming.
+    pub fn aim_preview(&self, action: &Action, every: usize) -> Option<Trajectory> {
+        if !self.config.aim_preview() || self.phase != Phase::Aiming {
+            return None;
+        }
+        let action = self.clamp(*action);
+        let projectile = self.active_tank().launch(&action, &self.config, self.wind);
+        Some(trajectory(
+            projectile,
+            &self.config,
+            self.terrain.mask(),
+            self.terrain.sea_level(),
+            every,
+        ))
+    }
+
+    // `action` with its power and angle within the limits
+    fn clamp(&self, action: Action) -> Action {
+        Action {
+            power: clamp_or_min(
+                action.power,
+                self.config.min_power() as f64,
+                self.config.max_power() as f64,
+            ),
+            angle: clamp_or_min(
+                action.angle,
+                self.config.min_angle() as f64,
+                self.config.max_angle() as f64,
+            ),
+            ..action
+        }
+    }
+
+    /// Gives up `player`'s turn without firing.
+    pub fn skip_turn(&mut self, player: PlayerId) -> Result<(), GameError> {
+        self.check_can_act(player)?;
+        self.moves.push(Move::Skip { player });
+        self.phase = Phase::TurnTransition;
+        self.events.push(Event::TurnEnded { player });
+        Ok(())
+    }
+
+    fn check_can_act(&self, player: PlayerId) -> Result<(), GameError> {
+        match self.phase {
+            Phase::Over => Err(GameError::MatchOver),
+            _ if player != self.active_player => Err(GameError::NotYourTurn {
+                active: self.active_player,
+            }),
+            Phase::Aiming => Ok(()),
+            Phase::InFlight | Phase::Resolving | Phase::TurnTransition => {
+                Err(GameError::AlreadyFired)
+            }
+        }
+    }
+
+    /// Advances the match by one tick of `TICK_MS`. Returns what the
+    /// projectile did if one was in flight; nothing happens while aiming or
+    /// once the match is over.
+    pub fn step(&mut self) -> Option<ShotUpdate> {
+        match self.phase {
+            Phase::InFlight => Some(self.step_warheads()),
+            Phase::Resolving => {
+                if !self.settle() {
+                    self.resolve();
+                }
+                None
+            }
+            Phase::TurnTransition => {
+                self.active_player = self.next_player();
+                self.start_turn();
+                None
+            }
+            Phase::Aiming | Phase::Over => None,
+        }
+    }
+
+    /// Steps until the match is waiting on a player to act again, or is over.
+    /// Returns the last impact of the shot, or how it ended if nothing was
+    /// hit, if one was in flight.
+    pub fn run_to_next_turn(&mut self) -> Option<ShotUpdate> {
+        let mut last = None;
+        while self.phase != Phase::Aiming && self.phase != Phase::Over {
+            match self.step() {
+                Some(update @ ShotUpdate::Impact(_)) => last = Some(update),
+                Some(update) if !matches!(last, Some(ShotUpdate::Impact(_))) => last = Some(update),
+                _ => (),
+            }
+        }
+        last
+    }
+
+    fn start_turn(&mut self) {
+        let max_wind = self.config.max_wind();
+        self.wind = if max_wind > 0.0 {
+            self.rng.gen_range(-max_wind, max_wind)
+        } else {
+            0.0
+        };
+        self.phase = Phase::Aiming;
+        self.events.push(Event::TurnStarted {
+            player: self.active_player,
+            wind: self.wind,
+        });
+    }
+
+    // the first player after the active one, in turn order, still alive
+    fn next_player(&self) -> PlayerId {
+        let count = self.terrain.tanks().len();
+        (1..=count)
+            .map(|offset| PlayerId(((self.active_player.index() + offset) % count) as u8))
+            .find(|&player| !self.terrain.tank(player).is_destroyed())
+            .unwrap_or(self.active_player)
+    }
+
+    fn step_warheads(&mut self) -> ShotUpdate {
+        let dt = self.config.projectile_speed_modifier() * TICK_MS;
+        let mut update = None;
+        let mut live = Vec::new();
+        for mut warhead in std::mem::take(&mut self.warheads) {
+            match warhead.step(dt, &self.config, &self.terrain) {
+                Step::Moved => live.push(warhead),
+                Step::Split(parts) => live.extend(parts),
+                Step::Detonated { point, entry } => {
+                    if let Some(record) = self.shots.last_mut() {
+                        record.impact.get_or_insert(point);
+                    }
+                    let impact = self.detonate(warhead.weapon(), point, entry);
+                    self.unsettled = Some(match self.unsettled {
+                        Some((min, max)) => (min.min(impact.min_index), max.max(impact.max_index)),
+                        None => (impact.min_index, impact.max_index),
+                    });
+                    self.events.push(Event::Impact(impact.clone()));
+                    update = Some(ShotUpdate::Impact(impact));
+                }
+                Step::Lost => self.events.push(Event::ShotLost {
+                    player: self.active_player,
+                }),
+            }
+        }
+        self.warheads = live;
+        if self.warheads.is_empty() {
+            self.phase = Phase::Resolving;
+        }
+        update.unwrap_or_else(|| match self.warheads.first() {
+            Some(warhead) => ShotUpdate::Flying(warhead.position()),
+            None => ShotUpdate::OutOfBounds,
+        })
+    }
+
+    // moves the ground on by one tick of a landslide; false once it is at
+    // rest and any fall damage has been dealt
+    fn settle(&mut self) -> bool {
+        let (min_index, max_index) = match self.unsettled {
+            Some(columns) => columns,
+            None => return false,
+        };
+        let ground: Vec<f64> = self.tank_grounds();
+        let max_slope = libm::tan(self.config.angle_of_repose().to_radians());
+        match self.terrain.settle((min_index, max_index), max_slope) {
+            Some((min_index, max_index)) => {
+                self.landslide_from.get_or_insert(ground);
+                // the slopes next to what moved may now be too steep
+                let width = self.terrain.width();
+                self.unsettled = Some((min_index.saturating_sub(1), (max_index + 1).min(width)));
+                self.events.push(Event::Landslide {
+                    min_index,
+                    max_index,
+                });
+                true
+            }
+            None => {
+                self.unsettled = None;
+                if let Some(before) = self.landslide_from.take() {
+                    let mut damage = self.fall_damage(&before);
+                    damage.extend(self.sink());
+                    self.events.push(Event::Settled { damage });
+                }
+                false
+            }
+        }
+    }
+
+    fn tank_grounds(&self) -> Vec<f64> {
+        self.terrain
+            .tanks()
+            .iter()
+            .map(|tank| tank.location().y)
+            .collect()
+    }
+
+    // hurts each tank that has dropped too far since it stood at `before`
+    fn fall_damage(&mut self, before: &[f64]) -> Vec<Damage> {
+        let damage: Vec<Damage> = self
+            .terrain
+            .tanks()
+            .iter()
+            .zip(before)
+            .filter_map(|(tank, before)| {
+                let fall = tank.location().y - before;
+                if fall > SAFE_FALL {
+                    Some(Damage {
+                        player: tank.player(),
+                        amount: (fall - SAFE_FALL) * FALL_DAMAGE_PER_PX,
+                        cause: DamageCause::Fall,
+                    })
+                } else {
+                    None
+                }
+            })
+            .collect();
+        for hit in damage.iter() {
+            self.terrain.tank_mut(hit.player).take_damage(hit.amount);
+        }
+        damage
+    }
+
+    // sinks each tank left standing below the surface of the sea
+    fn sink(&mut self) -> Vec<Damage> {
+        let damage: Vec<Damage> = self
+            .terrain
+            .tanks()
+            .iter()
+            .filter(|tank| !tank.is_destroyed() && self.terrain.is_under_water(tank.location()))
+            .map(|tank| Damage {
+                player: tank.player(),
+                amount: tank.health(),
+                cause: DamageCause::Sunk,
+            })
+            .collect();
+        for hit in damage.iter() {
+            self.terrain.tank_mut(hit.player).take_damage(hit.amount);
+        }
+        damage
+    }
+
+    fn resolve(&mut self) {
+        let mut alive = self
+            .terrain
+            .tanks()
+            .iter()
+            .filter(|tank| !tank.is_destroyed());
+        self.result = match (alive.next(), alive.next()) {
+            (Some(_), Some(_)) => None,
+            (last, None) => Some(MatchResult {
+                winner: last.map(Tank::player),
+            }),
+            (None, Some(_)) => unreachable!(),
+        };
+        match self.result {
+            Some(result) => {
+                self.phase = Phase::Over;
+                self.events.push(Event::MatchOver(result));
+            }
+            None => {
+                self.phase = Phase::TurnTransition;
+                self.events.push(Event::TurnEnded {
+                    player: self.active_player,
+                });
+            }
+        }
+    }
+
+    // what a blast of `radius` at `center` does to each tank in reach, falling
+    // off from `full` damage at the center
+    fn blast_damage(&self, center: Point, radius: f64, full: f64) -> Vec<Damage> {
+        self.terrain
+            .tanks()
+            .iter()
+            .filter_map(|tank| {
+                let middle = tank.center();
+                let distance = (middle.x - center.x).hypot(middle.y - center.y);
+                if distance < radius {
+                    Some(Damage {
+                        player: tank.player(),
+                        amount: full * (1.0 - distance / radius),
+                        cause: DamageCause::Blast,
+                    })
+                } else {
+                    None
+                }
+            })
+            .collect()
+    }
+
+    fn detonate(&mut self, weapon: Weapon, point: Point, entry: Option<Point>) -> Impact {
+        let ground_before = self.tank_grounds();
+
+        let mut damage = Vec::new();
+        let (mut min_index, mut max_index) = match weapon.effect() {
+            Effect::Blast {
+                radius,
+                damage: full,
+            } => {
+                // any mines the blast reaches go off too, and so on
+                let mut blasts = vec![(point, radius, full)];
+                let mut columns: Option<(usize, usize)> = None;
+                while let Some((center, radius, full)) = blasts.pop() {
+                    damage.extend(self.blast_damage(center, radius, full));
+                    let (min, max) = self.terrain.blast(center, radius);
+                    columns = Some(match columns {
+                        Some((min_index, max_index)) => (min_index.min(min), max_index.max(max)),
+                        None => (min, max),
+                    });
+                    for mine in self.terrain.trip_mines(center, radius) {
+                        match mine {
+                            Hazard::Mine { radius, damage, .. } => {
+                                blasts.push((mine.position(), radius, damage))
+                            }
+                        }
+                    }
+                }
+                columns.expect("there is always the first blast")
+            }
+            Effect::Dirt { radius } => self.terrain.add_dirt(point, radius),
+            Effect::Fire {
+                reach,
+                damage: amount,
+            } => {
+                let (min_index, max_index) = self.terrain.burn(point.x, reach);
+                for tank in self.terrain.tanks() {
+                    let x = tank.location().x;
+                    if x < max_index as f64 && x + tank.width() > min_index as f64 {
+                        damage.push(Damage {
+                            player: tank.player(),
+                            amount,
+                            cause: DamageCause::Fire,
+                        });
+                    }
+                }
+                (min_index, max_index)
+            }
+        };
+        if let Some(entry) = entry {
+            let (min, max) = self.terrain.dig(entry, point, TRENCH_WIDTH);
+            min_index = min_index.min(min);
+            max_index = max_index.max(max);
+        }
+
+        for hit in damage.iter() {
+            self.terrain.tank_mut(hit.player).take_damage(hit.amount);
+        }
+        let falls = self.fall_damage(&ground_before);
+        damage.extend(falls);
+        damage.extend(self.sink());
+
+        Impact {
+            weapon,
+            point,
+            min_index,
+            max_index,
+            damage,
+        }
+    }
+}
+
+// Bots can hand back anything, including NaN, which `f64::clamp` passes through.
+fn clamp_or_min(value: f64, min: f64, max: f64) -> f64 {
+    if value.is_nan() {
+        min
+    } else {
+        value.clamp(min, max)
+    }
+}
@@ -0,0 +1,411 @@
+//! Ways of shaping the ground a match starts on.
+//!
+//! A `TerrainGenerator` turns the terrain stream of a match seed into the top
+//! of the ground in every column. `TerrainSettings` picks one, along with its
+//! parameters, as part of `MatchSettings`.
+
+use super::settings::check;
+use super::{MatchRng, SettingsError};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::f64::consts::PI;
+
+// closest the ground gets to the top or bottom of the field, in px
+const MARGIN: f64 = 30.0;
+
+pub trait TerrainGenerator {
+    /// The top of the ground, in px down from the top of a field `height` px
+    /// tall, in each of `width` columns. Every random choice is drawn from
+    /// `rng`, so the same seed always gives the same ground.
+    fn generate(&self, width: usize, height: f64, rng: &mut MatchRng) -> Vec<f64>;
+
+    /// The surface of the sea flooding the low ground, in px down from the
+    /// top of a field `height` px tall, or `None` if the field is dry.
+    fn sea_level(&self, _height: f64) -> Option<f64> {
+        None
+    }
+}
+
+/// Which generator a match's terrain comes from, and its parameters. In a
+/// settings file it is a table naming the generator, where any parameter
+/// left out keeps its default:
+///
+/// ```toml
+/// [terrain]
+/// generator = "noise"
+/// octaves = 6
+/// ```
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "generator", rename_all = "snake_case")]
+pub enum TerrainSettings {
+    RandomWalk(RandomWalk),
+    MidpointDisplacement(MidpointDisplacement),
+    Noise(Noise),
+    Plateaus(Plateaus),
+    Islands(Islands),
+    Flat(Flat),
+}
+
+impl TerrainSettings {
+    pub fn generator(&self) -> &dyn TerrainGenerator {
+        match self {
+            TerrainSettings::RandomWalk(generator) => generator,
+            TerrainSettings::MidpointDisplacement(generator) => generator,
+            TerrainSettings::Noise(generator) => generator,
+            TerrainSettings::Plateaus(generator) => generator,
+            TerrainSettings::Islands(generator) => generator,
+            TerrainSettings::Flat(generator) => generator,
+        }
+    }
+
+    /// Checks the parameters for a field `height` px tall, reporting the
+    /// first one that is out of range.
+    pub(crate) fn validate(&self, height: f64) -> Result<(), SettingsError> {
+        match self {
+            TerrainSettings::RandomWalk(walk) => {
+                check("terrain.step_max", walk.step_max, 0.1, 10.0)?;
+                check("terrain.step_change", walk.step_change, 0.0, 10.0)?;
+                check("terrain.margin", walk.margin, 0.0, height / 3.0)?;
+            }
+            TerrainSettings::MidpointDisplacement(midpoint) => {
+                check("terrain.displacement", midpoint.displacement, 0.0, 1.0)?;
+                check("terrain.roughness", midpoint.roughness, 0.0, 1.0)?;
+            }
+            TerrainSettings::Noise(noise) => {
+                check("terrain.wavelength", noise.wavelength, 10.0, 4000.0)?;
+                check("terrain.octaves", noise.octaves as f64, 1.0, 8.0)?;
+                check("terrain.persistence", noise.persistence, 0.0, 1.0)?;
+                check("terrain.amplitude", noise.amplitude, 0.0, 1.0)?;
+                check("terrain.level", noise.level, 0.0, 1.0)?;
+            }
+            TerrainSettings::Plateaus(plateaus) => {
+                check("terrain.count", plateaus.count as f64, 1.0, 20.0)?;
+                check("terrain.max_width", plateaus.max_width, 1.0, 4000.0)?;
+                check(
+                    "terrain.min_width",
+                    plateaus.min_width,
+                    1.0,
+                    plateaus.max_width,
+                )?;
+                check("terrain.max_rise", plateaus.max_rise, 0.0, 1.0)?;
+                check("terrain.level", plateaus.level, 0.0, 1.0)?;
+                check("terrain.steepness", plateaus.steepness, 0.1, 100.0)?;
+            }
+            TerrainSettings::Islands(islands) => {
+                check("terrain.count", islands.count as f64, 1.0, 20.0)?;
+                check("terrain.sea_bed", islands.sea_bed, 0.0, 1.0)?;
+                check("terrain.sea_level", islands.sea_level, 0.0, islands.sea_bed)?;
+                check("terrain.max_rise", islands.max_rise, 0.0, 1.0)?;
+            }
+            TerrainSettings::Flat(flat) => check("terrain.level", flat.level, 0.0, 1.0)?,
+        }
+        Ok(())
+    }
+}
+
+impl Default for TerrainSettings {
+    fn default() -> TerrainSettings {
+        TerrainSettings::RandomWalk(RandomWalk::default())
+    }
+}
+
+/// Rolling hills: the ground climbs or falls by a slope that drifts at random
+/// from one column to the next, bouncing off the top and bottom of the field.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct RandomWalk {
+    /// Steepest the ground gets, in px per column.
+    pub step_max: f64,
+    /// Most the slope changes by from one column to the next.
+    pub step_change: f64,
+    /// Closest a peak gets to the top of the field, and a valley to the
+    /// bottom, in px.
+    pub margin: f64,
+}
+
+impl Default for RandomWalk {
+    fn default() -> RandomWalk {
+        RandomWalk {
+            step_max: 2.5,
+            step_change: 1.0,
+            margin: MARGIN,
+        }
+    }
+}
+
+impl TerrainGenerator for RandomWalk {
+    fn generate(&self, width: usize, height: f64, rng: &mut MatchRng) -> Vec<f64> {
+        let height_max = height - self.margin;
+
+        // starting conditions
+        let y1: f64 = rng.gen();
+        let mut terrain_height: f64 = y1 * height_max;
+        let y2: f64 = rng.gen();
+        let mut slope: f64 = (y2 * self.step_max) * 2.0 - self.step_max;
+
+        (0..width)
+            .map(|_| {
+                // change height and slope
+                terrain_height += slope;
+                let y3: f64 = rng.gen();
+                slope += (y3 * self.step_change) * 2.0 - self.step_change;
+
+                // clip height and slope to maximum
+                slope = slope.clamp(-self.step_max, self.step_max);
+
+                if terrain_height > height_max {
+                    terrain_height = height_max;
+                    slope *= -1.0;
+                }
+
+                if terrain_height < self.margin {
+                    terrain_height = self.margin;
+                    slope *= -1.0;
+                }
+
+                terrain_height
+            })
+            .collect()
+    }
+}
+
+/// Jagged mountains: the field is halved over and over, each midpoint pushed
+/// up or down at random by less than the last.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MidpointDisplacement {
+    /// Furthest the middle of the field can be pushed, as a fraction of the
+    /// field height.
+    pub displacement: f64,
+    /// What each halving's push is scaled by: near 0 is smooth, 1 is jagged.
+    pub roughness: f64,
+}
+
+impl Default for MidpointDisplacement {
+    fn default() -> MidpointDisplacement {
+        MidpointDisplacement {
+            displacement: 0.4,
+            roughness: 0.55,
+        }
+    }
+}
+
+impl TerrainGenerator for MidpointDisplacement {
+    fn generate(&self, width: usize, height: f64, rng: &mut MatchRng) -> Vec<f64> {
+        let segments = (width.max(2) - 1).next_power_of_two();
+        let mut points = vec![0.0; segments + 1];
+        points[0] = MARGIN + rng.gen::<f64>() * (height - 2.0 * MARGIN);
+        points[segments] = MARGIN + rng.gen::<f64>() * (height - 2.0 * MARGIN);
+
+        let mut spread = self.displacement * height;
+        let mut step = segments;
+        while step > 1 {
+            let half = step / 2;
+            for start in (0..segments).step_by(step) {
+                let middle = (points[start] + points[start + step]) / 2.0;
+                points[start + half] = middle + (rng.gen::<f64>() * 2.0 - 1.0) * spread;
+            }
+            spread *= self.roughness;
+            step = half;
+        }
+
+        points.truncate(width);
+        points.into_iter().map(|y| within(y, height)).collect()
+    }
+}
+
+/// Smooth, natural-looking hills from layers of gradient (Perlin) noise, each
+/// finer and fainter than the last.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Noise {
+    /// Width of the broadest hills, in px.
+    pub wavelength: f64,
+    /// Layers of noise; each has half the wavelength of the one before.
+    pub octaves: u32,
+    /// What each layer's strength is scaled by.
+    pub persistence: f64,
+    /// Most the ground rises and falls, lowest to highest, as a fraction of
+    /// the field height.
+    pub amplitude: f64,
+    /// Where the ground lies on average, as a fraction of the field height
+    /// down from the top.
+    pub level: f64,
+}
+
+impl Default for Noise {
+    fn default() -> Noise {
+        Noise {
+            wavelength: 300.0,
+            octaves: 4,
+            persistence: 0.5,
+            amplitude: 0.6,
+            level: 0.6,
+        }
+    }
+}
+
+impl TerrainGenerator for Noise {
+    fn generate(&self, width: usize, height: f64, rng: &mut MatchRng) -> Vec<f64> {
+        let mut total = vec![0.0; width];
+        let mut wavelength = self.wavelength;
+        let mut strength = 1.0;
+        let mut strengths = 0.0;
+        for _ in 0..self.octaves {
+            let lattice = (width as f64 / wavelength).ceil() as usize + 2;
+            let gradients: Vec<f64> = (0..lattice).map(|_| rng.gen::<f64>() * 2.0 - 1.0).collect();
+            for (x, total) in total.iter_mut().enumerate() {
+                *total += strength * gradient_noise(&gradients, x as f64 / wavelength);
+            }
+            strengths += strength;
+            strength *= self.persistence;
+            wavelength /= 2.0;
+        }
+
+        total
+            .into_iter()
+            .map(|noise| (self.level + noise / strengths * self.amplitude) * height)
+            .map(|y| within(y, height))
+            .collect()
+    }
+}
+
+/// Flat-topped mesas standing on a level plain; where two overlap they make
+/// terraces.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Plateaus {
+    pub count: u32,
+    /// Narrowest and widest the top of a mesa is, in px.
+    pub min_width: f64,
+    pub max_width: f64,
+    /// Tallest a mesa stands above the plain, as a fraction of the field
+    /// height.
+    pub max_rise: f64,
+    /// Where the plain lies, as a fraction of the field height down from the
+    /// top.
+    pub level: f64,
+    /// How steep the sides of the mesas are, in px per column.
+    pub steepness: f64,
+}
+
+impl Default for Plateaus {
+    fn default() -> Plateaus {
+        Plateaus {
+            count: 4,
+            min_width: 40.0,
+            max_width: 160.0,
+            max_rise: 0.45,
+            level: 0.8,
+            steepness: 3.0,
+        }
+    }
+}
+
+impl TerrainGenerator for Plateaus {
+    fn generate(&self, width: usize, height: f64, rng: &mut MatchRng) -> Vec<f64> {
+        let plain = self.level * height;
+        let mut heights = vec![plain; width];
+        for _ in 0..self.count {
+            let center = rng.gen::<f64>() * width as f64;
+            let top = self.min_width + rng.gen::<f64>() * (self.max_width - self.min_width);
+            let rise = self.max_rise * height * (0.5 + 0.5 * rng.gen::<f64>());
+            for (x, y) in heights.iter_mut().enumerate() {
+                let from_top = ((x as f64 - center).abs() - top / 2.0).max(0.0);
+                *y = y.min(plain - rise + from_top * self.steepness);
+            }
+        }
+        heights.into_iter().map(|y| within(y, height)).collect()
+    }
+}
+
+/// Rounded islands rising out of the sea from a flat, low sea bed, one to
+/// each equal stretch of the field.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Islands {
+    pub count: u32,
+    /// Where the sea bed lies, as a fraction of the field height down from
+    /// the top.
+    pub sea_bed: f64,
+    /// Where the surface of the sea lies, as a fraction of the field height
+    /// down from the top. Shots that fall in are lost and tanks that drop in
+    /// sink.
+    pub sea_level: f64,
+    /// Tallest an island stands above the sea bed, as a fraction of the field
+    /// height.
+    pub max_rise: f64,
+}
+
+impl Default for Islands {
+    fn default() -> Islands {
+        Islands {
+            count: 3,
+            sea_bed: 0.85,
+            sea_level: 0.7,
+            max_rise: 0.5,
+        }
+    }
+}
+
+impl TerrainGenerator for Islands {
+    fn generate(&self, width: usize, height: f64, rng: &mut MatchRng) -> Vec<f64> {
+        let sea_bed = self.sea_bed * height;
+        let stretch = width as f64 / self.count as f64;
+        let mut heights = vec![sea_bed; width];
+        for i in 0..self.count {
+            let center = stretch * (i as f64 + 0.25 + 0.5 * rng.gen::<f64>());
+            let radius = stretch * (0.3 + 0.15 * rng.gen::<f64>());
+            let rise = self.max_rise * height * (0.5 + 0.5 * rng.gen::<f64>());
+            for (x, y) in heights.iter_mut().enumerate() {
+                let distance = (x as f64 - center).abs();
+                if distance < radius {
+                    // levels out where it meets the sea bed
+                    let hump = rise * (1.0 + libm::cos(PI * distance / radius)) / 2.0;
+                    *y = y.min(sea_bed - hump);
+                }
+            }
+        }
+        heights.into_iter().map(|y| within(y, height)).collect()
+    }
+
+    fn sea_level(&self, height: f64) -> Option<f64> {
+        Some(self.sea_level * height)
+    }
+}
+
+/// Level ground all the way across; draws nothing from the seed.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Flat {
+    /// Where the ground lies, as a fraction of the field height down from
+    /// the top.
+    pub level: f64,
+}
+
+impl Default for Flat {
+    fn default() -> Flat {
+        Flat { level: 0.7 }
+    }
+}
+
+impl TerrainGenerator for Flat {
+    fn generate(&self, width: usize, height: f64, _rng: &mut MatchRng) -> Vec<f64> {
+        vec![within(self.level * height, height); width]
+    }
+}
+
+// 1D Perlin noise at `x`, in lattice cells, between -0.5 and 0.5
+fn gradient_noise(gradients: &[f64], x: f64) -> f64 {
+    let cell = x.floor();
+    let t = x - cell;
+    let i = cell as usize;
+    let fade = t * t * t * (t * (t * 6.0 - 15.0) + 10.0);
+    let from = gradients[i] * t;
+    let to = gradients[i + 1] * (t - 1.0);
+    from + (to - from) * fade
+}
+
+fn within(y: f64, height: f64) -> f64 {
+    y.clamp(MARGIN, height - MARGIN)
+}
@@ -0,0 +1,362 @@
+//! The relay's side of networked matches; see `net`.
+//!
+//! A `Lobby` knows nothing of sockets: the server hands it each message as
+//! it arrives, from a connection numbered however the server likes, and
+//! sends on whatever it returns.
+
+use super::{
+    Broadcast, ClientMessage, MatchRng, MatchSettings, ServerMessage, Turn, PROTOCOL_VERSION,
+};
+use rand::{Rng, SeedableRng};
+use std::collections::HashMap;
+
+/// Identifies one client's connection to the relay.
+pub type ConnectionId = u64;
+
+/// Every room open on the relay, and who is in each.
+///
+/// A room opens when the first player joins it and starts once every seat
+/// the settings ask for is taken. It keeps every move made in it, so that a
+/// player whose connection drops can rejoin and catch up, and follows the
+/// match itself, so that spectators can watch from any point in it. It
+/// closes when no player is connected to it any more.
+#[derive(Debug)]
+pub struct Lobby {
+    // for match seeds and seat tokens
+    rng: MatchRng,
+    rooms: HashMap<String, Room>,
+    // the room and seat of each connection that has one
+    seated: HashMap<ConnectionId, (String, u8)>,
+    // the room each spectator is watching
+    watching: HashMap<ConnectionId, String>,
+}
+
+#[derive(Debug)]
+struct Room {
+    seed: u64,
+    settings: MatchSettings,
+    seats: Vec<Seat>,
+    moves: Vec<Turn>,
+    // the match as the moves have played it, until one doesn't fit it
+    broadcast: Option<Broadcast>,
+    watchers: Vec<ConnectionId>,
+}
+
+#[derive(Debug)]
+struct Seat {
+    token: u64,
+    connection: Option<ConnectionId>,
+}
+
+impl Room {
+    fn started(&self) -> bool {
+        self.seats.len() == self.settings.players as usize
+    }
+
+    // the message to everyone connected to the room, watchers included
+    fn everyone(&self, message: ServerMessage) -> Vec<(ConnectionId, ServerMessage)> {
+        self.seats
+            .iter()
+            .filter_map(|seat| seat.connection)
+            .chain(self.watchers.iter().copied())
+            .map(|connection| (connection, message.clone()))
+            .collect()
+    }
+
+    // the message to everyone connected to the room but `except`
+    fn others(
+        &self,
+        except: ConnectionId,
+        message: ServerMessage,
+    ) -> Vec<(ConnectionId, ServerMessage)> {
+        let mut sends = self.everyone(message);
+        sends.retain(|(connection, _)| *connection != except);
+        sends
+    }
+
+    fn joined(&self, room: &str, seat: u8) -> ServerMessage {
+        ServerMessage::Joined {
+            room: room.to_string(),
+            seat,
+            token: self.seats[seat as usize].token,
+            seed: self.seed,
+            settings: self.settings.clone(),
+            started: self.started(),
+            turns: self.moves.len(),
+        }
+    }
+}
+
+impl Lobby {
+    /// An empty lobby, whose rooms draw their seeds from `seed`.
+    pub fn new(seed: u64) -> Lobby {
+        Lobby {
+            rng: MatchRng::seed_from_u64(seed),
+            rooms: HashMap::new(),
+            seated: HashMap::new(),
+            watching: HashMap::new(),
+        }
+    }
+
+    /// Handles `message` from `from`, and returns what to send to whom.
+    pub fn handle(
+        &mut self,
+        from: ConnectionId,
+        message: ClientMessage,
+    ) -> Vec<(ConnectionId, ServerMessage)> {
+        let result = match message {
+            ClientMessage::Join {
+                version,
+                room,
+                settings,
+            } => check_version(version).and_then(|()| self.join(from, room, settings)),
+            ClientMessage::Watch { version, room } => {
+                check_version(version).and_then(|()| self.watch(from, room))
+            }
+            ClientMessage::Rejoin {
+                version,
+                room,
+                seat,
+                token,
+                since,
+            } => check_version(version).and_then(|()| self.rejoin(from, room, seat, token, since)),
+            ClientMessage::Move {
+                turn,
+                checksum,
+                made,
+            } => self.relay(from, turn, Turn { checksum, made }),
+        };
+        result.unwrap_or_else(|message| vec![(from, ServerMessage::Error { message })])
+    }
+
+    /// Forgets `connection`, and returns what to tell the rest of its room.
+    pub fn disconnect(&mut self, connection: ConnectionId) -> Vec<(ConnectionId, ServerMessage)> {
+        if let Some(name) = self.watching.remove(&connection) {
+            let room = self.rooms.get_mut(&name).expect("watching an open room");
+            room.watchers.retain(|watcher| *watcher != connection);
+            return Vec::new();
+        }
+        let (name, seat) = match self.seated.remove(&connection) {
+            Some(seated) => seated,
+            None => return Vec::new(),
+        };
+        let room = self.rooms.get_mut(&name).expect("seated in an open room");
+        room.seats[seat as usize].connection = None;
+        if room.seats.iter().all(|seat| seat.connection.is_none()) {
+            let room = self.rooms.remove(&name).expect("just left");
+            let message = format!("room {} has closed", name);
+            return room
+                .watchers
+                .into_iter()
+                .map(|watcher| {
+                    self.watching.remove(&watcher);
+                    let message = message.clone();
+                    (watcher, ServerMessage::Error { message })
+                })
+                .collect();
+        }
+        room.others(
+            connection,
+            ServerMessage::Presence {
+                seat,
+                connected: false,
+            },
+        )
+    }
+
+    /// Names of the open rooms.
+    pub fn rooms(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = self.rooms.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        names
+    }
+
+    fn join(
+        &mut self,
+        from: ConnectionId,
+        name: String,
+        settings: Option<MatchSettings>,
+    ) -> Result<Vec<(ConnectionId, ServerMessage)>, String> {
+        self.check_unseated(from)?;
+        if !self.rooms.contains_key(&name) {
+            let settings = settings.unwrap_or_default();
+            settings.validate().map_err(|error| error.to_string())?;
+            let seed = self.rng.gen();
+            let players = vec!["remote".to_string(); settings.players as usize];
+            let broadcast = Broadcast::new(seed, settings.clone(), None, players)
+                .map_err(|error| error.to_string())?;
+            let room = Room {
+                seed,
+                settings,
+                seats: Vec::new(),
+                moves: Vec::new(),
+                broadcast: Some(broadcast),
+                watchers: Vec::new(),
+            };
+            self.rooms.insert(name.clone(), room);
+        }
+        let token = self.rng.gen();
+        let room = self.rooms.get_mut(&name).expect("just opened");
+        if room.started() {
+            return Err(format!("room {} is full", name));
+        }
+        let seat = room.seats.len() as u8;
+        room.seats.push(Seat {
+            token,
+            connection: Some(from),
+        });
+        self.seated.insert(from, (name.clone(), seat));
+
+        let mut replies = vec![(from, room.joined(&name, seat))];
+        replies.extend(room.others(
+            from,
+            ServerMessage::Presence {
+                seat,
+                connected: true,
+            },
+        ));
+        if room.started() {
+            replies.extend(room.everyone(ServerMessage::Started));
+        }
+        Ok(replies)
+    }
+
+    fn watch(
+        &mut self,
+        from: ConnectionId,
+        name: String,
+    ) -> Result<Vec<(ConnectionId, ServerMessage)>, String> {
+        self.check_unseated(from)?;
+        let room = self
+            .rooms
+            .get_mut(&name)
+            .ok_or_else(|| format!("there is no room {}", name))?;
+        let broadcast = room
+            .broadcast
+            .as_ref()
+            .ok_or_else(|| format!("room {} can't be watched", name))?;
+        let watching = ServerMessage::Watching {
+            room: name.clone(),
+            turns: broadcast.turns(),
+            snapshot: Box::new(broadcast.snapshot()),
+        };
+        room.watchers.push(from);
+        self.watching.insert(from, name);
+        Ok(vec![(from, watching)])
+    }
+
+    fn rejoin(
+        &mut self,
+        from: ConnectionId,
+        name: String,
+        seat: u8,
+        token: u64,
+        since: usize,
+    ) -> Result<Vec<(ConnectionId, ServerMessage)>, String> {
+        self.check_unseated(from)?;
+        let room = self
+            .rooms
+            .get_mut(&name)
+            .ok_or_else(|| format!("there is no room {}", name))?;
+        match room.seats.get_mut(seat as usize) {
+            Some(taken) if taken.token == token => {
+                // the old connection may not have noticed it dropped yet
+                if let Some(old) = taken.connection.replace(from) {
+                    self.seated.remove(&old);
+                }
+            }
+            _ => return Err(format!("seat {} of room {} isn't yours", seat, name)),
+        }
+        self.seated.insert(from, (name.clone(), seat));
+
+        let mut replies = vec![(from, room.joined(&name, seat))];
+        if since < room.moves.len() {
+            let moves = room.moves[since..].to_vec();
+            replies.push((from, ServerMessage::Moves { from: since, moves }));
+        }
+        replies.extend(room.others(
+            from,
+            ServerMessage::Presence {
+                seat,
+                connected: true,
+            },
+        ));
+        Ok(replies)
+    }
+
+    // takes a move from the player in its seat, and passes it on to everyone
+    // else in the room
+    fn relay(
+        &mut self,
+        from: ConnectionId,
+        turn: usize,
+        relayed: Turn,
+    ) -> Result<Vec<(ConnectionId, ServerMessage)>, String> {
+        let (name, seat) = self
+            .seated
+            .get(&from)
+            .ok_or_else(|| "join a room before moving".to_string())?;
+        let room = self.rooms.get_mut(name).expect("seated in an open room");
+        if !room.started() {
+            return Err("the match hasn't started".to_string());
+        }
+        if relayed.made.player().0 != *seat {
+            return Err(format!(
+                "seat {} can't move for player {}",
+                seat,
+                relayed.made.player().0
+            ));
+        }
+        if turn != room.moves.len() {
+            return Err(format!(
+                "move {} is out of order; the next is move {}",
+                turn,
+                room.moves.len()
+            ));
+        }
+        room.moves.push(relayed);
+        let mut sends = room.others(
+            from,
+            ServerMessage::Moves {
+                from: turn,
+                moves: vec![relayed],
+            },
+        );
+        // the players' copies are the ones that count; if the move doesn't
+        // fit this one, it just can't be watched any more
+        let followed = room
+            .broadcast
+            .as_mut()
+            .map(|broadcast| broadcast.play(&relayed));
+        if let Some(Err(error)) = followed {
+            room.broadcast = None;
+            let message = format!("can't follow room {} any more: {}", name, error);
+            for watcher in room.watchers.drain(..) {
+                self.watching.remove(&watcher);
+                sends.retain(|(connection, _)| *connection != watcher);
+                let message = message.clone();
+                sends.push((watcher, ServerMessage::Error { message }));
+            }
+        }
+        Ok(sends)
+    }
+
+    // players and spectators are in one room at a time
+    fn check_unseated(&self, from: ConnectionId) -> Result<(), String> {
+        match (self.seated.get(&from), self.watching.get(&from)) {
+            (Some((name, _)), _) | (None, Some(name)) => Err(format!("already in room {}", name)),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+fn check_version(version: u32) -> Result<(), String> {
+    if version == PROTOCOL_VERSION {
+        Ok(())
+    } else {
+        Err(format!(
+            "protocol version {} isn't supported; the relay speaks {}",
+            version, PROTOCOL_VERSION
+        ))
+    }
+}
@@ -0,0 +1,329 @@
+//! Battlefields saved to and loaded from files.
+//!
+//! A `Map` is a fixed field to play on instead of one generated from the seed:
+//! its ground, colors, where the tanks start and any hazards. Maps are stored
+//! as JSON, tagged with the `MAP_VERSION` of the format they were written in,
+//! and a few handcrafted ones ship with the crate in `Map::library`.
+
+use super::{Config, Point, Terrain, TerrainMask, TANK_WIDTH};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+
+/// The version of the map format this build reads and writes.
+pub const MAP_VERSION: u32 = 1;
+
+const LIBRARY: [&str; 4] = [
+    include_str!("../../maps/twin_peaks.json"),
+    include_str!("../../maps/canyon.json"),
+    include_str!("../../maps/sky_bridge.json"),
+    include_str!("../../maps/minefield.json"),
+];
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Map {
+    pub version: u32,
+    pub name: String,
+    /// Size of the field, in px. A match played on the map must be the same
+    /// size.
+    pub width: u32,
+    pub height: u32,
+    /// The ground in each column, from the left.
+    pub ground: Vec<Column>,
+    pub color_hex: String,
+    pub sky_color_hex: String,
+    /// Left edge of each player's tank, by `PlayerId`. A match for fewer
+    /// players uses the first ones.
+    pub spawns: Vec<f64>,
+    #[serde(default)]
+    pub hazards: Vec<Hazard>,
+    /// The surface of the sea, in px down from the top, if the low ground is
+    /// flooded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sea_level: Option<f64>,
+}
+
+/// The ground in one column of a map.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum Column {
+    /// Solid from this many px down to the bottom, with no caves.
+    Surface(u32),
+    /// Solid runs `[start, end]`, top first, with `end` exclusive.
+    Runs(Vec<[u32; 2]>),
+}
+
+/// Something placed on a map besides the ground and the tanks.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "kind", rename_all = "snake_case", deny_unknown_fields)]
+pub enum Hazard {
+    /// A charge at `(x, y)`, usually buried, that goes off like a missile
+    /// with this `radius` and `damage` when any blast reaches it.
+    Mine {
+        x: f64,
+        y: f64,
+        radius: f64,
+        damage: f64,
+    },
+}
+
+impl Hazard {
+    pub fn position(&self) -> Point {
+        match *self {
+            Hazard::Mine { x, y, .. } => Point::new(x, y),
+        }
+    }
+}
+
+/// Why a `Map` can't be loaded or played.
+#[derive(Clone, Debug, PartialEq)]
+pub enum MapError {
+    /// The JSON couldn't be read.
+    Parse(String),
+    /// The map was written in a version of the format this build can't read.
+    UnsupportedVersion(u32),
+    /// The map is well-formed but doesn't describe a field that can be played
+    /// on.
+    Invalid(String),
+    /// The map doesn't fit the match: the field is a different size or there
+    /// aren't enough spawns.
+    Mismatch(String),
+}
+
+impl fmt::Display for MapError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MapError::Parse(message) => write!(f, "invalid map: {}", message),
+            MapError::UnsupportedVersion(version) => write!(
+                f,
+                "map format version {} isn't supported; expected {}",
+                version, MAP_VERSION
+            ),
+            MapError::Invalid(message) => write!(f, "invalid map: {}", message),
+            MapError::Mismatch(message) => write!(f, "map doesn't fit the match: {}", message),
+        }
+    }
+}
+
+impl Error for MapError {}
+
+// read first, so that a map from a newer format is reported as such rather
+// than as whatever field it no longer parses at
+#[derive(Deserialize)]
+struct Header {
+    version: u32,
+}
+
+impl Map {
+    /// The field `terrain` is now, with its tanks where they stand, as a map
+    /// called `name`.
+    pub fn from_terrain(name: &str, terrain: &Terrain) -> Map {
+        let mask = terrain.mask();
+        let ground = (0..mask.width())
+            .map(|x| {
+                let runs: Vec<[u32; 2]> = mask
+                    .runs(x)
+                    .into_iter()
+                    .filter(|&(_, _, solid)| solid)
+                    .map(|(start, end, _)| [start as u32, end as u32])
+                    .collect();
+                match runs.as_slice() {
+                    &[[start, end]] if end as usize == mask.height() => Column::Surface(start),
+                    _ => Column::Runs(runs),
+                }
+            })
+            .collect();
+        Map {
+            version: MAP_VERSION,
+            name: name.to_string(),
+            width: mask.width() as u32,
+            height: mask.height() as u32,
+            ground,
+            color_hex: terrain.color_hex(),
+            sky_color_hex: terrain.sky_color_hex(),
+            spawns: terrain
+                .tanks()
+                .iter()
+                .map(|tank| tank.location().x)
+                .collect(),
+            hazards: terrain.hazards().to_vec(),
+            sea_level: terrain.sea_level(),
+        }
+    }
+
+    pub fn from_json(text: &str) -> Result<Map, MapError> {
+        let parse = |error: serde_json::Error| MapError::Parse(error.to_string());
+        let header: Header = serde_json::from_str(text).map_err(parse)?;
+        if header.version != MAP_VERSION {
+            return Err(MapError::UnsupportedVersion(header.version));
+        }
+        let map: Map = serde_json::from_str(text).map_err(parse)?;
+        map.validate()?;
+        Ok(map)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("maps are always valid JSON")
+    }
+
+    /// The handcrafted maps that ship with the crate.
+    pub fn library() -> Vec<Map> {
+        LIBRARY
+            .iter()
+            .map(|text| Map::from_json(text).expect("library maps are valid"))
+            .collect()
+    }
+
+    /// The map called `name` in `library`.
+    pub fn from_library(name: &str) -> Option<Map> {
+        Map::library().into_iter().find(|map| map.name == name)
+    }
+
+    /// Checks that the map describes a field that can be played on.
+    pub fn validate(&self) -> Result<(), MapError> {
+        let invalid = |message: String| Err(MapError::Invalid(message));
+        if !(300..=4000).contains(&self.width) || !(200..=2000).contains(&self.height) {
+            return invalid(format!(
+                "a {}x{} field is outside 300x200 to 4000x2000",
+                self.width, self.height
+            ));
+        }
+        if self.ground.len() != self.width as usize {
+            return invalid(format!(
+                "{} columns of ground for a field {} wide",
+                self.ground.len(),
+                self.width
+            ));
+        }
+        for (x, column) in self.ground.iter().enumerate() {
+            let fits = match column {
+                Column::Surface(top) => *top <= self.height,
+                Column::Runs(runs) => {
+                    let mut bottom = 0;
+                    runs.iter().all(|&[start, end]| {
+                        let fits = start >= bottom && start < end && end <= self.height;
+                        bottom = end;
+                        fits
+                    })
+                }
+            };
+            if !fits {
+                return invalid(format!(
+                    "ground in column {} is out of order or off the field",
+                    x
+                ));
+            }
+        }
+        for color in [&self.color_hex, &self.sky_color_hex] {
+            let digits = color.strip_prefix('#').unwrap_or("");
+            if digits.len() != 6 || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+                return invalid(format!("{:?} isn't a #RRGGBB color", color));
+            }
+        }
+        if !(2..=8).contains(&self.spawns.len()) {
+            return invalid(format!("{} spawns; expected 2 to 8", self.spawns.len()));
+        }
+        if let Some(x) = self
+            .spawns
+            .iter()
+            .find(|&&x| !(x >= 0.0 && x < self.width as f64))
+        {
+            return invalid(format!("spawn at {} is off the field", x));
+        }
+        let mut spawns = self.spawns.clone();
+        spawns.sort_by(f64::total_cmp);
+        if let Some(pair) = spawns
+            .windows(2)
+            .find(|pair| pair[1] - pair[0] < TANK_WIDTH)
+        {
+            return invalid(format!(
+                "spawns at {} and {} are closer than a tank is wide",
+                pair[0], pair[1]
+            ));
+        }
+        if let Some(sea_level) = self.sea_level {
+            if !(0.0..=self.height as f64).contains(&sea_level) {
+                return invalid(format!("a sea level of {} is off the field", sea_level));
+            }
+            let top = |column: &Column| match column {
+                Column::Surface(top) => *top,
+                Column::Runs(runs) => runs.first().map_or(self.height, |run| run[0]),
+            };
+            if let Some(x) = self
+                .spawns
+                .iter()
+                .find(|&&x| top(&self.ground[x as usize]) as f64 > sea_level)
+            {
+                return invalid(format!("spawn at {} is under the sea", x));
+            }
+        }
+        for hazard in self.hazards.iter() {
+            let point = hazard.position();
+            let on_field = point.x >= 0.0
+                && point.x < self.width as f64
+                && point.y >= 0.0
+                && point.y < self.height as f64;
+            let in_range = match *hazard {
+                Hazard::Mine { radius, damage, .. } => {
+                    radius > 0.0 && radius <= 200.0 && damage >= 0.0
+                }
+            };
+            if !on_field || !in_range {
+                return invalid(format!("{:?} is off the field or out of range", hazard));
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that a match played with `config` can be played on the map:
+    /// the field is the same size and there is a spawn, with room for a
+    /// tank, for every player.
+    pub fn fits(&self, config: &Config) -> Result<(), MapError> {
+        if self.width as f64 != config.width() || self.height as f64 != config.height() {
+            return Err(MapError::Mismatch(format!(
+                "the map is {}x{} but the match is {}x{}",
+                self.width,
+                self.height,
+                config.width(),
+                config.height()
+            )));
+        }
+        if self.spawns.len() < config.players() as usize {
+            return Err(MapError::Mismatch(format!(
+                "the map has spawns for {} players, not {}",
+                self.spawns.len(),
+                config.players()
+            )));
+        }
+        if let Some(x) = self
+            .spawns
+            .iter()
+            .find(|&&x| x + config.tank_width() > config.width())
+        {
+            return Err(MapError::Mismatch(format!(
+                "a tank at {} doesn't fit on the field",
+                x
+            )));
+        }
+        Ok(())
+    }
+
+    /// The map's ground as a mask.
+    pub fn mask(&self) -> TerrainMask {
+        let height = self.height as usize;
+        let mut mask = TerrainMask::empty(self.width as usize, height);
+        for (x, column) in self.ground.iter().enumerate() {
+            let runs = match column {
+                Column::Surface(top) => vec![[*top, self.height]],
+                Column::Runs(runs) => runs.clone(),
+            };
+            for [start, end] in runs {
+                for y in start..end {
+                    mask.set(x, y as usize, true);
+                }
+            }
+        }
+        mask
+    }
+}
@@ -0,0 +1,295 @@
+use super::Point;
+
+/// Which pixels of the field are solid ground.
+///
+/// Each pixel is a cell of the mask; the one at `(x, y)` covers the square
+/// from `(x, y)` to `(x + 1, y + 1)`, with y pointing down like the canvas.
+/// Unlike a heightmap, ground can have empty space under it, so blasts can
+/// carve round craters, overhangs and tunnels.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TerrainMask {
+    width: usize,
+    height: usize,
+    // column by column, so walking down a column stays in one run of memory
+    solid: Vec<bool>,
+}
+
+impl TerrainMask {
+    /// A mask with no ground at all.
+    pub fn empty(width: usize, height: usize) -> TerrainMask {
+        TerrainMask {
+            width,
+            height,
+            solid: vec![false; width * height],
+        }
+    }
+
+    /// Ground that is solid from `heights[x]` down to the bottom of every
+    /// column `x`, with no caves. A pixel is solid if any of it is below the
+    /// surface.
+    pub fn from_heights(heights: &[f64], height: usize) -> TerrainMask {
+        let mut mask = TerrainMask::empty(heights.len(), height);
+        for (x, &surface) in heights.iter().enumerate() {
+            for y in 0..height {
+                mask.set(x, y, (y + 1) as f64 > surface);
+            }
+        }
+        mask
+    }
+
+    pub fn width(&self) -> usize {
+        self.width
+    }
+
+    pub fn height(&self) -> usize {
+        self.height
+    }
+
+    /// Whether pixel `(x, y)` is ground. Everything outside the mask is
+    /// empty.
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        x < self.width && y < self.height && self.solid[x * self.height + y]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, solid: bool) {
+        if x < self.width && y < self.height {
+            self.solid[x * self.height + y] = solid;
+        }
+    }
+
+    /// Whether `point` is inside the ground.
+    pub fn is_solid(&self, point: Point) -> bool {
+        point.x >= 0.0 && point.y >= 0.0 && self.get(point.x as usize, point.y as usize)
+    }
+
+    /// The top of the highest ground in column `x`, or the bottom of the
+    /// field if the column has been blown clear.
+    pub fn surface(&self, x: usize) -> f64 {
+        let top = match self.column(x) {
+            Some(column) => column.iter().position(|&solid| solid),
+            None => None,
+        };
+        top.unwrap_or(self.height) as f64
+    }
+
+    /// Where something at height `y` in column `x` comes to rest: on the
+    /// first ground below it, or, if it is buried, on top of the ground it is
+    /// buried in.
+    pub fn ground_below(&self, x: usize, y: f64) -> f64 {
+        let mut row = (y.max(0.0) as usize).min(self.height);
+        if self.get(x, row) {
+            while row > 0 && self.get(x, row - 1) {
+                row -= 1;
+            }
+        } else {
+            while row < self.height && !self.get(x, row) {
+                row += 1;
+            }
+        }
+        row as f64
+    }
+
+    /// The solid and empty stretches of column `x`, top to bottom, as
+    /// `(start, end, solid)` with `end` exclusive.
+    pub fn runs(&self, x: usize) -> Vec<(usize, usize, bool)> {
+        let mut runs: Vec<(usize, usize, bool)> = Vec::new();
+        for (y, &solid) in self.column(x).unwrap_or_default().iter().enumerate() {
+            match runs.last_mut() {
+                Some(run) if run.2 == solid => run.1 = y + 1,
+                _ => runs.push((y, y + 1, solid)),
+            }
+        }
+        runs
+    }
+
+    /// Sets every pixel whose center is within `radius` of `center` to
+    /// `solid`, and returns the range of columns that may have changed.
+    pub fn fill_circle(&mut self, center: Point, radius: f64, solid: bool) -> (usize, usize) {
+        let (min_x, max_x) = self.columns(center.x - radius, center.x + radius);
+        for x in min_x..max_x {
+            let dx = x as f64 + 0.5 - center.x;
+            let half = (radius * radius - dx * dx).max(0.0).sqrt();
+            let (min_y, max_y) = self.rows(center.y - half, center.y + half);
+            for y in min_y..max_y {
+                let dy = y as f64 + 0.5 - center.y;
+                if dx * dx + dy * dy <= radius * radius {
+                    self.set(x, y, solid);
+                }
+            }
+        }
+        (min_x, max_x)
+    }
+
+    /// Sets every pixel whose center is within `radius` of the line from
+    /// `from` to `to` to `solid`, and returns the range of columns that may
+    /// have changed.
+    pub fn fill_line(
+        &mut self,
+        from: Point,
+        to: Point,
+        radius: f64,
+        solid: bool,
+    ) -> (usize, usize) {
+        let (min_x, max_x) = self.columns(from.x.min(to.x) - radius, from.x.max(to.x) + radius);
+        let (min_y, max_y) = self.rows(from.y.min(to.y) - radius, from.y.max(to.y) + radius);
+        let (lx, ly) = (to.x - from.x, to.y - from.y);
+        let length_squared = lx * lx + ly * ly;
+        for x in min_x..max_x {
+            for y in min_y..max_y {
+                let (px, py) = (x as f64 + 0.5 - from.x, y as f64 + 0.5 - from.y);
+                // how far along the line the nearest point to the pixel is
+                let t = if length_squared > 0.0 {
+                    ((px * lx + py * ly) / length_squared).clamp(0.0, 1.0)
+                } else {
+                    0.0
+                };
+                let (dx, dy) = (px - t * lx, py - t * ly);
+                if dx * dx + dy * dy <= radius * radius {
+                    self.set(x, y, solid);
+                }
+            }
+        }
+        (min_x, max_x)
+    }
+
+    /// Moves every pixel of ground that isn't joined to the bottom of the
+    /// field, directly or through other ground, one pixel down. Returns the
+    /// range of columns that changed, or `None` if nothing was loose.
+    pub fn drop_loose(&mut self) -> Option<(usize, usize)> {
+        let mut changed = None;
+        for (x, start, end) in self.loose_runs() {
+            if end < self.height && !self.get(x, end) {
+                self.set(x, start, false);
+                self.set(x, end, true);
+                changed = Some(extend(changed, x));
+            }
+        }
+        changed
+    }
+
+    /// Sheds earth from the top of each column in `columns` onto a
+    /// neighbouring column whose surface is more than `max_slope` px lower,
+    /// half of the excess at a time. Returns the range of columns that
+    /// changed, or `None` if every slope was stable.
+    pub fn slide(
+        &mut self,
+        (min_x, max_x): (usize, usize),
+        max_slope: f64,
+    ) -> Option<(usize, usize)> {
+        let max_x = max_x.min(self.width);
+        // earth shed down a 1 px step would land level with where it was and
+        // could trade places back and forth forever
+        let max_slope = max_slope.max(1.0);
+        // surfaces of the columns from `first` on, kept up to date as earth
+        // moves so that columns aren't scanned again
+        let first = min_x.saturating_sub(1);
+        let mut tops: Vec<usize> = (first..(max_x + 1).min(self.width))
+            .map(|x| self.surface(x) as usize)
+            .collect();
+        let mut changed = None;
+        for x in min_x..max_x {
+            // the lower of the two neighbours, if it is too far down
+            let lowest = [x.checked_sub(1), Some(x + 1)]
+                .iter()
+                .flatten()
+                .copied()
+                .filter(|&n| n >= first && n - first < tops.len())
+                .filter(|&n| (tops[n - first] as f64 - tops[x - first] as f64) > max_slope)
+                .max_by_key(|&n| (tops[n - first], n < x));
+            let n = match lowest {
+                Some(n) => n,
+                None => continue,
+            };
+            while (tops[n - first] as f64 - tops[x - first] as f64) > max_slope {
+                let (from, to) = (tops[x - first], tops[n - first]);
+                self.set(x, from, false);
+                self.set(n, to - 1, true);
+                tops[x - first] = if self.get(x, from + 1) {
+                    from + 1
+                } else {
+                    self.surface(x) as usize
+                };
+                tops[n - first] = to - 1;
+            }
+            changed = Some(extend(Some(extend(changed, x)), n));
+        }
+        changed
+    }
+
+    // solid runs, as `(x, start, end)`, that aren't held up by the bottom of
+    // the field through any other ground
+    fn loose_runs(&self) -> Vec<(usize, usize, usize)> {
+        let columns: Vec<Vec<(usize, usize)>> = (0..self.width)
+            .map(|x| {
+                self.runs(x)
+                    .into_iter()
+                    .filter(|&(_, _, solid)| solid)
+                    .map(|(start, end, _)| (start, end))
+                    .collect()
+            })
+            .collect();
+        let mut supported: Vec<Vec<bool>> =
+            columns.iter().map(|runs| vec![false; runs.len()]).collect();
+        let mut stack: Vec<(usize, usize)> = Vec::new();
+        for (x, runs) in columns.iter().enumerate() {
+            if let Some(&(_, end)) = runs.last() {
+                if end == self.height {
+                    stack.push((x, runs.len() - 1));
+                }
+            }
+        }
+        // runs in one column only touch runs beside them
+        while let Some((x, i)) = stack.pop() {
+            if supported[x][i] {
+                continue;
+            }
+            supported[x][i] = true;
+            let (start, end) = columns[x][i];
+            for n in [x.wrapping_sub(1), x + 1] {
+                if let Some(runs) = columns.get(n) {
+                    for (j, &(s, e)) in runs.iter().enumerate() {
+                        if s < end && start < e && !supported[n][j] {
+                            stack.push((n, j));
+                        }
+                    }
+                }
+            }
+        }
+        let mut loose = Vec::new();
+        for (x, runs) in columns.iter().enumerate() {
+            for (i, &(start, end)) in runs.iter().enumerate() {
+                if !supported[x][i] {
+                    loose.push((x, start, end));
+                }
+            }
+        }
+        loose
+    }
+
+    fn column(&self, x: usize) -> Option<&[bool]> {
+        self.solid.get(x * self.height..(x + 1) * self.height)
+    }
+
+    // the columns from `min` up to `max`, clipped to the mask
+    fn columns(&self, min: f64, max: f64) -> (usize, usize) {
+        clip(min, max, self.width)
+    }
+
+    fn rows(&self, min: f64, max: f64) -> (usize, usize) {
+        clip(min, max, self.height)
+    }
+}
+
+// a range of columns grown to take in `x`, with the end exclusive
+fn extend(range: Option<(usize, usize)>, x: usize) -> (usize, usize) {
+    match range {
+        Some((min, max)) => (min.min(x), max.max(x + 1)),
+        None => (x, x + 1),
+    }
+}
+
+fn clip(min: f64, max: f64, size: usize) -> (usize, usize) {
+    let min = (min.floor().max(0.0) as usize).min(size);
+    let max = ((max.ceil().max(0.0) as usize) + 1).min(size);
+    (min, max.max(min))
+}
@@ -0,0 +1,66 @@
+//! The game rules, with no dependency on the browser.
+//!
+//! Everything needed to play a match out (terrain, tanks, ballistics and
+//! turns) lives here, so a game can run natively from `cargo test`, a server
+//! or a bot tournament. The canvas/audio layer in `web` only consumes it.
+
+mod ai;
+mod arena;
+mod ballistics;
+mod bot;
+mod broadcast;
+mod config;
+mod controls;
+mod editor;
+mod game;
+mod generator;
+mod lobby;
+mod map;
+mod mask;
+mod net;
+mod plugin;
+mod point;
+mod replay;
+mod seed;
+mod settings;
+mod snapshot;
+mod state;
+mod tank;
+mod terrain;
+mod tournament;
+mod warhead;
+mod weapon;
+
+pub use ai::{ComputerBot, Difficulty};
+pub use arena::{play_match, MatchError, MatchReport, MAX_TURNS};
+pub use ballistics::{predict_impact, trajectory, Projectile, Trajectory, GRAVITY, TICK_MS};
+pub use bot::{play_bot_turn, Action, Bot, BotRegistry, Controller, Observation, ShotResult};
+pub use broadcast::{Broadcast, Spectator};
+pub use config::{Config, TANK_WIDTH};
+pub use controls::{Aim, Command, Drag, Keymap, KeymapError};
+pub use editor::MapEditor;
+pub use game::{Damage, DamageCause, Game, Impact, MatchResult, PlayerId, ShotUpdate};
+pub use generator::{
+    Flat, Islands, MidpointDisplacement, Noise, Plateaus, RandomWalk, TerrainGenerator,
+    TerrainSettings,
+};
+pub use lobby::{ConnectionId, Lobby};
+pub use map::{Column, Hazard, Map, MapError, MAP_VERSION};
+pub use mask::TerrainMask;
+pub use net::{ClientMessage, Lockstep, NetError, ServerMessage, Turn, PROTOCOL_VERSION};
+pub use plugin::{Plugin, PluginBot, PluginError, PluginLimits, PLUGIN_ABI_VERSION};
+pub use point::Point;
+pub use replay::{Move, Playback, Replay, ReplayError, REPLAY_VERSION};
+pub use seed::{match_rng, round_seed, MatchRng, Stream};
+pub use settings::{MatchSettings, SettingsError};
+pub use snapshot::{SavedRound, SavedTank, Snapshot, SnapshotError, SNAPSHOT_VERSION};
+pub use state::{Event, GameError, Phase};
+pub use tank::{Tank, PLAYER_COLORS};
+pub use terrain::{Terrain, SKY_COLORS, TERRAIN_COLORS};
+pub use tournament::{
+    expected_score, Format, MatchRecord, Standing, Standings, Tournament, TournamentError,
+    INITIAL_RATING, K_FACTOR,
+};
+pub use weapon::{Effect, Inventory, Weapon};
+
+use warhead::{Step, Warhead};
@@ -0,0 +1,295 @@
+//! Playing a match between computers over the network.
+//!
+//! Only moves travel. Every client simulates the whole match itself, from
+//! the seed and settings a relay hands out, in lockstep with the others: a
+//! `Lobby` is the relay's side, seating players in rooms and passing each
+//! move on to the rest of the room, and a `Lockstep` is a client's, sending
+//! the local player's moves and applying everyone's in the order the relay
+//! has them. Each move carries the `Game::checksum` of the game it was made
+//! in, so a client whose simulation has drifted finds out on that turn
+//! instead of playing on in a different match. Spectators follow a room the
+//! same way, without a seat; see `broadcast`.
+//!
+//! Messages are JSON, tagged with their `"type"`.
+
+use super::{Action, Game, GameError, MatchSettings, Move, Phase, PlayerId, Snapshot};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fmt;
+
+/// The version of the protocol this build speaks. Clients must join with
+/// the relay's version.
+pub const PROTOCOL_VERSION: u32 = 2;
+
+/// A move as the relay passes it on.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Turn {
+    /// `Game::checksum` of the game just before the move.
+    pub checksum: u64,
+    #[serde(rename = "move")]
+    pub made: Move,
+}
+
+/// What a client sends the relay.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum ClientMessage {
+    /// Takes the next free seat in `room`, opening the room with `settings`
+    /// (or the defaults) if nobody is in it yet.
+    Join {
+        version: u32,
+        room: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        settings: Option<MatchSettings>,
+    },
+    /// Watches `room` without taking a seat, starting from the match as it
+    /// stands.
+    Watch { version: u32, room: String },
+    /// Takes `seat` back after the connection dropped, with the token it was
+    /// given. `since` is how many moves of the match the client already has.
+    Rejoin {
+        version: u32,
+        room: String,
+        seat: u8,
+        token: u64,
+        since: usize,
+    },
+    /// The move made on turn `turn` of the match, counting the turns of
+    /// every round from 0, with the checksum of the game before it.
+    Move {
+        turn: usize,
+        checksum: u64,
+        #[serde(rename = "move")]
+        made: Move,
+    },
+}
+
+/// What the relay sends a client.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "kebab-case")]
+pub enum ServerMessage {
+    /// The client has `seat` in `room`, and can take it back with `token`.
+    /// `turns` is how many moves of the match the relay has.
+    Joined {
+        room: String,
+        seat: u8,
+        token: u64,
+        seed: u64,
+        settings: MatchSettings,
+        started: bool,
+        turns: usize,
+    },
+    /// The client is watching `room`. `snapshot` is the match after its
+    /// first `turns` moves; the rest follow as `Moves`.
+    Watching {
+        room: String,
+        turns: usize,
+        snapshot: Box<Snapshot>,
+    },
+    /// Every seat is taken, so the match begins.
+    Started,
+    /// The moves of the match from turn `from` on, oldest first.
+    Moves { from: usize, moves: Vec<Turn> },
+    /// The player in `seat` has connected or dropped out.
+    Presence { seat: u8, connected: bool },
+    /// The last message was refused.
+    Error { message: String },
+}
+
+impl ClientMessage {
+    pub fn from_json(text: &str) -> Result<ClientMessage, String> {
+        serde_json::from_str(text).map_err(|error| error.to_string())
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("messages are plain data")
+    }
+}
+
+impl ServerMessage {
+    pub fn from_json(text: &str) -> Result<ServerMessage, String> {
+        serde_json::from_str(text).map_err(|error| error.to_string())
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("messages are plain data")
+    }
+}
+
+/// Why a networked match can't go on.
+#[derive(Clone, Debug, PartialEq)]
+pub enum NetError {
+    /// The game was not the one the move was made in: this client's
+    /// simulation and the sender's have diverged.
+    Desync {
+        turn: usize,
+        expected: u64,
+        actual: u64,
+    },
+    /// The move was refused, here or, for a relayed move, in this client's
+    /// copy of the match.
+    Refused { turn: usize, error: GameError },
+}
+
+impl fmt::Display for NetError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            NetError::Desync {
+                turn,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "out of sync at turn {}: checksum {:016x}, expected {:016x}",
+                turn + 1,
+                actual,
+                expected
+            ),
+            NetError::Refused { turn, error } => {
+                write!(f, "move {} was refused: {}", turn + 1, error)
+            }
+        }
+    }
+}
+
+impl Error for NetError {}
+
+/// A client's side of a networked match: the seat it plays, and every move
+/// of the match it knows of, in order.
+///
+/// Local moves go through `fire` or `skip`, which make them and return the
+/// message to send. What the relay sends goes to `receive`, and `apply` then plays
+/// the moves from other seats into the game one at a time, whenever it is
+/// waiting for one. When a round ends with moves still to come, the caller
+/// starts the next round as it would locally; turns are counted across
+/// rounds.
+#[derive(Clone, Debug)]
+pub struct Lockstep {
+    seat: PlayerId,
+    turns: Vec<Turn>,
+    // how many of `turns` are in the game
+    played: usize,
+}
+
+impl Lockstep {
+    pub fn new(seat: PlayerId) -> Lockstep {
+        Lockstep {
+            seat,
+            turns: Vec::new(),
+            played: 0,
+        }
+    }
+
+    pub fn seat(&self) -> PlayerId {
+        self.seat
+    }
+
+    /// Moves of the match so far, whether played yet or not.
+    pub fn turns(&self) -> usize {
+        self.turns.len()
+    }
+
+    /// Whether there are moves from the relay waiting for `apply`.
+    pub fn has_pending(&self) -> bool {
+        self.played < self.turns.len()
+    }
+
+    /// Fires `action` for this client's seat, and returns the message that
+    /// tells the relay.
+    pub fn fire(&mut self, game: &mut Game, action: Action) -> Result<ClientMessage, NetError> {
+        self.play(game, |game, player| game.fire(player, action))
+    }
+
+    /// Gives up this client's turn, and returns the message that tells the
+    /// relay.
+    pub fn skip(&mut self, game: &mut Game) -> Result<ClientMessage, NetError> {
+        self.play(game, Game::skip_turn)
+    }
+
+    fn play(
+        &mut self,
+        game: &mut Game,
+        make: impl FnOnce(&mut Game, PlayerId) -> Result<(), GameError>,
+    ) -> Result<ClientMessage, NetError> {
+        let turn = self.turns.len();
+        let refused = |error| NetError::Refused { turn, error };
+        // a move the relay already has comes first
+        if let Some(pending) = self.turns.get(self.played) {
+            return Err(refused(GameError::NotYourTurn {
+                active: pending.made.player(),
+            }));
+        }
+        let checksum = game.checksum();
+        make(game, self.seat).map_err(refused)?;
+        // as `Game::fire` clamped it
+        let made = *game.moves().last().expect("a move was just made");
+        self.turns.push(Turn { checksum, made });
+        self.played += 1;
+        Ok(ClientMessage::Move {
+            turn,
+            checksum,
+            made,
+        })
+    }
+
+    /// Takes in a message from the relay. Returns the messages to send back,
+    /// which after a rejoin are the moves made here that the relay never got.
+    pub fn receive(&mut self, message: &ServerMessage) -> Vec<ClientMessage> {
+        match message {
+            ServerMessage::Joined { turns, .. } => self
+                .turns
+                .iter()
+                .enumerate()
+                .skip(*turns)
+                .map(|(turn, sent)| ClientMessage::Move {
+                    turn,
+                    checksum: sent.checksum,
+                    made: sent.made,
+                })
+                .collect(),
+            ServerMessage::Moves { from, moves } => {
+                // the relay sends each move once, but after a rejoin it may
+                // repeat ones this client has
+                for (turn, &relayed) in (*from..).zip(moves.iter()) {
+                    if turn == self.turns.len() {
+                        self.turns.push(relayed);
+                    }
+                }
+                Vec::new()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    /// Plays the next move from the relay into `game`, if there is one and
+    /// the game is waiting on a player. Returns the move, or the reason the
+    /// match can't go on.
+    pub fn apply(&mut self, game: &mut Game) -> Result<Option<Move>, NetError> {
+        let turn = self.played;
+        let pending = match self.turns.get(turn) {
+            Some(pending) if game.phase() == Phase::Aiming => *pending,
+            _ => return Ok(None),
+        };
+        play_turn(game, turn, &pending)?;
+        self.played += 1;
+        Ok(Some(pending.made))
+    }
+}
+
+// makes the move relayed as turn `turn` of the match in `game`, if it was
+// made in the same game
+pub(super) fn play_turn(game: &mut Game, turn: usize, relayed: &Turn) -> Result<(), NetError> {
+    let actual = game.checksum();
+    if actual != relayed.checksum {
+        return Err(NetError::Desync {
+            turn,
+            expected: relayed.checksum,
+            actual,
+        });
+    }
+    let made = match relayed.made {
+        Move::Fire { player, action } => game.fire(player, action),
+        Move::Skip { player } => game.skip_turn(player),
+    };
+    made.map_err(|error| NetError::Refused { turn, error })
+}
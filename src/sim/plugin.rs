@@ -0,0 +1,456 @@
+//! Bots compiled to WebAssembly, loaded at run time.
+//!
+//! A plugin is a `.wasm` module written in any language. It runs in a
+//! sandbox: it can't import anything, so it has no way to reach the host
+//! beyond the calls below, and it is cut off once it has used up the fuel it
+//! is given for a turn or tries to grow its memory past a cap. Fuel counts
+//! instructions rather than time, so a plugin decides the same way on every
+//! machine and replays of its matches hold.
+//!
+//! # The bot ABI, version 1
+//!
+//! The module must export:
+//!
+//! - `memory`, its linear memory;
+//! - `warbots_abi() -> i32`, returning the version of the ABI it was written
+//!   against, `1`;
+//! - `warbots_alloc(len: i32) -> i32`, returning where in memory the host may
+//!   write `len` bytes;
+//! - `warbots_decide(ptr: i32, len: i32) -> i64`, called each turn with the
+//!   UTF-8 JSON observation the host wrote at `ptr`. It returns where its
+//!   reply is, as the reply's offset in the high 32 bits and its length in
+//!   the low 32 bits.
+//!
+//! The observation looks like this, with the field's constants under
+//! `field` and every tank in `tank` and `enemies` described the same way:
+//!
+//! ```json
+//! {
+//!   "abi": 1,
+//!   "player": 0,
+//!   "random": 2868764263,
+//!   "field": {"width": 1000.0, "height": 600.0, "gravity": 0.0005, "...": 0},
+//!   "wind": -0.00012,
+//!   "heights": [412.0, 412.5, "..."],
+//!   "tank": {"player": 0, "x": 120.0, "y": 400.0, "facing": 1.0,
+//!            "health": 100.0, "ammo": {"nuke": 1}},
+//!   "enemies": [{"player": 1, "x": 840.0, "...": 0}],
+//!   "shots": [{"player": 1, "power": 250.0, "angle": 60.0,
+//!              "weapon": "missile", "impact": [402.5, 380.0]}]
+//! }
+//! ```
+//!
+//! `field.sea_level` is how far down the surface of the sea lies, or `null`
+//! if there is no sea; shots that fall into it are lost.
+//!
+//! `random` is fresh each turn and drawn from the match seed, for plugins
+//! that need randomness. The reply is an action, in the same units as
+//! `Action`, or `null` to pass:
+//!
+//! ```json
+//! {"power": 310.0, "angle": 62.5, "weapon": "missile"}
+//! ```
+//!
+//! A plugin that traps, runs out of fuel or replies with anything else
+//! passes that turn and every turn after it.
+
+use super::{Action, Bot, BotRegistry, MatchRng, Observation, Tank, Weapon};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+use wasmi::core::TrapCode;
+use wasmi::{Engine, Linker, Memory, Module, Store, StoreLimits, StoreLimitsBuilder, TypedFunc};
+
+/// The version of the bot ABI this build speaks.
+pub const PLUGIN_ABI_VERSION: i32 = 1;
+// longest reply read back from a plugin, in bytes
+const MAX_REPLY: usize = 64 * 1024;
+
+/// How much a plugin may use.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PluginLimits {
+    /// Fuel for starting the module up, and again for each turn. Most
+    /// instructions burn one.
+    pub fuel: u64,
+    /// Largest its memory may grow to, in bytes.
+    pub memory: usize,
+    /// Largest module that is loaded, in bytes.
+    pub module_size: usize,
+}
+
+impl Default for PluginLimits {
+    fn default() -> PluginLimits {
+        PluginLimits {
+            fuel: 50_000_000,
+            memory: 32 * 1024 * 1024,
+            module_size: 8 * 1024 * 1024,
+        }
+    }
+}
+
+/// Why a plugin can't be loaded, or stopped playing.
+#[derive(Clone, Debug, PartialEq)]
+pub enum PluginError {
+    /// The bytes aren't a WebAssembly module that can be run.
+    Invalid(String),
+    /// Plugins can't import anything.
+    Import {
+        module: String,
+        name: String,
+    },
+    /// A required export is missing or has the wrong type.
+    Export(String),
+    UnsupportedAbi(i32),
+    /// It used up its fuel for the turn.
+    OutOfFuel,
+    /// It trapped, e.g. on `unreachable` or memory out of bounds.
+    Trap(String),
+    /// It broke the ABI, e.g. with a reply outside its memory or one that
+    /// isn't an action.
+    Protocol(String),
+}
+
+impl fmt::Display for PluginError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PluginError::Invalid(message) => write!(f, "invalid plugin: {}", message),
+            PluginError::Import { module, name } => write!(
+                f,
+                "plugins can't import anything, but this one imports {}.{}",
+                module, name
+            ),
+            PluginError::Export(message) => write!(f, "plugin export {}", message),
+            PluginError::UnsupportedAbi(version) => write!(
+                f,
+                "plugin ABI version {} isn't supported; expected {}",
+                version, PLUGIN_ABI_VERSION
+            ),
+            PluginError::OutOfFuel => write!(f, "plugin ran out of fuel"),
+            PluginError::Trap(message) => write!(f, "plugin trapped: {}", message),
+            PluginError::Protocol(message) => write!(f, "plugin broke the ABI: {}", message),
+        }
+    }
+}
+
+impl Error for PluginError {}
+
+impl From<wasmi::Error> for PluginError {
+    fn from(error: wasmi::Error) -> PluginError {
+        match error.as_trap_code() {
+            Some(TrapCode::OutOfFuel) => PluginError::OutOfFuel,
+            _ => PluginError::Trap(error.to_string()),
+        }
+    }
+}
+
+/// A loaded plugin, from which any number of bots can be started.
+#[derive(Debug)]
+pub struct Plugin {
+    name: String,
+    limits: PluginLimits,
+    engine: Engine,
+    module: Module,
+}
+
+impl Plugin {
+    /// Loads the module in `wasm` as the bot called `name`, checking that it
+    /// starts up and speaks this ABI.
+    pub fn new(name: &str, wasm: &[u8], limits: PluginLimits) -> Result<Plugin, PluginError> {
+        if wasm.len() > limits.module_size {
+            return Err(PluginError::Invalid(format!(
+                "the module is {} bytes, over the limit of {}",
+                wasm.len(),
+                limits.module_size
+            )));
+        }
+        let mut config = wasmi::Config::default();
+        config.consume_fuel(true);
+        let engine = Engine::new(&config);
+        let module =
+            Module::new(&engine, wasm).map_err(|error| PluginError::Invalid(error.to_string()))?;
+        if let Some(import) = module.imports().next() {
+            return Err(PluginError::Import {
+                module: import.module().to_string(),
+                name: import.name().to_string(),
+            });
+        }
+        let plugin = Plugin {
+            name: name.to_string(),
+            limits,
+            engine,
+            module,
+        };
+        plugin.start()?;
+        Ok(plugin)
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// A fresh instance of the plugin, drawing the randomness it is offered
+    /// from `rng`. One that fails to start passes every turn.
+    pub fn bot(&self, rng: MatchRng) -> PluginBot {
+        PluginBot {
+            name: self.name.clone(),
+            rng,
+            running: self.start(),
+        }
+    }
+
+    fn start(&self) -> Result<Running, PluginError> {
+        let limits = StoreLimitsBuilder::new()
+            .memory_size(self.limits.memory)
+            .instances(1)
+            .memories(1)
+            .tables(1)
+            .build();
+        let mut store = Store::new(&self.engine, limits);
+        store.limiter(|limits| limits);
+        store.set_fuel(self.limits.fuel).expect("fuel is turned on");
+        // e.g. for asking for more memory up front than it may have
+        let instance = Linker::new(&self.engine)
+            .instantiate(&mut store, &self.module)
+            .map_err(|error| PluginError::Invalid(error.to_string()))?
+            .start(&mut store)?;
+
+        let memory = instance
+            .get_memory(&store, "memory")
+            .ok_or_else(|| PluginError::Export("memory is missing".to_string()))?;
+        let export = |name: &'static str, signature: &'static str| {
+            move |_| PluginError::Export(format!("{} must be a function {}", name, signature))
+        };
+        let abi: TypedFunc<(), i32> = instance
+            .get_typed_func(&store, "warbots_abi")
+            .map_err(export("warbots_abi", "() -> i32"))?;
+        let alloc = instance
+            .get_typed_func(&store, "warbots_alloc")
+            .map_err(export("warbots_alloc", "(i32) -> i32"))?;
+        let decide = instance
+            .get_typed_func(&store, "warbots_decide")
+            .map_err(export("warbots_decide", "(i32, i32) -> i64"))?;
+        let version = abi.call(&mut store, ())?;
+        if version != PLUGIN_ABI_VERSION {
+            return Err(PluginError::UnsupportedAbi(version));
+        }
+        Ok(Running {
+            fuel: self.limits.fuel,
+            store,
+            memory,
+            alloc,
+            decide,
+        })
+    }
+}
+
+/// A bot played by an instance of a `Plugin`.
+pub struct PluginBot {
+    name: String,
+    rng: MatchRng,
+    running: Result<Running, PluginError>,
+}
+
+// an instance of a plugin, ready for its turn
+struct Running {
+    fuel: u64,
+    store: Store<StoreLimits>,
+    memory: Memory,
+    alloc: TypedFunc<i32, i32>,
+    decide: TypedFunc<(i32, i32), i64>,
+}
+
+impl PluginBot {
+    /// Why the plugin has stopped playing, if it has.
+    pub fn fault(&self) -> Option<&PluginError> {
+        self.running.as_ref().err()
+    }
+}
+
+impl Bot for PluginBot {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn decide(&mut self, observation: &Observation) -> Option<Action> {
+        let random = self.rng.gen();
+        let running = self.running.as_mut().ok()?;
+        match running.decide(&request(observation, random)) {
+            Ok(action) => action,
+            Err(error) => {
+                self.running = Err(error);
+                None
+            }
+        }
+    }
+}
+
+impl Running {
+    fn decide(&mut self, request: &str) -> Result<Option<Action>, PluginError> {
+        self.store.set_fuel(self.fuel).expect("fuel is turned on");
+        let len = request.len() as i32;
+        let ptr = self.alloc.call(&mut self.store, len)?;
+        self.memory
+            .write(&mut self.store, ptr as u32 as usize, request.as_bytes())
+            .map_err(|_| {
+                PluginError::Protocol(format!("{} bytes at {} is out of memory", len, ptr))
+            })?;
+
+        let packed = self.decide.call(&mut self.store, (ptr, len))? as u64;
+        let (ptr, len) = ((packed >> 32) as usize, (packed & 0xFFFF_FFFF) as usize);
+        if len > MAX_REPLY {
+            return Err(PluginError::Protocol(format!(
+                "the reply is {} bytes, over the limit of {}",
+                len, MAX_REPLY
+            )));
+        }
+        let mut reply = vec![0; len];
+        self.memory
+            .read(&self.store, ptr, &mut reply)
+            .map_err(|_| {
+                PluginError::Protocol(format!(
+                    "a reply of {} bytes at {} is out of memory",
+                    len, ptr
+                ))
+            })?;
+        let reply: Option<Reply> = serde_json::from_slice(&reply)
+            .map_err(|error| PluginError::Protocol(format!("invalid reply: {}", error)))?;
+        match reply {
+            Some(reply) => {
+                let weapon = Weapon::from_name(&reply.weapon).ok_or_else(|| {
+                    PluginError::Protocol(format!("unknown weapon {:?}", reply.weapon))
+                })?;
+                Ok(Some(Action {
+                    power: reply.power,
+                    angle: reply.angle,
+                    weapon,
+                }))
+            }
+            None => Ok(None),
+        }
+    }
+}
+
+impl BotRegistry {
+    /// Adds `plugin` as a bot under its name, replacing any existing one.
+    pub fn register_plugin(&mut self, plugin: Plugin) {
+        let name = plugin.name().to_string();
+        self.register(&name, move |rng| Box::new(plugin.bot(rng)));
+    }
+}
+
+// the observation as a plugin is sent it
+#[derive(Serialize)]
+struct Request<'a> {
+    abi: i32,
+    player: u8,
+    random: u32,
+    field: Field,
+    wind: f64,
+    heights: &'a [f64],
+    tank: TankView,
+    enemies: Vec<TankView>,
+    shots: Vec<ShotView>,
+}
+
+#[derive(Serialize)]
+struct Field {
+    width: f64,
+    height: f64,
+    gravity: f64,
+    max_wind: f64,
+    min_power: u16,
+    max_power: u16,
+    min_angle: u8,
+    max_angle: u8,
+    power_normalizer: f64,
+    projectile_speed_modifier: f64,
+    tank_width: f64,
+    tank_height: f64,
+    turret_length: f64,
+    sea_level: Option<f64>,
+}
+
+#[derive(Serialize)]
+struct TankView {
+    player: u8,
+    x: f64,
+    y: f64,
+    facing: f64,
+    health: f64,
+    ammo: BTreeMap<&'static str, u32>,
+}
+
+#[derive(Serialize)]
+struct ShotView {
+    player: u8,
+    power: f64,
+    angle: f64,
+    weapon: &'static str,
+    impact: Option<[f64; 2]>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Reply {
+    power: f64,
+    angle: f64,
+    weapon: String,
+}
+
+fn request(observation: &Observation, random: u32) -> String {
+    let config = &observation.config;
+    let request = Request {
+        abi: PLUGIN_ABI_VERSION,
+        player: observation.player.0,
+        random,
+        field: Field {
+            width: config.width(),
+            height: config.height(),
+            gravity: config.gravity(),
+            max_wind: config.max_wind(),
+            min_power: config.min_power(),
+            max_power: config.max_power(),
+            min_angle: config.min_angle(),
+            max_angle: config.max_angle(),
+            power_normalizer: config.power_normalizer(),
+            projectile_speed_modifier: config.projectile_speed_modifier(),
+            tank_width: config.tank_width(),
+            tank_height: config.tank_height(),
+            turret_length: config.turret_length(),
+            sea_level: observation.sea_level,
+        },
+        wind: observation.wind,
+        heights: &observation.heights,
+        tank: tank_view(&observation.own_tank),
+        enemies: observation.enemies.iter().map(tank_view).collect(),
+        shots: observation
+            .shots
+            .iter()
+            .map(|shot| ShotView {
+                player: shot.player.0,
+                power: shot.action.power,
+                angle: shot.action.angle,
+                weapon: shot.action.weapon.name(),
+                impact: shot.impact.map(|point| [point.x(), point.y()]),
+            })
+            .collect(),
+    };
+    serde_json::to_string(&request).expect("observations are always valid JSON")
+}
+
+fn tank_view(tank: &Tank) -> TankView {
+    let location = tank.location();
+    TankView {
+        player: tank.player().0,
+        x: location.x(),
+        y: location.y(),
+        facing: tank.facing(),
+        health: tank.health(),
+        ammo: Weapon::ALL
+            .iter()
+            .filter_map(|&weapon| Some((weapon.name(), tank.inventory().count(weapon)?)))
+            .collect(),
+    }
+}
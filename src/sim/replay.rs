@@ -0,0 +1,366 @@
+//! Recording matches and playing them back.
+//!
+//! A match is decided entirely by its seed, its settings, its map if it had
+//! one, and what each player did on each turn, so that is all a `Replay`
+//! stores. `Playback` re-simulates a replay turn by turn, and can jump to any
+//! turn by playing the round it is in from the start.
+
+use super::{
+    round_seed, Action, Config, Event, Game, GameError, Map, MapError, MatchSettings, Phase,
+    PlayerId, SettingsError, Weapon,
+};
+use serde::{Deserialize, Serialize};
+use std::convert::TryFrom;
+use std::error::Error;
+use std::fmt;
+
+/// The version of the replay format this build reads and writes.
+pub const REPLAY_VERSION: u32 = 1;
+
+/// What a player did with a turn.
+///
+/// Stored as `[player, power, angle, weapon]` for a shot and as the bare
+/// player number for a skipped turn, to keep long matches small.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "Record", into = "Record")]
+pub enum Move {
+    /// Fired `action`, as clamped by `Game::fire`.
+    Fire {
+        player: PlayerId,
+        action: Action,
+    },
+    Skip {
+        player: PlayerId,
+    },
+}
+
+impl Move {
+    pub fn player(&self) -> PlayerId {
+        match *self {
+            Move::Fire { player, .. } | Move::Skip { player } => player,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum Record {
+    Fire(u8, f64, f64, String),
+    Skip(u8),
+}
+
+impl From<Move> for Record {
+    fn from(action: Move) -> Record {
+        match action {
+            Move::Fire { player, action } => Record::Fire(
+                player.0,
+                action.power,
+                action.angle,
+                action.weapon.name().to_string(),
+            ),
+            Move::Skip { player } => Record::Skip(player.0),
+        }
+    }
+}
+
+impl TryFrom<Record> for Move {
+    type Error = String;
+
+    fn try_from(record: Record) -> Result<Move, String> {
+        match record {
+            Record::Fire(player, power, angle, weapon) => {
+                let weapon = Weapon::from_name(&weapon)
+                    .ok_or_else(|| format!("unknown weapon {:?}", weapon))?;
+                Ok(Move::Fire {
+                    player: PlayerId(player),
+                    action: Action {
+                        power,
+                        angle,
+                        weapon,
+                    },
+                })
+            }
+            Record::Skip(player) => Ok(Move::Skip {
+                player: PlayerId(player),
+            }),
+        }
+    }
+}
+
+/// Everything needed to play a match again exactly as it went.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Replay {
+    pub version: u32,
+    /// The match seed; each round is played on `round_seed` of it.
+    pub seed: u64,
+    pub settings: MatchSettings,
+    /// The map every round was played on, if the terrain wasn't generated.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub map: Option<Map>,
+    /// The moves made in each round, oldest first. The last round may not
+    /// have been played to the end.
+    pub rounds: Vec<Vec<Move>>,
+}
+
+/// Why a `Replay` can't be loaded or played.
+#[derive(Clone, Debug, PartialEq)]
+pub enum ReplayError {
+    /// The JSON couldn't be read.
+    Parse(String),
+    /// The replay was written in a version of the format this build can't
+    /// read.
+    UnsupportedVersion(u32),
+    Settings(SettingsError),
+    Map(MapError),
+    /// A recorded move was refused when played back, so the replay doesn't
+    /// describe a match this build would play.
+    Diverged {
+        round: usize,
+        turn: usize,
+        error: GameError,
+    },
+    /// There is no such turn to seek to.
+    NoSuchTurn {
+        round: usize,
+        turn: usize,
+    },
+}
+
+impl fmt::Display for ReplayError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ReplayError::Parse(message) => write!(f, "invalid replay: {}", message),
+            ReplayError::UnsupportedVersion(version) => write!(
+                f,
+                "replay format version {} isn't supported; expected {}",
+                version, REPLAY_VERSION
+            ),
+            ReplayError::Settings(error) => write!(f, "invalid replay: {}", error),
+            ReplayError::Map(error) => write!(f, "invalid replay: {}", error),
+            ReplayError::Diverged { round, turn, error } => write!(
+                f,
+                "replay diverged at round {} turn {}: {}",
+                round + 1,
+                turn + 1,
+                error
+            ),
+            ReplayError::NoSuchTurn { round, turn } => {
+                write!(f, "the replay has no round {} turn {}", round + 1, turn + 1)
+            }
+        }
+    }
+}
+
+impl Error for ReplayError {}
+
+impl From<SettingsError> for ReplayError {
+    fn from(error: SettingsError) -> ReplayError {
+        ReplayError::Settings(error)
+    }
+}
+
+impl From<MapError> for ReplayError {
+    fn from(error: MapError) -> ReplayError {
+        ReplayError::Map(error)
+    }
+}
+
+// read first, so that a replay from a newer format is reported as such
+// rather than as whatever field it no longer parses at
+#[derive(Deserialize)]
+struct Header {
+    version: u32,
+}
+
+impl Replay {
+    /// A replay of a match that hasn't had any moves yet.
+    pub fn new(seed: u64, settings: MatchSettings, map: Option<Map>) -> Replay {
+        Replay {
+            version: REPLAY_VERSION,
+            seed,
+            settings,
+            map,
+            rounds: Vec::new(),
+        }
+    }
+
+    /// Adds the moves made in `game` as the next round.
+    pub fn record(&mut self, game: &Game) {
+        self.rounds.push(game.moves().to_vec());
+    }
+
+    pub fn from_json(text: &str) -> Result<Replay, ReplayError> {
+        let parse = |error: serde_json::Error| ReplayError::Parse(error.to_string());
+        let header: Header = serde_json::from_str(text).map_err(parse)?;
+        if header.version != REPLAY_VERSION {
+            return Err(ReplayError::UnsupportedVersion(header.version));
+        }
+        let replay: Replay = serde_json::from_str(text).map_err(parse)?;
+        replay.settings.validate()?;
+        if let Some(map) = &replay.map {
+            map.validate()?;
+        }
+        Ok(replay)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("replays are always valid JSON")
+    }
+}
+
+/// A replay being played back.
+///
+/// `step` advances it one tick at a time, like `Game::step`, making each
+/// recorded move when its turn comes and starting each round after the last
+/// one ends. The position is counted in `round` and `turn`, the number of
+/// moves made so far in the round.
+#[derive(Clone, Debug)]
+pub struct Playback {
+    replay: Replay,
+    config: Config,
+    game: Game,
+    round: usize,
+    turn: usize,
+}
+
+impl Playback {
+    /// Starts playing `replay` from the first turn.
+    pub fn new(replay: Replay) -> Result<Playback, ReplayError> {
+        let config = replay.settings.config()?;
+        let game = round_game(&replay, &config, 0)?;
+        Ok(Playback {
+            replay,
+            config,
+            game,
+            round: 0,
+            turn: 0,
+        })
+    }
+
+    pub fn replay(&self) -> &Replay {
+        &self.replay
+    }
+
+    /// The round being played back as it is now.
+    pub fn game(&self) -> &Game {
+        &self.game
+    }
+
+    /// Takes the events the game emitted since the last call, as
+    /// `Game::drain_events`. Events from a round that has been left are
+    /// dropped.
+    pub fn drain_events(&mut self) -> Vec<Event> {
+        self.game.drain_events()
+    }
+
+    pub fn round(&self) -> usize {
+        self.round
+    }
+
+    pub fn turn(&self) -> usize {
+        self.turn
+    }
+
+    /// How many moves were recorded in `round`.
+    pub fn turns(&self, round: usize) -> usize {
+        self.replay.rounds.get(round).map_or(0, Vec::len)
+    }
+
+    /// Whether everything recorded has been played.
+    pub fn is_finished(&self) -> bool {
+        match self.game.phase() {
+            Phase::Aiming => self.turn >= self.turns(self.round),
+            Phase::Over => self.round + 1 >= self.replay.rounds.len(),
+            Phase::InFlight | Phase::Resolving | Phase::TurnTransition => false,
+        }
+    }
+
+    /// Advances the playback by one tick, or makes the next move if the game
+    /// is waiting on one. Returns whether anything happened, which it
+    /// doesn't once the playback is finished.
+    pub fn step(&mut self) -> Result<bool, ReplayError> {
+        match self.game.phase() {
+            Phase::Aiming => {
+                let next = match self.replay.rounds.get(self.round) {
+                    Some(moves) => moves.get(self.turn).copied(),
+                    None => None,
+                };
+                let result = match next {
+                    Some(Move::Fire { player, action }) => self.game.fire(player, action),
+                    Some(Move::Skip { player }) => self.game.skip_turn(player),
+                    None => return Ok(false),
+                };
+                result.map_err(|error| self.diverged(error))?;
+                self.turn += 1;
+            }
+            Phase::Over => {
+                if self.turn < self.turns(self.round) {
+                    return Err(self.diverged(GameError::MatchOver));
+                }
+                if self.round + 1 >= self.replay.rounds.len() {
+                    return Ok(false);
+                }
+                self.round += 1;
+                self.turn = 0;
+                self.game = round_game(&self.replay, &self.config, self.round)?;
+            }
+            Phase::InFlight | Phase::Resolving | Phase::TurnTransition => {
+                self.game.step();
+            }
+        }
+        Ok(true)
+    }
+
+    /// Plays the next move through to the start of the turn after it, or on
+    /// into the next round if this one is over. Returns whether anything
+    /// happened.
+    pub fn next_turn(&mut self) -> Result<bool, ReplayError> {
+        if !self.step()? {
+            return Ok(false);
+        }
+        while !matches!(self.game.phase(), Phase::Aiming | Phase::Over) {
+            self.step()?;
+        }
+        Ok(true)
+    }
+
+    /// Jumps to just before move `turn` of `round` is made, or to the end of
+    /// the round if `turn` is the number of moves in it. Going back replays
+    /// the round from its start.
+    pub fn seek(&mut self, round: usize, turn: usize) -> Result<(), ReplayError> {
+        if round >= self.replay.rounds.len() || turn > self.turns(round) {
+            return Err(ReplayError::NoSuchTurn { round, turn });
+        }
+        if round != self.round || turn < self.turn {
+            self.game = round_game(&self.replay, &self.config, round)?;
+            self.round = round;
+            self.turn = 0;
+        }
+        while self.round == round
+            && !(self.turn == turn && matches!(self.game.phase(), Phase::Aiming | Phase::Over))
+        {
+            if !self.step()? {
+                break;
+            }
+        }
+        Ok(())
+    }
+
+    fn diverged(&self, error: GameError) -> ReplayError {
+        ReplayError::Diverged {
+            round: self.round,
+            turn: self.turn,
+            error,
+        }
+    }
+}
+
+// the game `replay`'s round `round` starts as
+fn round_game(replay: &Replay, config: &Config, round: usize) -> Result<Game, ReplayError> {
+    let seed = round_seed(replay.seed, round as u32);
+    match &replay.map {
+        Some(map) => Ok(Game::with_map(config.clone(), map, seed)?),
+        None => Ok(Game::new(config.clone(), seed)),
+    }
+}
@@ -0,0 +1,51 @@
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+/// The generator behind every random decision in a match. ChaCha's output is
+/// specified, so a seed means the same thing on every platform and version.
+pub type MatchRng = ChaCha8Rng;
+
+/// Independent random streams derived from one match seed. Keeping them apart
+/// means that e.g. drawing one more random number during play can never change
+/// the terrain that a seed produces.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Stream {
+    Terrain,
+    /// Where the tanks start.
+    Spawn,
+    Events,
+    /// Private to the bot playing the n-th seat.
+    Player(u8),
+}
+
+impl Stream {
+    fn id(self) -> u64 {
+        match self {
+            Stream::Terrain => 0,
+            Stream::Events => 1,
+            Stream::Spawn => 2,
+            Stream::Player(n) => 16 + n as u64,
+        }
+    }
+}
+
+pub fn match_rng(seed: u64, stream: Stream) -> MatchRng {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    rng.set_stream(stream.id());
+    rng
+}
+
+/// Seed for the terrain and events of round `round` (counting from 0) of a
+/// match seeded with `seed`. The first round is just `seed`; later ones are
+/// mixed from it, so that no round of one match replays a round of a match
+/// on a nearby seed.
+pub fn round_seed(seed: u64, round: u32) -> u64 {
+    if round == 0 {
+        return seed;
+    }
+    // a splitmix64 step, `round` steps along from `seed`
+    let mut z = seed.wrapping_add((round as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15));
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
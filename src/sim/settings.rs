@@ -0,0 +1,193 @@
+use super::{Config, Inventory, TerrainSettings, Weapon, GRAVITY};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+
+/// Everything that can be chosen about a match before it starts.
+///
+/// Settings can be built in code, starting from `MatchSettings::default()`,
+/// or loaded from TOML or JSON, where any setting left out keeps its default:
+///
+/// ```toml
+/// width = 1200
+/// gravity = 0.0008
+///
+/// [loadout]
+/// nuke = 2
+/// "dirt bomb" = 5
+/// ```
+///
+/// `config` checks them and turns them into the `Config` a `Game` is started
+/// with.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MatchSettings {
+    /// Size of the field, in px.
+    pub width: u32,
+    pub height: u32,
+    pub players: u8,
+    /// Downward acceleration of projectiles, in px per unit of flight time
+    /// squared.
+    pub gravity: f64,
+    /// Strongest wind a turn can have, in the same units as `gravity`.
+    pub max_wind: f64,
+    pub tank_health: f64,
+    /// Steepest slope, in degrees, that earth can stand at after an
+    /// explosion before it slides; 90 keeps it from sliding at all.
+    pub angle_of_repose: f64,
+    /// How the ground is shaped at the start of each round.
+    pub terrain: TerrainSettings,
+    /// What each tank starts with of every limited weapon, by
+    /// `Weapon::name`. Weapons left out can't be fired; missiles are always
+    /// unlimited.
+    pub loadout: BTreeMap<String, u32>,
+    /// Matches played, on fresh terrain, to decide the winner.
+    pub rounds: u32,
+    /// Whether players aiming are shown where their shot will go, e.g. to
+    /// practice; competitive matches leave it off.
+    pub aim_preview: bool,
+}
+
+/// Why a `MatchSettings` can't be used.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SettingsError {
+    /// The TOML or JSON couldn't be read.
+    Parse(String),
+    OutOfRange {
+        setting: String,
+        value: f64,
+        min: f64,
+        max: f64,
+    },
+    UnknownWeapon(String),
+    /// The loadout lists the missile, which never runs out.
+    UnlimitedWeapon,
+}
+
+impl fmt::Display for SettingsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SettingsError::Parse(message) => write!(f, "invalid settings: {}", message),
+            SettingsError::OutOfRange {
+                setting,
+                value,
+                min,
+                max,
+            } if min == max => write!(f, "{} must be {}, not {}", setting, min, value),
+            SettingsError::OutOfRange {
+                setting,
+                value,
+                min,
+                max,
+            } => write!(
+                f,
+                "{} must be between {} and {}, not {}",
+                setting, min, max, value
+            ),
+            SettingsError::UnknownWeapon(name) => write!(
+                f,
+                "unknown weapon {:?} in loadout; expected one of {}",
+                name,
+                Weapon::ALL
+                    .iter()
+                    .map(|weapon| weapon.name())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            SettingsError::UnlimitedWeapon => {
+                write!(f, "missiles are unlimited and can't be part of the loadout")
+            }
+        }
+    }
+}
+
+impl Error for SettingsError {}
+
+impl MatchSettings {
+    pub fn from_toml(text: &str) -> Result<MatchSettings, SettingsError> {
+        toml::from_str(text).map_err(|error| SettingsError::Parse(error.to_string()))
+    }
+
+    pub fn from_json(text: &str) -> Result<MatchSettings, SettingsError> {
+        serde_json::from_str(text).map_err(|error| SettingsError::Parse(error.to_string()))
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("settings are always valid JSON")
+    }
+
+    /// Checks every setting, reporting the first one that is out of range.
+    pub fn validate(&self) -> Result<(), SettingsError> {
+        check("width", self.width as f64, 300.0, 4000.0)?;
+        check("height", self.height as f64, 200.0, 2000.0)?;
+        check("players", self.players as f64, 2.0, 8.0)?;
+        check("gravity", self.gravity, 0.0001, 0.01)?;
+        check("max_wind", self.max_wind, 0.0, self.gravity)?;
+        check("tank_health", self.tank_health, 1.0, 10_000.0)?;
+        check("angle_of_repose", self.angle_of_repose, 45.0, 90.0)?;
+        self.terrain.validate(self.height as f64)?;
+        check("rounds", self.rounds as f64, 1.0, 99.0)?;
+        for (name, &count) in self.loadout.iter() {
+            match Weapon::from_name(name) {
+                Some(Weapon::Missile) => return Err(SettingsError::UnlimitedWeapon),
+                Some(_) => check(&format!("loadout.{:?}", name), count as f64, 0.0, 99.0)?,
+                None => return Err(SettingsError::UnknownWeapon(name.clone())),
+            }
+        }
+        Ok(())
+    }
+
+    /// The config for a match played with these settings.
+    pub fn config(&self) -> Result<Config, SettingsError> {
+        self.validate()?;
+        let loadout: Vec<(Weapon, u32)> = self
+            .loadout
+            .iter()
+            .filter_map(|(name, &count)| Weapon::from_name(name).map(|weapon| (weapon, count)))
+            .collect();
+        Ok(Config::from_settings(self, Inventory::new(&loadout)))
+    }
+}
+
+impl Default for MatchSettings {
+    fn default() -> MatchSettings {
+        let standard = Inventory::standard();
+        let loadout = Weapon::ALL
+            .iter()
+            .filter_map(|&weapon| {
+                standard
+                    .count(weapon)
+                    .map(|count| (weapon.name().to_string(), count))
+            })
+            .collect();
+        MatchSettings {
+            width: 900,
+            height: 500,
+            players: 2,
+            gravity: GRAVITY,
+            max_wind: 0.0001,
+            tank_health: 100.0,
+            // steeper than any slope the random walk makes
+            angle_of_repose: 75.0,
+            terrain: TerrainSettings::default(),
+            loadout,
+            rounds: 1,
+            aim_preview: false,
+        }
+    }
+}
+
+pub(super) fn check(setting: &str, value: f64, min: f64, max: f64) -> Result<(), SettingsError> {
+    // written so that NaN is out of range too
+    if value >= min && value <= max {
+        Ok(())
+    } else {
+        Err(SettingsError::OutOfRange {
+            setting: setting.to_string(),
+            value,
+            min,
+            max,
+        })
+    }
+}
@@ -0,0 +1,232 @@
+//! Saving a match to carry on with later.
+//!
+//! A `Snapshot` holds everything about a match in progress: the round being
+//! played as it stands (the field, every tank's health and ammo, whose turn
+//! it is, the wind and how far its random events have got), the rounds
+//! before it as a `Replay`, the score and who is playing. Matches are saved
+//! between turns, so nothing is ever caught in mid-air.
+//!
+//! Bots are saved only by name. A resumed match gives each of their seats a
+//! new bot, which draws its random stream from the start again and has
+//! learned nothing yet, so from there on it may play differently than the
+//! match left running would have. Every resume of the same snapshot still
+//! plays the same.
+
+use super::{
+    round_seed, Game, Inventory, Map, MapError, Move, PlayerId, Replay, SettingsError, Weapon,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::fmt;
+
+/// The version of the snapshot format this build reads and writes.
+pub const SNAPSHOT_VERSION: u32 = 1;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Snapshot {
+    pub version: u32,
+    /// Who plays each seat, by `PlayerId`: `"human"` or the name of a bot in
+    /// the `BotRegistry`, or `"remote"` in a relay's snapshot of a networked
+    /// match.
+    pub players: Vec<String>,
+    /// The match's seed, settings and map, with the moves of every round
+    /// before the one in play.
+    pub replay: Replay,
+    /// Rounds won by each player, by `PlayerId`, counting the one in play if
+    /// it is over.
+    pub wins: Vec<u32>,
+    pub round: SavedRound,
+}
+
+/// The round in play, as a `Snapshot` stores it.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SavedRound {
+    /// The ground, colors and mines as they are now, with each tank's spawn
+    /// where it stands.
+    pub field: Map,
+    /// By `PlayerId`.
+    pub tanks: Vec<SavedTank>,
+    pub active_player: u8,
+    pub wind: f64,
+    /// How many 32-bit words of the round's random events have been used.
+    pub events_drawn: u64,
+    /// Every move made in the round so far, oldest first.
+    pub moves: Vec<Move>,
+    /// Where each shot in `moves` came down as `[x, y]`, or `None` if it
+    /// left the field.
+    pub impacts: Vec<Option<[f64; 2]>>,
+    /// Whether the round is over, and if so who won it; `None` is a draw.
+    pub over: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub winner: Option<u8>,
+}
+
+/// A tank as a `SavedRound` stores it. It stands at its spawn on the field.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SavedTank {
+    /// The bottom of the tank, which may be in a cave below the surface.
+    pub y: f64,
+    pub health: f64,
+    /// Shots left of every limited weapon, by `Weapon::name`.
+    pub ammo: BTreeMap<String, u32>,
+}
+
+/// Why a match can't be saved, or a `Snapshot` can't be loaded.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SnapshotError {
+    /// The JSON couldn't be read.
+    Parse(String),
+    /// The snapshot was written in a version of the format this build can't
+    /// read.
+    UnsupportedVersion(u32),
+    Settings(SettingsError),
+    Map(MapError),
+    /// A shot is still being played out; matches are saved between turns.
+    MidTurn,
+    /// The snapshot is well-formed but doesn't describe a match that can be
+    /// played.
+    Invalid(String),
+}
+
+impl fmt::Display for SnapshotError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SnapshotError::Parse(message) => write!(f, "invalid snapshot: {}", message),
+            SnapshotError::UnsupportedVersion(version) => write!(
+                f,
+                "snapshot format version {} isn't supported; expected {}",
+                version, SNAPSHOT_VERSION
+            ),
+            SnapshotError::Settings(error) => write!(f, "invalid snapshot: {}", error),
+            SnapshotError::Map(error) => write!(f, "invalid snapshot: {}", error),
+            SnapshotError::MidTurn => write!(f, "matches can only be saved between turns"),
+            SnapshotError::Invalid(message) => write!(f, "invalid snapshot: {}", message),
+        }
+    }
+}
+
+impl Error for SnapshotError {}
+
+impl From<SettingsError> for SnapshotError {
+    fn from(error: SettingsError) -> SnapshotError {
+        SnapshotError::Settings(error)
+    }
+}
+
+impl From<MapError> for SnapshotError {
+    fn from(error: MapError) -> SnapshotError {
+        SnapshotError::Map(error)
+    }
+}
+
+// read first, so that a snapshot from a newer format is reported as such
+// rather than as whatever field it no longer parses at
+#[derive(Deserialize)]
+struct Header {
+    version: u32,
+}
+
+impl Snapshot {
+    /// Saves a match between `players` that has got as far as `game`, the
+    /// round after the ones in `replay`, with `wins` so far. The active
+    /// player must be aiming, or the round over.
+    pub fn new(
+        players: Vec<String>,
+        replay: Replay,
+        wins: Vec<u32>,
+        game: &Game,
+    ) -> Result<Snapshot, SnapshotError> {
+        let round = game.save().ok_or(SnapshotError::MidTurn)?;
+        Ok(Snapshot {
+            version: SNAPSHOT_VERSION,
+            players,
+            replay,
+            wins,
+            round,
+        })
+    }
+
+    pub fn from_json(text: &str) -> Result<Snapshot, SnapshotError> {
+        let parse = |error: serde_json::Error| SnapshotError::Parse(error.to_string());
+        let header: Header = serde_json::from_str(text).map_err(parse)?;
+        if header.version != SNAPSHOT_VERSION {
+            return Err(SnapshotError::UnsupportedVersion(header.version));
+        }
+        let snapshot: Snapshot = serde_json::from_str(text).map_err(parse)?;
+        snapshot.validate()?;
+        Ok(snapshot)
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string(self).expect("snapshots are always valid JSON")
+    }
+
+    /// Checks that the snapshot describes a match that can be carried on.
+    pub fn validate(&self) -> Result<(), SnapshotError> {
+        let settings = &self.replay.settings;
+        settings.validate()?;
+        if let Some(map) = &self.replay.map {
+            map.validate()?;
+        }
+        self.round.field.validate()?;
+        let players = settings.players as usize;
+        if self.players.len() != players || self.wins.len() != players {
+            return Err(SnapshotError::Invalid(format!(
+                "{} seats and {} scores for {} players",
+                self.players.len(),
+                self.wins.len(),
+                players
+            )));
+        }
+        if self.replay.rounds.len() >= settings.rounds as usize {
+            return Err(SnapshotError::Invalid(format!(
+                "round {} of a {} round match",
+                self.replay.rounds.len() + 1,
+                settings.rounds
+            )));
+        }
+        Ok(())
+    }
+
+    /// The round in play, just as it was saved.
+    pub fn game(&self) -> Result<Game, SnapshotError> {
+        let config = self.replay.settings.config()?;
+        let seed = round_seed(self.replay.seed, self.replay.rounds.len() as u32);
+        Game::restore(config, seed, &self.round)
+    }
+}
+
+impl SavedTank {
+    pub(crate) fn inventory(&self) -> Result<Inventory, SnapshotError> {
+        let mut loadout = Vec::new();
+        for (name, &count) in self.ammo.iter() {
+            match Weapon::from_name(name) {
+                Some(weapon) if weapon != Weapon::Missile => loadout.push((weapon, count)),
+                _ => {
+                    return Err(SnapshotError::Invalid(format!(
+                        "{:?} isn't a limited weapon",
+                        name
+                    )))
+                }
+            }
+        }
+        Ok(Inventory::new(&loadout))
+    }
+}
+
+impl SavedRound {
+    pub(crate) fn player(&self, index: u8) -> Result<PlayerId, SnapshotError> {
+        if (index as usize) < self.tanks.len() {
+            Ok(PlayerId(index))
+        } else {
+            Err(SnapshotError::Invalid(format!(
+                "there is no player {}",
+                index as usize + 1
+            )))
+        }
+    }
+}
@@ -0,0 +1,81 @@
+use super::{Action, Damage, Impact, MatchResult, PlayerId, Weapon};
+use std::error::Error;
+use std::fmt;
+
+/// Where a match is in its turn cycle.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Phase {
+    /// Waiting for the active player to fire.
+    Aiming,
+    InFlight,
+    /// The shot has landed or left the field; loose earth is settling and
+    /// damage is being tallied.
+    Resolving,
+    /// Handing over to the next player.
+    TurnTransition,
+    Over,
+}
+
+/// Something that happened in a match, emitted on each change of `Phase`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Event {
+    TurnStarted {
+        player: PlayerId,
+        /// The wind for the whole turn; see `Game::wind`.
+        wind: f64,
+    },
+    ShotFired {
+        player: PlayerId,
+        action: Action,
+    },
+    /// One warhead went off; a MIRV has several of these per shot.
+    Impact(Impact),
+    /// Earth fell or slid in these columns during one tick after the shot.
+    Landslide {
+        min_index: usize,
+        max_index: usize,
+    },
+    /// The ground has come to rest after a landslide. `damage` is what the
+    /// tanks that rode it down took from the fall.
+    Settled {
+        damage: Vec<Damage>,
+    },
+    /// The projectile left the field without hitting anything.
+    ShotLost {
+        player: PlayerId,
+    },
+    TurnEnded {
+        player: PlayerId,
+    },
+    MatchOver(MatchResult),
+}
+
+/// Why an action was refused.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum GameError {
+    NotYourTurn {
+        active: PlayerId,
+    },
+    /// The player has already fired this turn.
+    AlreadyFired,
+    /// The player has none of that weapon left.
+    OutOfAmmo {
+        weapon: Weapon,
+    },
+    MatchOver,
+}
+
+impl fmt::Display for GameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            GameError::NotYourTurn { active } => {
+                write!(f, "it is player {}'s turn", active.index() + 1)
+            }
+            GameError::AlreadyFired => write!(f, "already fired this turn"),
+            GameError::OutOfAmmo { weapon } => write!(f, "no {} left", weapon.name()),
+            GameError::MatchOver => write!(f, "the match is over"),
+        }
+    }
+}
+
+impl Error for GameError {}
@@ -0,0 +1,155 @@
+use super::{Action, Config, Inventory, PlayerId, Point, Projectile};
+
+/// Each player's tank color, by `PlayerId`.
+pub const PLAYER_COLORS: [&str; 8] = [
+    "#FF0000", "#3399FF", "#FFDD00", "#FF66CC", "#00FFFF", "#FF8800", "#AA66FF", "#FFFFFF",
+];
+
+#[derive(Clone, Debug)]
+pub struct Tank {
+    player: PlayerId,
+    width: f64,
+    height: f64,
+    location: Point,
+    turret_length: f64,
+    turret_width: f64,
+    // 1.0 when the turret points right at 180°, -1.0 when it is mirrored
+    facing: f64,
+    health: f64,
+    max_health: f64,
+    inventory: Inventory,
+}
+
+impl Tank {
+    pub fn new(player: PlayerId, point: Point, facing: f64, config: &Config) -> Tank {
+        let width = config.tank_width();
+        let height = config.tank_height();
+        let location = point;
+        let turret_length = config.turret_length();
+        let turret_width = 1.8;
+        let max_health = config.tank_health();
+        Tank {
+            player,
+            width,
+            height,
+            location,
+            turret_length,
+            turret_width,
+            facing,
+            health: max_health,
+            max_health,
+            inventory: config.starting_inventory().clone(),
+        }
+    }
+
+    pub fn player(&self) -> PlayerId {
+        self.player
+    }
+
+    pub fn color_hex(&self) -> &'static str {
+        PLAYER_COLORS[self.player.index() % PLAYER_COLORS.len()]
+    }
+
+    pub fn width(&self) -> f64 {
+        self.width
+    }
+
+    pub fn height(&self) -> f64 {
+        self.height
+    }
+
+    /// Bottom-left corner of the tank body.
+    pub fn location(&self) -> Point {
+        self.location
+    }
+
+    pub fn turret_width(&self) -> f64 {
+        self.turret_width
+    }
+
+    pub fn facing(&self) -> f64 {
+        self.facing
+    }
+
+    pub fn health(&self) -> f64 {
+        self.health
+    }
+
+    pub fn max_health(&self) -> f64 {
+        self.max_health
+    }
+
+    /// The weapons the tank has left to fire.
+    pub fn inventory(&self) -> &Inventory {
+        &self.inventory
+    }
+
+    pub fn is_destroyed(&self) -> bool {
+        self.health <= 0.0
+    }
+
+    /// Middle of the tank body, which blast damage is measured from.
+    pub fn center(&self) -> Point {
+        Point::new(
+            self.location.x + self.width / 2.0,
+            self.location.y - self.height / 2.0,
+        )
+    }
+
+    /// Whether `point` is inside the tank body.
+    pub fn contains(&self, point: Point) -> bool {
+        point.x >= self.location.x
+            && point.x < self.location.x + self.width
+            && point.y >= self.location.y - self.height
+            && point.y <= self.location.y
+    }
+
+    /// Point the turret pivots around, at the top-middle of the body.
+    pub fn turret_base(&self) -> Point {
+        Point::new(
+            self.location.x + self.width / 2.0,
+            self.location.y - self.height,
+        )
+    }
+
+    /// Tip of the turret when it is aimed at `angle` radians.
+    pub fn turret_location(&self, angle: f64) -> Point {
+        let base = self.turret_base();
+        let x = base.x - self.turret_length * self.facing * libm::cos(angle);
+        let y = base.y - self.turret_length * libm::sin(angle);
+        Point::new(x, y)
+    }
+
+    /// The projectile this tank sends out for `action`.
+    pub fn launch(&self, action: &Action, config: &Config, wind: f64) -> Projectile {
+        let angle = action.angle.to_radians();
+        let turret_location = self.turret_location(angle);
+        let origin = Point::new(
+            turret_location.x,
+            turret_location.y + config.projectile_size(),
+        );
+        let speed = action.power / config.power_normalizer() * action.weapon.speed_factor();
+        Projectile::launch(origin, speed, angle, self.facing, config.gravity(), wind)
+    }
+
+    pub(crate) fn move_to(&mut self, location: Point, facing: f64) {
+        self.location = location;
+        self.facing = facing;
+    }
+
+    pub(crate) fn set_ground(&mut self, y: f64) {
+        self.location.y = y;
+    }
+
+    pub(crate) fn inventory_mut(&mut self) -> &mut Inventory {
+        &mut self.inventory
+    }
+
+    pub(crate) fn set_health(&mut self, health: f64) {
+        self.health = health;
+    }
+
+    pub(crate) fn take_damage(&mut self, amount: f64) {
+        self.health = (self.health - amount).max(0.0);
+    }
+}
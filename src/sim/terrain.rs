@@ -0,0 +1,360 @@
+use super::{Config, Hazard, Map, MatchRng, PlayerId, Point, Tank, TerrainMask};
+use rand::Rng;
+
+pub const TERRAIN_COLORS: [&str; 11] = [
+    "#27FF00", "#43AB08", "#9D5109", "#EABC00", "#00960E", "#CCCCCC", "#FFFFFF", "#F7CAA6",
+    "#BAEFFF", "#8E4103", "#A50000",
+];
+
+pub const SKY_COLORS: [&str; 1] = ["#000000"];
+
+#[derive(Clone, Debug)]
+pub struct Terrain {
+    mask: TerrainMask,
+    // top of the ground in each column, kept in step with `mask`
+    heights: Vec<f64>,
+    // whether earth may have been cut loose since it last came to rest
+    loose: bool,
+    color_hex: String,
+    sky_color_hex: String,
+    // indexed by `PlayerId`
+    tanks: Vec<Tank>,
+    // mines that haven't gone off yet
+    hazards: Vec<Hazard>,
+    // px down from the top, if the low ground is flooded
+    sea_level: Option<f64>,
+}
+
+impl Terrain {
+    /// Terrain from the match's generator with a tank for every player,
+    /// placed from `spawn_rng`.
+    pub fn new(config: &Config, rng: &mut MatchRng, spawn_rng: &mut MatchRng) -> Terrain {
+        let generator = config.terrain().generator();
+        let mut heights = generator.generate(config.width() as usize, config.height(), rng);
+        // anything steeper would start sliding at the first shot; whole px
+        // because that is what the surface is rounded to
+        let repose = libm::tan(config.angle_of_repose().to_radians()).floor();
+        wear_down(&mut heights, repose.max(1.0));
+        let mask = TerrainMask::from_heights(&heights, config.height() as usize);
+        let heights: Vec<f64> = (0..mask.width()).map(|x| mask.surface(x)).collect();
+        let sea_level = generator.sea_level(config.height());
+        let mut spawns = spawn_positions(config, spawn_rng);
+        if let Some(sea_level) = sea_level {
+            onto_land(&mut spawns, &heights, config.tank_width(), sea_level);
+        }
+        let tanks = place_tanks(config, &spawns, &heights);
+
+        let color = pick(&TERRAIN_COLORS, rng);
+        let sky_color = pick(&SKY_COLORS, rng);
+
+        Terrain {
+            mask,
+            heights,
+            loose: false,
+            color_hex: color.to_string(),
+            sky_color_hex: sky_color.to_string(),
+            tanks,
+            hazards: Vec::new(),
+            sea_level,
+        }
+    }
+
+    /// The field `map` describes, with a tank for each of the config's
+    /// players. The map must be the size of the config's field and have
+    /// enough spawns; see `Map::fits`.
+    pub(crate) fn from_map(config: &Config, map: &Map) -> Terrain {
+        let mask = map.mask();
+        let heights: Vec<f64> = (0..mask.width()).map(|x| mask.surface(x)).collect();
+        let spawns = &map.spawns[..config.players() as usize];
+        Terrain {
+            tanks: place_tanks(config, spawns, &heights),
+            mask,
+            heights,
+            loose: false,
+            color_hex: map.color_hex.clone(),
+            sky_color_hex: map.sky_color_hex.clone(),
+            hazards: map.hazards.clone(),
+            sea_level: map.sea_level,
+        }
+    }
+
+    /// Which pixels are ground, including any caves and overhangs.
+    pub fn mask(&self) -> &TerrainMask {
+        &self.mask
+    }
+
+    /// The top of the ground in each column. There may be empty space further
+    /// down; see `mask`.
+    pub fn heights(&self) -> &[f64] {
+        &self.heights
+    }
+
+    pub fn width(&self) -> usize {
+        self.heights.len()
+    }
+
+    pub fn color_hex(&self) -> String {
+        self.color_hex.clone()
+    }
+
+    pub fn sky_color_hex(&self) -> String {
+        self.sky_color_hex.clone()
+    }
+
+    /// The surface of the sea, in px down from the top, or `None` if there
+    /// is no sea. Everything below it is under water.
+    pub fn sea_level(&self) -> Option<f64> {
+        self.sea_level
+    }
+
+    /// Whether `point` is under the sea.
+    pub fn is_under_water(&self, point: Point) -> bool {
+        self.sea_level.is_some_and(|sea_level| point.y > sea_level)
+    }
+
+    pub fn tank(&self, player: PlayerId) -> &Tank {
+        &self.tanks[player.index()]
+    }
+
+    /// Every player's tank, including any destroyed, by `PlayerId`.
+    pub fn tanks(&self) -> &[Tank] {
+        &self.tanks
+    }
+
+    pub(crate) fn tank_mut(&mut self, player: PlayerId) -> &mut Tank {
+        &mut self.tanks[player.index()]
+    }
+
+    /// The mines on the field that haven't gone off yet.
+    pub fn hazards(&self) -> &[Hazard] {
+        &self.hazards
+    }
+
+    /// Takes the mines within `radius` of `center` off the field, for them to
+    /// go off.
+    pub(crate) fn trip_mines(&mut self, center: Point, radius: f64) -> Vec<Hazard> {
+        let (tripped, left) = self.hazards.iter().partition(|hazard| {
+            let point = hazard.position();
+            (point.x - center.x).hypot(point.y - center.y) <= radius
+        });
+        self.hazards = left;
+        tripped
+    }
+
+    pub(crate) fn add_hazard(&mut self, hazard: Hazard) {
+        self.hazards.push(hazard);
+    }
+
+    pub(crate) fn remove_hazard(&mut self, index: usize) -> Hazard {
+        self.hazards.remove(index)
+    }
+
+    pub(crate) fn set_colors(&mut self, color: &str, sky_color: &str) {
+        self.color_hex = color.to_string();
+        self.sky_color_hex = sky_color.to_string();
+    }
+
+    /// Puts `player`'s tank with its left edge at `x`, on top of the ground
+    /// and facing the middle of the field.
+    pub(crate) fn move_tank(&mut self, player: PlayerId, x: f64) {
+        let width = self.width() as f64;
+        let tank = &mut self.tanks[player.index()];
+        let location = Point::new(x, self.heights[x as usize]);
+        tank.move_to(location, facing(x, tank.width(), width));
+    }
+
+    /// Whether `point` is inside the ground or a tank that is still alive.
+    pub fn collides(&self, point: Point) -> bool {
+        self.hits_tank(point) || self.mask.is_solid(point)
+    }
+
+    /// Whether `point` is inside a tank that is still alive.
+    pub fn hits_tank(&self, point: Point) -> bool {
+        self.tanks
+            .iter()
+            .any(|tank| !tank.is_destroyed() && tank.contains(point))
+    }
+
+    /// Blows a round crater of `radius` centered on `center` and returns
+    /// the range of columns that changed.
+    pub fn blast(&mut self, center: Point, radius: f64) -> (usize, usize) {
+        let columns = self.mask.fill_circle(center, radius, false);
+        self.loose = true;
+        self.refresh(columns)
+    }
+
+    /// Packs a disc of earth of `radius` centered on `point` and returns the
+    /// range of columns that changed.
+    pub fn add_dirt(&mut self, point: Point, radius: f64) -> (usize, usize) {
+        let columns = self.mask.fill_circle(point, radius, true);
+        self.loose = true;
+        self.refresh(columns)
+    }
+
+    /// Bores a tunnel `width` px wide along the straight line from `from` to
+    /// `to` and returns the range of columns that changed.
+    pub fn dig(&mut self, from: Point, to: Point, width: f64) -> (usize, usize) {
+        let columns = self.mask.fill_line(from, to, width / 2.0, false);
+        self.loose = true;
+        self.refresh(columns)
+    }
+
+    /// Lets loose earth fall one pixel and, within `columns`, slopes steeper
+    /// than `max_slope` px per column shed one pixel downhill, carrying any
+    /// tanks on them along. Returns the range of columns that changed, or
+    /// `None` once the ground is at rest.
+    pub fn settle(&mut self, columns: (usize, usize), max_slope: f64) -> Option<(usize, usize)> {
+        // only blasts and falls can cut earth loose; what slides stays on
+        // top of the ground
+        let dropped = if self.loose {
+            self.mask.drop_loose()
+        } else {
+            None
+        };
+        self.loose = dropped.is_some();
+        let slid = self.mask.slide(columns, max_slope);
+        let changed = match (dropped, slid) {
+            (Some(a), Some(b)) => Some((a.0.min(b.0), a.1.max(b.1))),
+            (a, b) => a.or(b),
+        };
+        changed.map(|columns| self.refresh(columns))
+    }
+
+    /// The columns that fire started at column `x` runs across: it spreads up
+    /// to `reach` columns each way but can't climb more than a pixel per
+    /// column.
+    pub fn burn(&self, x: f64, reach: usize) -> (usize, usize) {
+        let start = (x.max(0.0) as usize).min(self.heights.len() - 1);
+        let can_spread = |from: usize, to: usize| self.heights[to] >= self.heights[from] - 1.0;
+
+        let mut min_index = start;
+        while start - min_index < reach && min_index > 0 && can_spread(min_index, min_index - 1) {
+            min_index -= 1;
+        }
+        let mut max_index = start;
+        while max_index - start < reach
+            && max_index + 1 < self.heights.len()
+            && can_spread(max_index, max_index + 1)
+        {
+            max_index += 1;
+        }
+        (min_index, max_index + 1)
+    }
+
+    // bring `heights` and the tanks up to date after the mask changed in
+    // `columns`, and hand the range back
+    fn refresh(&mut self, (min_index, max_index): (usize, usize)) -> (usize, usize) {
+        for x in min_index..max_index {
+            self.heights[x] = self.mask.surface(x);
+        }
+        // tanks drop onto the highest ground left under any part of them, or
+        // climb out of earth piled on top of them
+        let mask = &self.mask;
+        for tank in self.tanks.iter_mut() {
+            let location = tank.location();
+            let left = (location.x.max(0.0) as usize).min(mask.width() - 1);
+            let right = ((location.x + tank.width()).ceil() as usize).clamp(left + 1, mask.width());
+            let y = (left..right)
+                .map(|x| mask.ground_below(x, location.y))
+                .fold(f64::INFINITY, f64::min);
+            tank.set_ground(y);
+        }
+        (min_index, max_index)
+    }
+}
+
+// a tank with its left edge at each of `spawns`, by `PlayerId`, standing on
+// the ground in `heights`
+fn place_tanks(config: &Config, spawns: &[f64], heights: &[f64]) -> Vec<Tank> {
+    spawns
+        .iter()
+        .enumerate()
+        .map(|(i, &x)| {
+            let location = Point::new(x, heights[x as usize]);
+            let facing = facing(x, config.tank_width(), config.width());
+            Tank::new(PlayerId(i as u8), location, facing, config)
+        })
+        .collect()
+}
+
+// which way a tank `tank_width` wide with its left edge at `x` turns, to face
+// the middle of the field
+fn facing(x: f64, tank_width: f64, field_width: f64) -> f64 {
+    if x + tank_width / 2.0 < field_width / 2.0 {
+        1.0
+    } else {
+        -1.0
+    }
+}
+
+// lowers peaks until no slope is steeper than `max_slope` px per column
+fn wear_down(heights: &mut [f64], max_slope: f64) {
+    for x in 1..heights.len() {
+        heights[x] = heights[x].max(heights[x - 1] - max_slope);
+    }
+    for x in (1..heights.len()).rev() {
+        heights[x - 1] = heights[x - 1].max(heights[x] - max_slope);
+    }
+}
+
+// left edges of each player's tank, by `PlayerId`: evenly spread across the
+// field, each nudged a little at random, then dealt out in random order
+fn spawn_positions(config: &Config, rng: &mut MatchRng) -> Vec<f64> {
+    let players = config.players() as usize;
+    let tank_width = config.tank_width();
+    let margin = (config.width() / 9.0).round();
+    let spacing = (config.width() - 2.0 * margin - tank_width) / (players - 1) as f64;
+    // small enough that neighbours never get within half the spacing
+    let nudge = (spacing / 8.0).min(50.0);
+
+    let mut positions: Vec<f64> = (0..players)
+        .map(|i| {
+            let x = margin + spacing * i as f64 + rng.gen_range(-nudge, nudge);
+            x.round()
+        })
+        .collect();
+    // Fisher-Yates, drawing `u32`s for the same reason as `pick`
+    for i in (1..players).rev() {
+        let j = rng.gen_range(0, i as u32 + 1) as usize;
+        positions.swap(i, j);
+    }
+    positions
+}
+
+// moves each of `spawns` to the nearest place where a tank `tank_width` wide
+// stands wholly on ground above `sea_level`, clear of the tanks before it;
+// any with nowhere to go stay put
+fn onto_land(spawns: &mut [f64], heights: &[f64], tank_width: f64, sea_level: f64) {
+    let columns = tank_width.ceil() as usize;
+    let last = match heights.len().checked_sub(columns) {
+        Some(last) => last,
+        None => return,
+    };
+    for i in 0..spawns.len() {
+        let (placed, rest) = spawns.split_at_mut(i);
+        let fits = |x: usize| {
+            x <= last
+                && heights[x..x + columns].iter().all(|&y| y < sea_level)
+                && placed
+                    .iter()
+                    .all(|&other| (other - x as f64).abs() >= tank_width)
+        };
+        let start = rest[0] as usize;
+        let nearest = (0..heights.len())
+            .flat_map(|distance| {
+                std::iter::once(Some(start + distance))
+                    .chain(std::iter::once(start.checked_sub(distance)))
+            })
+            .flatten()
+            .find(|&x| fits(x));
+        if let Some(x) = nearest {
+            rest[0] = x as f64;
+        }
+    }
+}
+
+// `SliceRandom::choose` samples a `usize`, which is 32 bits on wasm and 64
+// natively, so it would pick differently in the browser than in tests.
+fn pick<'a>(choices: &[&'a str], rng: &mut MatchRng) -> &'a str {
+    choices[rng.gen_range(0, choices.len() as u32) as usize]
+}
@@ -0,0 +1,461 @@
+//! Ranking bots by playing them against each other.
+//!
+//! Whenever two entrants meet they play a series: one match on each of the
+//! tournament's seeds from each side, so that neither gains from the seat
+//! or the ground it drew. Every match counts a point for a win and half for
+//! a draw, and moves both entrants' Elo ratings.
+
+use super::{match_rng, play_match, BotRegistry, Map, MatchError, MatchSettings, Stream};
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+use std::error::Error;
+use std::fmt;
+
+/// Every entrant's rating before their first match.
+pub const INITIAL_RATING: f64 = 1500.0;
+/// Most an Elo rating can move in one match.
+pub const K_FACTOR: f64 = 32.0;
+
+/// How entrants are drawn against each other.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Format {
+    /// Everyone meets everyone else once.
+    RoundRobin,
+    /// Each round, entrants meet someone on about the same points they
+    /// haven't met yet. With an odd number of entrants, the lowest placed
+    /// one who hasn't had a bye sits the round out, for half the points of a
+    /// series.
+    Swiss { rounds: u32 },
+    /// A seeded knockout bracket; the winner of each series goes through.
+    /// Where a series is tied, the higher rated entrant goes through, then
+    /// the higher seed.
+    Elimination,
+}
+
+impl Format {
+    pub fn name(self) -> &'static str {
+        match self {
+            Format::RoundRobin => "round-robin",
+            Format::Swiss { .. } => "swiss",
+            Format::Elimination => "elimination",
+        }
+    }
+
+    /// The rounds a Swiss tournament needs to find a clear winner among
+    /// `entrants`, as a knockout would.
+    pub fn swiss(entrants: usize) -> Format {
+        let mut rounds = 0;
+        while 1usize << rounds < entrants {
+            rounds += 1;
+        }
+        Format::Swiss { rounds }
+    }
+}
+
+/// A tournament between bots, ready to be played.
+#[derive(Clone, Debug)]
+pub struct Tournament {
+    pub format: Format,
+    /// The bots taking part, by name in the `BotRegistry`, best seeded
+    /// first. The same bot can be entered more than once.
+    pub entrants: Vec<String>,
+    /// Each series plays a match on every one of these seeds from each side.
+    pub seeds: Vec<u64>,
+    /// For two players.
+    pub settings: MatchSettings,
+    pub map: Option<Map>,
+}
+
+/// How a tournament finished, and every match that decided it.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Standings {
+    pub format: Format,
+    /// Best placed first.
+    pub table: Vec<Standing>,
+    /// In the order they were played.
+    pub matches: Vec<MatchRecord>,
+}
+
+/// An entrant's place in the `Standings`.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Standing {
+    pub name: String,
+    /// Where the entrant was seeded, from 1.
+    pub seed: usize,
+    pub rating: f64,
+    pub played: u32,
+    pub wins: u32,
+    pub draws: u32,
+    pub losses: u32,
+    /// A point for every win and half for every draw, with any byes.
+    pub points: f64,
+    /// In an elimination bracket, the round the entrant went out in,
+    /// counting from 1; `None` for the champion.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub eliminated_in: Option<u32>,
+}
+
+/// One match of a tournament.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MatchRecord {
+    /// The tournament round it was played in, counting from 1.
+    pub round: u32,
+    pub seed: u64,
+    /// The entrants, by seed from 1, in turn order.
+    pub seats: [usize; 2],
+    /// Rounds of the match won from each seat.
+    pub score: [u32; 2],
+    /// The seat that won the match, or `None` for a draw.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub winner: Option<u8>,
+}
+
+/// Why a tournament can't be played.
+#[derive(Clone, Debug, PartialEq)]
+pub enum TournamentError {
+    /// There must be at least two entrants.
+    Entrants(usize),
+    UnknownBot(String),
+    NoSeeds,
+    /// Tournament matches are between two players.
+    Players(u8),
+    Match(MatchError),
+}
+
+impl fmt::Display for TournamentError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TournamentError::Entrants(count) => {
+                write!(f, "a tournament needs at least two entrants, not {}", count)
+            }
+            TournamentError::UnknownBot(name) => write!(f, "unknown bot: {}", name),
+            TournamentError::NoSeeds => write!(f, "a tournament needs at least one seed"),
+            TournamentError::Players(players) => write!(
+                f,
+                "tournament matches are between 2 players, not {}",
+                players
+            ),
+            TournamentError::Match(error) => error.fmt(f),
+        }
+    }
+}
+
+impl Error for TournamentError {}
+
+impl From<MatchError> for TournamentError {
+    fn from(error: MatchError) -> TournamentError {
+        TournamentError::Match(error)
+    }
+}
+
+/// The chance, by Elo, that a player rated `rating` beats one rated
+/// `opponent`.
+pub fn expected_score(rating: f64, opponent: f64) -> f64 {
+    1.0 / (1.0 + libm::pow(10.0, (opponent - rating) / 400.0))
+}
+
+// the tournament as it is played
+struct Run<'a> {
+    tournament: &'a Tournament,
+    registry: &'a BotRegistry,
+    table: Vec<Standing>,
+    matches: Vec<MatchRecord>,
+}
+
+impl Tournament {
+    /// Plays the whole tournament with bots from `registry`.
+    pub fn run(&self, registry: &BotRegistry) -> Result<Standings, TournamentError> {
+        if self.entrants.len() < 2 {
+            return Err(TournamentError::Entrants(self.entrants.len()));
+        }
+        let names = registry.names();
+        if let Some(name) = self
+            .entrants
+            .iter()
+            .find(|name| !names.contains(&name.as_str()))
+        {
+            return Err(TournamentError::UnknownBot(name.clone()));
+        }
+        if self.seeds.is_empty() {
+            return Err(TournamentError::NoSeeds);
+        }
+        if self.settings.players != 2 {
+            return Err(TournamentError::Players(self.settings.players));
+        }
+        self.settings.validate().map_err(MatchError::from)?;
+
+        let mut run = Run {
+            tournament: self,
+            registry,
+            table: self
+                .entrants
+                .iter()
+                .enumerate()
+                .map(|(i, name)| Standing {
+                    name: name.clone(),
+                    seed: i + 1,
+                    rating: INITIAL_RATING,
+                    played: 0,
+                    wins: 0,
+                    draws: 0,
+                    losses: 0,
+                    points: 0.0,
+                    eliminated_in: None,
+                })
+                .collect(),
+            matches: Vec::new(),
+        };
+        match self.format {
+            Format::RoundRobin => run.round_robin()?,
+            Format::Swiss { rounds } => run.swiss(rounds)?,
+            Format::Elimination => run.elimination()?,
+        }
+
+        let mut table = run.table;
+        let format = self.format;
+        table.sort_by(|a, b| match format {
+            Format::Elimination => {
+                let out = |standing: &Standing| standing.eliminated_in.unwrap_or(u32::MAX);
+                out(b).cmp(&out(a)).then_with(|| by_points(a, b))
+            }
+            _ => by_points(a, b),
+        });
+        Ok(Standings {
+            format,
+            table,
+            matches: run.matches,
+        })
+    }
+}
+
+// more points first, then higher rating, then the higher seed
+fn by_points(a: &Standing, b: &Standing) -> Ordering {
+    b.points
+        .partial_cmp(&a.points)
+        .unwrap_or(Ordering::Equal)
+        .then_with(|| b.rating.partial_cmp(&a.rating).unwrap_or(Ordering::Equal))
+        .then_with(|| a.seed.cmp(&b.seed))
+}
+
+impl<'a> Run<'a> {
+    fn round_robin(&mut self) -> Result<(), TournamentError> {
+        // the circle method: the first entrant stays put and everyone else
+        // moves round one place a round, with an empty seat for a bye
+        let mut circle: Vec<Option<usize>> = (0..self.table.len()).map(Some).collect();
+        if circle.len() % 2 == 1 {
+            circle.push(None);
+        }
+        let seats = circle.len();
+        for round in 1..seats as u32 {
+            for i in 0..seats / 2 {
+                if let (Some(a), Some(b)) = (circle[i], circle[seats - 1 - i]) {
+                    self.series(round, a, b)?;
+                }
+            }
+            circle[1..].rotate_right(1);
+        }
+        Ok(())
+    }
+
+    fn swiss(&mut self, rounds: u32) -> Result<(), TournamentError> {
+        let entrants = self.table.len();
+        let mut met = vec![vec![false; entrants]; entrants];
+        let mut had_bye = vec![false; entrants];
+        for round in 1..=rounds {
+            let mut order: Vec<usize> = (0..entrants).collect();
+            order.sort_by(|&a, &b| by_points(&self.table[a], &self.table[b]));
+            if entrants % 2 == 1 {
+                let bye = order
+                    .iter()
+                    .rposition(|&i| !had_bye[i])
+                    .unwrap_or(entrants - 1);
+                let i = order.remove(bye);
+                had_bye[i] = true;
+                self.table[i].points += self.tournament.seeds.len() as f64;
+            }
+            while !order.is_empty() {
+                let a = order.remove(0);
+                let next = order.iter().position(|&b| !met[a][b]).unwrap_or(0);
+                let b = order.remove(next);
+                met[a][b] = true;
+                met[b][a] = true;
+                self.series(round, a, b)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn elimination(&mut self) -> Result<(), TournamentError> {
+        let entrants = self.table.len();
+        // seeded so that the best two can only meet in the final: 1 v 8,
+        // 4 v 5, 2 v 7 and 3 v 6 for eight, with the missing seeds as byes
+        let mut bracket = vec![0];
+        while bracket.len() < entrants {
+            let size = bracket.len() * 2;
+            bracket = bracket
+                .iter()
+                .flat_map(|&seed| vec![seed, size - 1 - seed])
+                .collect();
+        }
+        let mut alive: Vec<Option<usize>> = bracket
+            .into_iter()
+            .map(|seed| if seed < entrants { Some(seed) } else { None })
+            .collect();
+
+        let mut round = 1;
+        while alive.len() > 1 {
+            let mut through = Vec::with_capacity(alive.len() / 2);
+            for pair in alive.chunks(2) {
+                through.push(match (pair[0], pair[1]) {
+                    (Some(a), Some(b)) => {
+                        let (a_points, b_points) = self.series(round, a, b)?;
+                        let a_wins = match a_points.partial_cmp(&b_points) {
+                            Some(Ordering::Greater) => true,
+                            Some(Ordering::Less) => false,
+                            _ => by_points(&self.table[a], &self.table[b]) != Ordering::Greater,
+                        };
+                        let (winner, loser) = if a_wins { (a, b) } else { (b, a) };
+                        self.table[loser].eliminated_in = Some(round);
+                        Some(winner)
+                    }
+                    (a, b) => a.or(b),
+                });
+            }
+            alive = through;
+            round += 1;
+        }
+        Ok(())
+    }
+
+    // plays `a` against `b` on every seed from both sides, returning the
+    // points each of them took
+    fn series(&mut self, round: u32, a: usize, b: usize) -> Result<(f64, f64), TournamentError> {
+        let mut points = (0.0, 0.0);
+        for &seed in self.tournament.seeds.iter() {
+            for &(first, second) in [(a, b), (b, a)].iter() {
+                let score = self.play(round, seed, [first, second])?;
+                if first == a {
+                    points.0 += score;
+                    points.1 += 1.0 - score;
+                } else {
+                    points.0 += 1.0 - score;
+                    points.1 += score;
+                }
+            }
+        }
+        Ok(points)
+    }
+
+    // plays one match between `seats` and returns what it scored for the
+    // first seat
+    fn play(&mut self, round: u32, seed: u64, seats: [usize; 2]) -> Result<f64, TournamentError> {
+        let tournament = self.tournament;
+        let mut bots = Vec::with_capacity(2);
+        for (seat, &entrant) in seats.iter().enumerate() {
+            let rng = match_rng(seed, Stream::Player(seat as u8));
+            let name = &tournament.entrants[entrant];
+            let bot = self
+                .registry
+                .create(name, rng)
+                .ok_or_else(|| TournamentError::UnknownBot(name.clone()))?;
+            bots.push(bot);
+        }
+        let report = play_match(
+            &tournament.settings,
+            seed,
+            tournament.map.as_ref(),
+            &mut bots,
+        )?;
+        let winner = report.winner().map(|player| player.index() as u8);
+        let score = match winner {
+            Some(0) => 1.0,
+            Some(_) => 0.0,
+            None => 0.5,
+        };
+
+        let [first, second] = seats;
+        let expected = expected_score(self.table[first].rating, self.table[second].rating);
+        let change = K_FACTOR * (score - expected);
+        self.table[first].rating += change;
+        self.table[second].rating -= change;
+        for (&entrant, score) in seats.iter().zip([score, 1.0 - score].iter()) {
+            let standing = &mut self.table[entrant];
+            standing.played += 1;
+            standing.points += score;
+            match score.partial_cmp(&0.5) {
+                Some(Ordering::Greater) => standing.wins += 1,
+                Some(Ordering::Less) => standing.losses += 1,
+                _ => standing.draws += 1,
+            }
+        }
+        self.matches.push(MatchRecord {
+            round,
+            seed,
+            seats: [first + 1, second + 1],
+            score: [report.wins[0], report.wins[1]],
+            winner,
+        });
+        Ok(score)
+    }
+}
+
+impl Standings {
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("standings are always valid JSON")
+    }
+}
+
+/// The table, one entrant to a line.
+impl fmt::Display for Standings {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let width = self
+            .table
+            .iter()
+            .map(|standing| standing.name.len())
+            .max()
+            .unwrap_or(0)
+            .max("bot".len());
+        let elimination = self.format == Format::Elimination;
+        write!(
+            f,
+            "{:>3}  {:<width$}  {:>4}  {:>6}  {:>6}  {:>4}  {:>4}  {:>4}  {:>6}",
+            "#",
+            "bot",
+            "seed",
+            "rating",
+            "played",
+            "won",
+            "drew",
+            "lost",
+            "points",
+            width = width
+        )?;
+        if elimination {
+            write!(f, "  out in")?;
+        }
+        for (place, standing) in self.table.iter().enumerate() {
+            write!(
+                f,
+                "\n{:>3}  {:<width$}  {:>4}  {:>6.0}  {:>6}  {:>4}  {:>4}  {:>4}  {:>6.1}",
+                place + 1,
+                standing.name,
+                standing.seed,
+                standing.rating,
+                standing.played,
+                standing.wins,
+                standing.draws,
+                standing.losses,
+                standing.points,
+                width = width
+            )?;
+            if let Some(round) = standing.eliminated_in.filter(|_| elimination) {
+                write!(f, "  round {}", round)?;
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,238 @@
+//! How each weapon moves once it has been fired: through the air, along the
+//! ground or through it.
+
+use super::{Config, Point, Projectile, Terrain, Weapon};
+
+const MIRV_WARHEADS: usize = 5;
+// px per unit of flight time between neighbouring MIRV warheads
+const MIRV_SPREAD: f64 = 0.08;
+const ROLL_PX_PER_TICK: f64 = 1.0;
+// a roller that has come this far without stopping goes off anyway
+const ROLL_DISTANCE: f64 = 300.0;
+const DIG_PX_PER_TICK: f64 = 1.5;
+const DIG_DISTANCE: f64 = 60.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Motion {
+    Flying {
+        // a MIRV that hasn't split yet
+        can_split: bool,
+    },
+    Rolling {
+        // 1.0 to the right, -1.0 to the left
+        direction: f64,
+        travelled: f64,
+    },
+    Digging {
+        entry: Point,
+        // unit vector of the direction it is boring in
+        dx: f64,
+        dy: f64,
+        travelled: f64,
+    },
+}
+
+/// One live piece of a shot. Most shots are a single warhead; a MIRV becomes
+/// several.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Warhead {
+    weapon: Weapon,
+    projectile: Projectile,
+    motion: Motion,
+}
+
+/// What a warhead did in one tick.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Step {
+    Moved,
+    /// It broke up into these warheads, which replace it.
+    Split(Vec<Warhead>),
+    /// It went off at `point`. `entry` is where a digger went into the
+    /// ground.
+    Detonated {
+        point: Point,
+        entry: Option<Point>,
+    },
+    /// It left the field.
+    Lost,
+}
+
+impl Warhead {
+    pub fn new(weapon: Weapon, projectile: Projectile) -> Warhead {
+        Warhead {
+            weapon,
+            projectile,
+            motion: Motion::Flying {
+                can_split: weapon == Weapon::Mirv,
+            },
+        }
+    }
+
+    pub fn weapon(&self) -> Weapon {
+        self.weapon
+    }
+
+    pub fn position(&self) -> Point {
+        self.projectile.position()
+    }
+
+    /// Moves the warhead on by `dt` units of flight time.
+    pub fn step(&mut self, dt: f64, config: &Config, terrain: &Terrain) -> Step {
+        match self.motion {
+            Motion::Flying { can_split } => self.fly(dt, can_split, config, terrain),
+            Motion::Rolling {
+                direction,
+                travelled,
+            } => self.roll(direction, travelled, config, terrain),
+            Motion::Digging {
+                entry,
+                dx,
+                dy,
+                travelled,
+            } => self.dig(entry, dx, dy, travelled, config, terrain),
+        }
+    }
+
+    fn fly(&mut self, dt: f64, can_split: bool, config: &Config, terrain: &Terrain) -> Step {
+        self.projectile.step(dt);
+        let point = self.projectile.position();
+
+        // stop processing if the bullet has gone below or beyond the screen,
+        // or into the sea
+        if !config.in_bounds(point) || terrain.is_under_water(point) {
+            return Step::Lost;
+        }
+        if terrain.collides(point) {
+            return self.land(point, terrain);
+        }
+        // the top of the arc is where it stops climbing
+        if can_split && self.projectile.velocity().1 >= 0.0 {
+            return Step::Split(self.split());
+        }
+        Step::Moved
+    }
+
+    fn split(&self) -> Vec<Warhead> {
+        let middle = (MIRV_WARHEADS / 2) as f64;
+        (0..MIRV_WARHEADS)
+            .map(|i| Warhead {
+                weapon: self.weapon,
+                projectile: self.projectile.split((i as f64 - middle) * MIRV_SPREAD),
+                motion: Motion::Flying { can_split: false },
+            })
+            .collect()
+    }
+
+    // the warhead has just hit the ground or a tank at `point`
+    fn land(&mut self, point: Point, terrain: &Terrain) -> Step {
+        if terrain.hits_tank(point) {
+            return Step::Detonated { point, entry: None };
+        }
+        let (vx, vy) = self.projectile.velocity();
+        match self.weapon {
+            Weapon::Roller => {
+                let column = point.x as usize;
+                let mask = terrain.mask();
+                let ground = mask.ground_below(column, point.y);
+                let left = mask.ground_below(column.saturating_sub(1), ground - 1.0);
+                let right = mask.ground_below((column + 1).min(mask.width() - 1), ground - 1.0);
+                // downhill if there is one, else the way it was going; heights
+                // grow downwards
+                let direction = if left != right {
+                    (right - left).signum()
+                } else if vx < 0.0 {
+                    -1.0
+                } else {
+                    1.0
+                };
+                self.projectile
+                    .set_position(Point::new(point.x, ground - 1.0));
+                self.motion = Motion::Rolling {
+                    direction,
+                    travelled: 0.0,
+                };
+                Step::Moved
+            }
+            Weapon::Digger => {
+                let speed = vx.hypot(vy);
+                self.motion = Motion::Digging {
+                    entry: point,
+                    dx: vx / speed,
+                    dy: vy / speed,
+                    travelled: 0.0,
+                };
+                Step::Moved
+            }
+            _ => Step::Detonated { point, entry: None },
+        }
+    }
+
+    fn roll(&mut self, direction: f64, travelled: f64, config: &Config, terrain: &Terrain) -> Step {
+        let here = self.projectile.position();
+        let x = here.x + direction * ROLL_PX_PER_TICK;
+        if x <= 0.0 || x >= config.width() {
+            return Step::Lost;
+        }
+        // the ground it is on, and where it would be one step on
+        let mask = terrain.mask();
+        let ground = mask.ground_below(x as usize, here.y);
+        if ground < mask.ground_below(here.x as usize, here.y) {
+            // rollers only go downhill
+            return Step::Detonated {
+                point: here,
+                entry: None,
+            };
+        }
+        let next = Point::new(x, ground - 1.0);
+        if terrain.is_under_water(next) {
+            return Step::Lost;
+        }
+        let travelled = travelled + ROLL_PX_PER_TICK;
+        if terrain.hits_tank(next) || travelled >= ROLL_DISTANCE {
+            return Step::Detonated {
+                point: next,
+                entry: None,
+            };
+        }
+        self.projectile.set_position(next);
+        self.motion = Motion::Rolling {
+            direction,
+            travelled,
+        };
+        Step::Moved
+    }
+
+    fn dig(
+        &mut self,
+        entry: Point,
+        dx: f64,
+        dy: f64,
+        travelled: f64,
+        config: &Config,
+        terrain: &Terrain,
+    ) -> Step {
+        let here = self.projectile.position();
+        let next = Point::new(here.x + dx * DIG_PX_PER_TICK, here.y + dy * DIG_PX_PER_TICK);
+        if !config.in_bounds(next) {
+            return Step::Detonated {
+                point: here,
+                entry: Some(entry),
+            };
+        }
+        let travelled = travelled + DIG_PX_PER_TICK;
+        if terrain.hits_tank(next) || travelled >= DIG_DISTANCE {
+            return Step::Detonated {
+                point: next,
+                entry: Some(entry),
+            };
+        }
+        self.projectile.set_position(next);
+        self.motion = Motion::Digging {
+            entry,
+            dx,
+            dy,
+            travelled,
+        };
+        Step::Moved
+    }
+}
@@ -0,0 +1,166 @@
+/// What a tank can fire.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Weapon {
+    /// The original 3px shell with a 30px blast.
+    #[default]
+    Missile,
+    /// A slow, huge blast that hurts everything nearby.
+    Nuke,
+    /// Splits into a spread of small warheads at the top of its arc.
+    Mirv,
+    /// Bores on through the ground it hits before going off.
+    Digger,
+    /// Piles up a mound of earth instead of blowing a crater.
+    DirtBomb,
+    /// Lands, then rolls downhill until it hits a tank or can't go on.
+    Roller,
+    /// Spills burning fuel that runs downhill, burning tanks it reaches.
+    Napalm,
+}
+
+/// What happens where a weapon comes down.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Effect {
+    /// A crater, with damage falling off from the center to the edge.
+    Blast { radius: f64, damage: f64 },
+    /// A mound of earth. Nobody is hurt.
+    Dirt { radius: f64 },
+    /// Fire spreading up to `reach` columns each way; every tank it reaches
+    /// takes the same `damage`.
+    Fire { reach: usize, damage: f64 },
+}
+
+impl Weapon {
+    pub const ALL: [Weapon; 7] = [
+        Weapon::Missile,
+        Weapon::Nuke,
+        Weapon::Mirv,
+        Weapon::Digger,
+        Weapon::DirtBomb,
+        Weapon::Roller,
+        Weapon::Napalm,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Weapon::Missile => "missile",
+            Weapon::Nuke => "nuke",
+            Weapon::Mirv => "mirv",
+            Weapon::Digger => "digger",
+            Weapon::DirtBomb => "dirt bomb",
+            Weapon::Roller => "roller",
+            Weapon::Napalm => "napalm",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Weapon> {
+        Weapon::ALL
+            .iter()
+            .copied()
+            .find(|weapon| weapon.name() == name)
+    }
+
+    /// What the weapon does when it goes off. For the MIRV this is each of
+    /// its warheads.
+    pub fn effect(self) -> Effect {
+        match self {
+            Weapon::Missile | Weapon::Roller => Effect::Blast {
+                radius: 30.0,
+                damage: 60.0,
+            },
+            Weapon::Nuke => Effect::Blast {
+                radius: 80.0,
+                damage: 100.0,
+            },
+            Weapon::Mirv => Effect::Blast {
+                radius: 20.0,
+                damage: 35.0,
+            },
+            Weapon::Digger => Effect::Blast {
+                radius: 12.0,
+                damage: 25.0,
+            },
+            Weapon::DirtBomb => Effect::Dirt { radius: 40.0 },
+            Weapon::Napalm => Effect::Fire {
+                reach: 40,
+                damage: 40.0,
+            },
+        }
+    }
+
+    /// Multiplies the launch speed for a given power: the nuke is heavy.
+    pub fn speed_factor(self) -> f64 {
+        match self {
+            Weapon::Nuke => 0.85,
+            _ => 1.0,
+        }
+    }
+}
+
+/// How many of each weapon a tank has left. Missiles never run out.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Inventory {
+    ammo: Vec<(Weapon, u32)>,
+}
+
+impl Inventory {
+    /// `loadout` lists the starting count of every limited weapon; any
+    /// weapon missing from it, other than the missile, can't be fired.
+    pub fn new(loadout: &[(Weapon, u32)]) -> Inventory {
+        let ammo = Weapon::ALL
+            .iter()
+            .filter(|&&weapon| weapon != Weapon::Missile)
+            .map(|&weapon| {
+                let count = loadout
+                    .iter()
+                    .filter(|(w, _)| *w == weapon)
+                    .map(|(_, count)| count)
+                    .sum();
+                (weapon, count)
+            })
+            .collect();
+        Inventory { ammo }
+    }
+
+    /// What every tank starts a match with.
+    pub fn standard() -> Inventory {
+        Inventory::new(&[
+            (Weapon::Nuke, 1),
+            (Weapon::Mirv, 2),
+            (Weapon::Digger, 3),
+            (Weapon::DirtBomb, 3),
+            (Weapon::Roller, 3),
+            (Weapon::Napalm, 2),
+        ])
+    }
+
+    /// Shots of `weapon` left, or `None` if it is unlimited.
+    pub fn count(&self, weapon: Weapon) -> Option<u32> {
+        self.ammo
+            .iter()
+            .find(|(w, _)| *w == weapon)
+            .map(|&(_, count)| count)
+    }
+
+    pub fn has(&self, weapon: Weapon) -> bool {
+        self.count(weapon) != Some(0)
+    }
+
+    // uses up one shot of `weapon`; false if there was none left
+    pub(crate) fn take(&mut self, weapon: Weapon) -> bool {
+        match self.ammo.iter_mut().find(|(w, _)| *w == weapon) {
+            Some((_, 0)) => false,
+            Some((_, count)) => {
+                *count -= 1;
+                true
+            }
+            None => true,
+        }
+    }
+}
+
+impl Default for Inventory {
+    fn default() -> Inventory {
+        Inventory::standard()
+    }
+}
@@ -0,0 +1,1408 @@
+//! The browser front-end: draws a `sim::Game` on the canvas, plays its sounds
+//! and feeds it keyboard, mouse and touch input. The map editor in `editor`
+//! and the replay viewer in `replay` share the canvas, `net` plays or
+//! watches matches against other browsers and `hotseat` lets several people
+//! play at one keyboard.
+
+use crate::sim::{
+    match_rng, round_seed, Action, Aim, BotRegistry, Command, Controller, Drag, Event, Game,
+    Keymap, Lockstep, Map, MatchResult, MatchSettings, Move, NetError, Phase, PlayerId, Plugin,
+    PluginLimits, Point, Replay, Snapshot, Spectator, Stream, Tank, Terrain, Weapon, PLAYER_COLORS,
+    TICK_MS,
+};
+use crate::utils;
+use hotseat::Profile;
+use std::cell::{Cell, RefCell};
+use std::rc::Rc;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+mod editor;
+mod hotseat;
+mod net;
+mod replay;
+
+const AUDIO_BUFFER_SIZE: usize = 8192;
+// longest gap between frames that is simulated, e.g. after the tab was hidden
+const MAX_FRAME_MS: f64 = 250.0;
+// columns at the left edge that the HUD is drawn over
+const HUD_WIDTH: usize = 480;
+// where the match in progress is kept in local storage
+const SAVE_KEY: &str = "warbots-match";
+// ticks of flight between the dots of the aim preview
+const PREVIEW_TICKS: usize = 12;
+// the empty space under the surface of the sea
+const SEA_COLOR: &str = "#1E5AA8";
+
+thread_local! {
+    static CLIENT: RefCell<Option<Client>> = const { RefCell::new(None) };
+    // whether the animation loop and input handlers are in place
+    static HANDLERS: Cell<bool> = const { Cell::new(false) };
+    // the built-in bots, and those loaded from WebAssembly this session by
+    // `load_plugin`
+    static REGISTRY: RefCell<BotRegistry> = RefCell::new(BotRegistry::new());
+}
+
+/// Starts a match on a random seed.
+#[wasm_bindgen]
+pub fn start() -> Result<(), JsValue> {
+    start_with_seed(rand::random())
+}
+
+/// Starts the match that `seed` generates, e.g. to reproduce a bug report.
+#[wasm_bindgen]
+pub fn start_with_seed(seed: u64) -> Result<(), JsValue> {
+    start_match(seed, vec!["human".to_string(), "medium".to_string()])
+}
+
+/// Names that `start_match` accepts for a player, besides `"human"`.
+#[wasm_bindgen]
+pub fn bot_names() -> js_sys::Array {
+    REGISTRY.with(|registry| {
+        let registry = registry.borrow();
+        registry.names().into_iter().map(JsValue::from).collect()
+    })
+}
+
+/// Loads the WebAssembly bot in `wasm` under `name`, for `start_match` and
+/// the rest to play. It replaces any bot of the same name.
+#[wasm_bindgen]
+pub fn load_plugin(name: &str, wasm: &[u8]) -> Result<(), JsValue> {
+    let plugin = Plugin::new(name, wasm, PluginLimits::default())
+        .map_err(|error| JsValue::from(error.to_string()))?;
+    REGISTRY.with(|registry| registry.borrow_mut().register_plugin(plugin));
+    Ok(())
+}
+
+/// Starts a match with one player for each name, in turn order, driven by
+/// `"human"` or a registered bot.
+#[wasm_bindgen]
+pub fn start_match(seed: u64, players: Vec<String>) -> Result<(), JsValue> {
+    let settings = MatchSettings {
+        players: players.len() as u8,
+        ..MatchSettings::default()
+    };
+    launch(seed, &players, settings, None)
+}
+
+/// The settings `start_match` uses, as JSON to edit and hand to
+/// `start_match_with_settings`.
+#[wasm_bindgen]
+pub fn default_settings() -> String {
+    MatchSettings::default().to_json()
+}
+
+/// Like `start_match`, with `MatchSettings` given as JSON. Settings left out
+/// keep their defaults; there must be a name for each of `players`.
+#[wasm_bindgen]
+pub fn start_match_with_settings(
+    seed: u64,
+    players: Vec<String>,
+    settings: &str,
+) -> Result<(), JsValue> {
+    let settings =
+        MatchSettings::from_json(settings).map_err(|error| JsValue::from(error.to_string()))?;
+    if players.len() != settings.players as usize {
+        return Err(JsValue::from(format!(
+            "the settings are for {} players, not {}",
+            settings.players,
+            players.len()
+        )));
+    }
+    launch(seed, &players, settings, None)
+}
+
+/// Names of the maps that ship with the game, for `library_map`.
+#[wasm_bindgen]
+pub fn map_names() -> js_sys::Array {
+    Map::library()
+        .into_iter()
+        .map(|map| JsValue::from(map.name))
+        .collect()
+}
+
+/// The library map called `name`, as JSON for `start_match_on_map`.
+#[wasm_bindgen]
+pub fn library_map(name: &str) -> Option<String> {
+    Map::from_library(name).map(|map| map.to_json())
+}
+
+/// Like `start_match`, on the map given as JSON instead of terrain from the
+/// seed. Every round is played on the same map.
+#[wasm_bindgen]
+pub fn start_match_on_map(seed: u64, players: Vec<String>, map: &str) -> Result<(), JsValue> {
+    let map = Map::from_json(map).map_err(|error| JsValue::from(error.to_string()))?;
+    let settings = MatchSettings {
+        width: map.width,
+        height: map.height,
+        players: players.len() as u8,
+        ..MatchSettings::default()
+    };
+    launch(seed, &players, settings, Some(map))
+}
+
+/// The running match's field as it is now, as map JSON called `name`.
+#[wasm_bindgen]
+pub fn export_map(name: &str) -> Option<String> {
+    with_client(|client| Map::from_terrain(name, client.game.terrain()).to_json())
+}
+
+/// Everything played so far in the running match, as replay JSON for
+/// `start_replay`, or `undefined` if no match is running.
+#[wasm_bindgen]
+pub fn export_replay() -> Option<String> {
+    with_client(|client| {
+        let mut replay = client.replay.clone();
+        replay.record(&client.game);
+        replay.to_json()
+    })
+}
+
+/// The running match as snapshot JSON for `load_match`, or `undefined` if
+/// there is none or a shot is still in the air.
+#[wasm_bindgen]
+pub fn save_match() -> Option<String> {
+    with_client(|client| client.snapshot())
+        .flatten()
+        .map(|snapshot| snapshot.to_json())
+}
+
+/// Carries on with a match saved by `save_match`. Its bots start afresh; see
+/// `Snapshot`.
+#[wasm_bindgen]
+pub fn load_match(snapshot: &str) -> Result<(), JsValue> {
+    utils::set_panic_hook();
+    let snapshot =
+        Snapshot::from_json(snapshot).map_err(|error| JsValue::from(error.to_string()))?;
+    let players = controllers(snapshot.replay.seed, &snapshot.players)?;
+    let seats = snapshot.players.clone();
+    let mut client = restore(snapshot, players)?;
+    client.seats = Some(seats);
+    net::close();
+    show_restored(client);
+    Ok(())
+}
+
+/// Carries on with the match this browser saved before the page was last
+/// left, if there is one. Returns whether there was.
+#[wasm_bindgen]
+pub fn resume_saved_match() -> Result<bool, JsValue> {
+    let saved = match local_storage().map(|storage| storage.get_item(SAVE_KEY)) {
+        Some(Ok(Some(saved))) => saved,
+        _ => return Ok(false),
+    };
+    if let Err(error) = load_match(&saved) {
+        // it would fail the same way next time
+        if let Some(storage) = local_storage() {
+            let _ = storage.remove_item(SAVE_KEY);
+        }
+        return Err(error);
+    }
+    Ok(true)
+}
+
+fn launch(
+    seed: u64,
+    names: &[String],
+    settings: MatchSettings,
+    map: Option<Map>,
+) -> Result<(), JsValue> {
+    utils::set_panic_hook();
+    let players = controllers(seed, names)?;
+    let mut client = new_match(seed, players, settings, map)?;
+    client.seats = Some(names.to_vec());
+    net::close();
+    show(client);
+    Ok(())
+}
+
+// the match `net` has found, with a human in `seat` and everyone else
+// playing from elsewhere
+fn launch_online(seed: u64, settings: MatchSettings, seat: PlayerId) -> Result<(), JsValue> {
+    let players = (0..settings.players)
+        .map(|player| {
+            if player == seat.0 {
+                Controller::Human
+            } else {
+                Controller::Remote
+            }
+        })
+        .collect();
+    let mut client = new_match(seed, players, settings, None)?;
+    client.lockstep = Some(Lockstep::new(seat));
+    show(client);
+    Ok(())
+}
+
+// the match `net` is watching, as it stood after `turns` moves, with
+// nobody here playing in it
+fn launch_spectator(snapshot: Snapshot, turns: usize) -> Result<(), JsValue> {
+    let players = snapshot
+        .players
+        .iter()
+        .map(|_| Controller::Remote)
+        .collect();
+    let mut client = restore(snapshot, players)?;
+    client.spectator = Some(Spectator::new(turns));
+    show_restored(client);
+    Ok(())
+}
+
+// a client for the first round of a new match, with the canvas sized for it
+// and the map editor and replays closed
+fn new_match(
+    seed: u64,
+    players: Vec<Controller>,
+    settings: MatchSettings,
+    map: Option<Map>,
+) -> Result<Client, JsValue> {
+    let config = settings
+        .config()
+        .map_err(|error| JsValue::from(error.to_string()))?;
+
+    web_sys::console::log_1(&format!("warbots: match seed {}", seed).into());
+    let canvas = canvas();
+    canvas.set_width(config.width() as u32);
+    canvas.set_height(config.height() as u32);
+    let game = match &map {
+        Some(map) => Game::with_map(config, map, round_seed(seed, 0))
+            .map_err(|error| JsValue::from(error.to_string()))?,
+        None => Game::new(config, round_seed(seed, 0)),
+    };
+    editor::close();
+    replay::close();
+    Ok(Client::new(game, players, Replay::new(seed, settings, map)))
+}
+
+// a client for the match `snapshot` saved, with the canvas sized for it and
+// the map editor and replays closed
+fn restore(snapshot: Snapshot, players: Vec<Controller>) -> Result<Client, JsValue> {
+    let game = snapshot
+        .game()
+        .map_err(|error| JsValue::from(error.to_string()))?;
+    let canvas = canvas();
+    canvas.set_width(game.config().width() as u32);
+    canvas.set_height(game.config().height() as u32);
+    let over = game.is_over();
+    let mut client = Client::new(game, players, snapshot.replay);
+    client.rounds_played = client.replay.rounds.len() as u32 + over as u32;
+    client.wins = snapshot.wins;
+    editor::close();
+    replay::close();
+    Ok(client)
+}
+
+// a controller for each seat, from `"human"` or a bot's name; bots are
+// seeded from the match seed
+fn controllers(seed: u64, names: &[String]) -> Result<Vec<Controller>, JsValue> {
+    REGISTRY.with(|registry| {
+        let registry = registry.borrow();
+        names
+            .iter()
+            .enumerate()
+            .map(|(seat, name)| {
+                registry
+                    .controller(name, match_rng(seed, Stream::Player(seat as u8)))
+                    .ok_or_else(|| JsValue::from(format!("unknown bot: {}", name)))
+            })
+            .collect()
+    })
+}
+
+// makes `client` the match on the canvas, replacing any other
+fn show(client: Client) {
+    client.draw_terrain(0, client.game.config().width() as usize);
+    CLIENT.with(|c| c.borrow_mut().replace(client));
+    install_handlers();
+}
+
+// shows a restored match, with the result if its round is over
+fn show_restored(client: Client) {
+    let result = client.game.result();
+    show(client);
+    if let Some(result) = result {
+        with_client(|client| client.draw_result(result));
+    }
+}
+
+fn install_handlers() {
+    if HANDLERS.with(|installed| installed.replace(true)) {
+        return;
+    }
+    let window = web_sys::window().unwrap();
+
+    // FIXME: Hack for requestAnimationFrame loop
+    let f = Rc::new(RefCell::new(None));
+    let g = f.clone();
+    *g.borrow_mut() = Some(Closure::wrap(Box::new(move |timestamp| {
+        with_client(|client| client.on_animation_frame(timestamp));
+        replay::on_animation_frame(timestamp);
+        request_animation_frame(f.borrow().as_ref().unwrap());
+    }) as Box<dyn FnMut(f64)>));
+    request_animation_frame(g.borrow().as_ref().unwrap());
+
+    // players' keys act as they go down, and repeat while held
+    let onkeydown_handler = Closure::wrap(Box::new(|e: web_sys::KeyboardEvent| {
+        if with_client(|client| client.on_key(&e.key(), e.repeat())).unwrap_or(false) {
+            // e.g. the arrow keys and space bar would scroll the page
+            e.prevent_default();
+        }
+    }) as Box<dyn FnMut(web_sys::KeyboardEvent)>);
+    window.set_onkeydown(Some(onkeydown_handler.as_ref().unchecked_ref()));
+    onkeydown_handler.forget();
+
+    // FIXME: Hacky key event handler binding
+    let onkeyup_handler = Closure::wrap(Box::new(|e: web_sys::KeyboardEvent| {
+        // the map editor and replays take their keys as they come up
+        if !editor::on_key(e.key_code()) {
+            replay::on_key(e.key_code());
+        }
+    }) as Box<dyn FnMut(web_sys::KeyboardEvent)>);
+    window.set_onkeyup(Some(onkeyup_handler.as_ref().unchecked_ref()));
+    onkeyup_handler.forget();
+
+    // the map editor and the players aiming share the canvas's mouse and
+    // touches; whichever isn't using the canvas ignores them
+    let canvas = canvas();
+    let down = mouse_handler(Mouse::Down);
+    canvas.set_onmousedown(Some(down.as_ref().unchecked_ref()));
+    down.forget();
+    let moved = mouse_handler(Mouse::Move);
+    canvas.set_onmousemove(Some(moved.as_ref().unchecked_ref()));
+    moved.forget();
+    let up = mouse_handler(Mouse::Up);
+    canvas.set_onmouseup(Some(up.as_ref().unchecked_ref()));
+    up.forget();
+    let left = mouse_handler(Mouse::Cancel);
+    canvas.set_onmouseleave(Some(left.as_ref().unchecked_ref()));
+    left.forget();
+
+    let start = touch_handler(Mouse::Down);
+    canvas.set_ontouchstart(Some(start.as_ref().unchecked_ref()));
+    start.forget();
+    let moved = touch_handler(Mouse::Move);
+    canvas.set_ontouchmove(Some(moved.as_ref().unchecked_ref()));
+    moved.forget();
+    let end = touch_handler(Mouse::Up);
+    canvas.set_ontouchend(Some(end.as_ref().unchecked_ref()));
+    end.forget();
+    let cancel = touch_handler(Mouse::Cancel);
+    canvas.set_ontouchcancel(Some(cancel.as_ref().unchecked_ref()));
+    cancel.forget();
+}
+
+fn mouse_handler(kind: Mouse) -> Closure<dyn FnMut(web_sys::MouseEvent)> {
+    Closure::wrap(Box::new(move |e: web_sys::MouseEvent| {
+        on_mouse(kind, field_point(e.client_x(), e.client_y()));
+    }) as Box<dyn FnMut(web_sys::MouseEvent)>)
+}
+
+// one finger works as a mouse does, and a second one going down cancels it
+fn touch_handler(kind: Mouse) -> Closure<dyn FnMut(web_sys::TouchEvent)> {
+    Closure::wrap(Box::new(move |e: web_sys::TouchEvent| {
+        // or the page would scroll, and the browser send mouse events too
+        e.prevent_default();
+        let kind = if kind == Mouse::Down && e.touches().length() > 1 {
+            Mouse::Cancel
+        } else {
+            kind
+        };
+        if let Some(touch) = e.changed_touches().get(0) {
+            on_mouse(kind, field_point(touch.client_x(), touch.client_y()));
+        }
+    }) as Box<dyn FnMut(web_sys::TouchEvent)>)
+}
+
+fn on_mouse(kind: Mouse, point: Point) {
+    editor::on_mouse(kind, point);
+    with_client(|client| client.on_mouse(kind, point));
+}
+
+// where on the field a point on the page is, however large the canvas is
+// shown
+fn field_point(client_x: i32, client_y: i32) -> Point {
+    let canvas = canvas();
+    let bounds = canvas.get_bounding_client_rect();
+    let scale_x = canvas.width() as f64 / bounds.width().max(1.0);
+    let scale_y = canvas.height() as f64 / bounds.height().max(1.0);
+    Point::new(
+        (client_x as f64 - bounds.left()) * scale_x,
+        (client_y as f64 - bounds.top()) * scale_y,
+    )
+}
+
+/// Names of the weapons a human can pick, for `get_weapon` to return.
+#[wasm_bindgen]
+pub fn weapon_names() -> js_sys::Array {
+    Weapon::ALL
+        .iter()
+        .map(|weapon| JsValue::from(weapon.name()))
+        .collect()
+}
+
+/// How the running match ended, or `undefined` while it is still going.
+#[wasm_bindgen]
+pub fn match_result() -> Option<MatchResult> {
+    with_client(|client| client.game.result()).flatten()
+}
+
+/// Where the active player's shot would go if they fired it now, as JSON:
+/// `{"points": [[x, y], ...], "impact": [x, y]}`, where `impact` is `null` if
+/// it would leave the field. `undefined` if the match doesn't allow aim
+/// previews or nobody is aiming.
+#[wasm_bindgen]
+pub fn aim_preview(power: f64, angle: f64, weapon: &str) -> Option<String> {
+    let action = Action {
+        power,
+        angle,
+        weapon: Weapon::from_name(weapon)?,
+    };
+    let trajectory = with_client(|client| client.game.aim_preview(&action, PREVIEW_TICKS))??;
+    let xy = |point: Point| [point.x(), point.y()];
+    let json = serde_json::json!({
+        "points": trajectory.points.iter().copied().map(xy).collect::<Vec<_>>(),
+        "impact": trajectory.impact.map(xy),
+    });
+    Some(json.to_string())
+}
+
+/// Redraws the turret, and the aim preview if there is one, after the aim
+/// has been typed into the page.
+#[wasm_bindgen]
+pub fn aim_changed() {
+    with_client(|client| {
+        client.redraw_tanks();
+        client.update_preview();
+    });
+}
+
+#[wasm_bindgen]
+pub fn tank_health(player: u8) -> f64 {
+    with_client(|client| {
+        let tanks = client.game.terrain().tanks();
+        tanks.get(player as usize).map_or(0.0, Tank::health)
+    })
+    .unwrap_or(0.0)
+}
+
+fn with_client<R, F: FnOnce(&mut Client) -> R>(f: F) -> Option<R> {
+    CLIENT.with(|c| c.borrow_mut().as_mut().map(f))
+}
+
+struct Client {
+    game: Game,
+    // the seed, settings and map of the match, with the moves of every
+    // round before this one
+    replay: Replay,
+    // who plays each seat, for saving the match; matches that aren't saved,
+    // like map editor tests, have none
+    seats: Option<Vec<String>>,
+    // rounds finished so far, and how many of them each player won
+    rounds_played: u32,
+    // these are indexed by `PlayerId`
+    wins: Vec<u32>,
+    players: Vec<Controller>,
+    profiles: Vec<Profile>,
+    // where each human was aiming when their turn last ended
+    aims: Vec<Aim>,
+    // whether humans take turns at this keyboard, and so hand it over
+    // between turns
+    hand_over: bool,
+    // the human the keyboard is being passed to, until they say they're
+    // ready
+    handoff: Option<PlayerId>,
+    // the human whose turn it was last
+    last_human: Option<PlayerId>,
+    // where the mouse or finger went down on the canvas, while it is down
+    drag: Option<Drag>,
+    // the moves of a match played over the network
+    lockstep: Option<Lockstep>,
+    // the moves of a match watched over the network
+    spectator: Option<Spectator>,
+    bot_angles: Vec<f64>,
+    audio: Audio,
+    // animation frame timestamp the projectile was last advanced at
+    last_timestamp: Option<f64>,
+    // real time not yet simulated, always less than one tick
+    pending_ms: f64,
+    // columns napalm is burning on until the turn ends
+    burning: Vec<(usize, usize)>,
+    // columns the aim preview is drawn across, until it is wiped
+    preview: Option<(usize, usize)>,
+}
+
+impl Client {
+    fn new(game: Game, players: Vec<Controller>, replay: Replay) -> Client {
+        Client {
+            game,
+            replay,
+            seats: None,
+            rounds_played: 0,
+            wins: vec![0; players.len()],
+            bot_angles: vec![90.0; players.len()],
+            profiles: (0..players.len())
+                .map(|seat| Profile::standard(PlayerId(seat as u8)))
+                .collect(),
+            aims: vec![Aim::default(); players.len()],
+            hand_over: players.iter().filter(|player| player.is_human()).count() > 1,
+            handoff: None,
+            last_human: None,
+            drag: None,
+            players,
+            lockstep: None,
+            spectator: None,
+            audio: Audio::new(),
+            last_timestamp: None,
+            pending_ms: 0.0,
+            burning: Vec::new(),
+            preview: None,
+        }
+    }
+
+    fn on_animation_frame(&mut self, timestamp: f64) {
+        match self.game.phase() {
+            Phase::Aiming => {
+                self.last_timestamp = None;
+                self.pending_ms = 0.0;
+                self.take_remote_turn();
+                self.take_bot_turn();
+            }
+            // the other players have moved on to the next round
+            Phase::Over if self.has_remote_moves() => self.start_next_round(),
+            Phase::Over => (),
+            Phase::InFlight | Phase::Resolving | Phase::TurnTransition => self.advance(timestamp),
+        }
+        self.handle_events();
+    }
+
+    // run as many ticks as the real time since the last frame covers
+    fn advance(&mut self, timestamp: f64) {
+        let dt = timestamp - self.last_timestamp.unwrap_or(timestamp);
+        self.last_timestamp = Some(timestamp);
+        self.pending_ms += dt.min(MAX_FRAME_MS);
+
+        erase_projectiles(&self.game, |player| self.look(player));
+        while self.pending_ms >= TICK_MS && !self.game.is_over() {
+            self.pending_ms -= TICK_MS;
+            self.game.step();
+            if self.game.phase() == Phase::Aiming {
+                break;
+            }
+        }
+        draw_projectiles(&self.game);
+    }
+
+    fn handle_events(&mut self) {
+        let mut turn_over = false;
+        for event in self.game.drain_events() {
+            turn_over |= matches!(event, Event::TurnStarted { .. } | Event::MatchOver(_));
+            match event {
+                Event::ShotFired { .. } => {
+                    self.audio.play(&self.audio.fire_sound);
+                    self.update_preview();
+                }
+                Event::Impact(impact) => {
+                    self.audio.play(&self.audio.collision_sound);
+                    self.draw_terrain(impact.min_index, impact.max_index);
+                    if impact.weapon == Weapon::Napalm {
+                        self.draw_fire(impact.min_index, impact.max_index);
+                        self.burning.push((impact.min_index, impact.max_index));
+                    }
+                    if impact.min_index < HUD_WIDTH {
+                        self.draw_hud();
+                    }
+                }
+                Event::Landslide {
+                    min_index,
+                    max_index,
+                } => {
+                    self.draw_terrain(min_index, max_index);
+                    if min_index < HUD_WIDTH {
+                        self.draw_hud();
+                    }
+                }
+                // health bars
+                Event::Settled { .. } => self.redraw_tanks(),
+                Event::TurnEnded { .. } => {
+                    for (min_index, max_index) in std::mem::take(&mut self.burning) {
+                        self.draw_terrain(min_index, max_index);
+                    }
+                }
+                Event::TurnStarted { player, .. } => {
+                    self.start_turn(player);
+                    self.redraw_tanks();
+                    self.draw_hud();
+                    self.update_preview();
+                    if let Some(player) = self.handoff {
+                        self.draw_handoff(player);
+                    }
+                }
+                Event::MatchOver(result) => {
+                    self.rounds_played += 1;
+                    if let Some(player) = result.winner() {
+                        self.wins[player.index()] += 1;
+                    }
+                    self.draw_result(result);
+                }
+                Event::ShotLost { .. } => (),
+            }
+        }
+        if turn_over {
+            self.autosave();
+        }
+    }
+
+    fn snapshot(&self) -> Option<Snapshot> {
+        let seats = self.seats.clone()?;
+        Snapshot::new(seats, self.replay.clone(), self.wins.clone(), &self.game).ok()
+    }
+
+    // keeps the match in local storage between turns, so that reloading the
+    // page doesn't lose it, until the last round is over
+    fn autosave(&self) {
+        let storage = match local_storage() {
+            Some(storage) if self.seats.is_some() => storage,
+            _ => return,
+        };
+        let finished = self.game.is_over() && self.rounds_played >= self.game.config().rounds();
+        let saved = if finished {
+            storage.remove_item(SAVE_KEY)
+        } else {
+            match self.snapshot() {
+                Some(snapshot) => storage.set_item(SAVE_KEY, &snapshot.to_json()),
+                // a bot fired as soon as its turn started; the last save stands
+                None => return,
+            }
+        };
+        if let Err(error) = saved {
+            web_sys::console::log_1(&format!("warbots: can't save the match: {:?}", error).into());
+        }
+    }
+
+    // puts the aim `player` left off with back in the page's boxes, and
+    // passes them the keyboard if someone else had it
+    fn start_turn(&mut self, player: PlayerId) {
+        if !self.players[player.index()].is_human() {
+            return;
+        }
+        let inventory = self.game.active_tank().inventory();
+        let aim = &mut self.aims[player.index()];
+        if !inventory.has(aim.weapon) {
+            aim.weapon = Weapon::default();
+        }
+        set_aim(aim.power, aim.angle, aim.weapon.name());
+        if self.hand_over && self.last_human != Some(player) {
+            self.handoff = Some(player);
+        }
+        self.last_human = Some(player);
+    }
+
+    // handles `key` going down, returning whether it was one of the
+    // players' keys
+    fn on_key(&mut self, key: &str, repeat: bool) -> bool {
+        // spectators only watch
+        if self.spectator.is_some() {
+            return false;
+        }
+        if self.game.is_over() {
+            let next = self
+                .local_keymaps()
+                .iter()
+                .any(|keymap| keymap.command(key) == Some(Command::Fire));
+            if next && !repeat {
+                self.start_next_round();
+            }
+            return next;
+        }
+        // the keyboard belongs to whichever human's turn it is
+        let player = self.game.active_player();
+        if !self.players[player.index()].is_human() {
+            return false;
+        }
+        let command = match hotseat::keymap(player).command(key) {
+            Some(command) => command,
+            None => return false,
+        };
+        if self.handoff.is_some() {
+            if command == Command::Fire && !repeat {
+                self.end_handoff();
+            }
+            return true;
+        }
+        match command {
+            Command::Fire if repeat => (),
+            Command::Fire => self.handle_player_fire_attempt(),
+            command => {
+                let mut aim = aim_from_page();
+                let inventory = self.game.active_tank().inventory();
+                aim.apply(command, self.game.config(), inventory);
+                set_aim(aim.power, aim.angle, aim.weapon.name());
+                self.aims[player.index()] = aim;
+                self.redraw_tanks();
+                self.update_preview();
+            }
+        }
+        true
+    }
+
+    // aims by dragging across the canvas, with the turret following the
+    // pointer, and fires when it is let go; a tap starts the next round or
+    // takes the turn handed over, as the fire key does
+    fn on_mouse(&mut self, kind: Mouse, point: Point) {
+        let drag = match kind {
+            Mouse::Down => {
+                self.drag = Some(Drag::new(point));
+                return;
+            }
+            Mouse::Cancel => {
+                self.drag = None;
+                return;
+            }
+            Mouse::Move => match self.drag {
+                Some(drag) => drag,
+                None => return,
+            },
+            Mouse::Up => match self.drag.take() {
+                Some(drag) => drag,
+                None => return,
+            },
+        };
+        if self.spectator.is_some() {
+            return;
+        }
+        let tapped = kind == Mouse::Up && !drag.is_aiming(point);
+        if self.game.is_over() {
+            if tapped {
+                self.start_next_round();
+            }
+            return;
+        }
+        let player = self.game.active_player();
+        if !self.players[player.index()].is_human() || self.game.phase() != Phase::Aiming {
+            return;
+        }
+        if self.handoff.is_some() {
+            if tapped {
+                self.end_handoff();
+            }
+            return;
+        }
+        if !drag.is_aiming(point) {
+            return;
+        }
+        let mut aim = aim_from_page();
+        let facing = self.game.active_tank().facing();
+        drag.apply(point, facing, self.game.config(), &mut aim);
+        set_aim(aim.power, aim.angle, aim.weapon.name());
+        self.aims[player.index()] = aim;
+        if kind == Mouse::Up {
+            self.handle_player_fire_attempt();
+        } else {
+            self.redraw_tanks();
+            self.update_preview();
+        }
+    }
+
+    // the human the keyboard was passed to is ready to take their turn
+    fn end_handoff(&mut self) {
+        self.handoff = None;
+        self.draw_terrain(0, self.game.config().width() as usize);
+        self.draw_hud();
+        self.update_preview();
+    }
+
+    // the keymaps of the humans at this computer, or if there are none, the
+    // standard one for whoever is watching
+    fn local_keymaps(&self) -> Vec<Keymap> {
+        let keymaps: Vec<Keymap> = self
+            .game
+            .players()
+            .filter(|player| self.players[player.index()].is_human())
+            .map(hotseat::keymap)
+            .collect();
+        if keymaps.is_empty() {
+            vec![Keymap::default()]
+        } else {
+            keymaps
+        }
+    }
+
+    fn start_next_round(&mut self) {
+        if self.rounds_played >= self.game.config().rounds() {
+            return;
+        }
+        self.replay.record(&self.game);
+        let config = self.game.config().clone();
+        let seed = round_seed(self.replay.seed, self.rounds_played);
+        self.game = match &self.replay.map {
+            Some(map) => Game::with_map(config, map, seed).expect("the map fitted the first round"),
+            None => Game::new(config, seed),
+        };
+        self.burning.clear();
+        self.handoff = None;
+        self.preview = None;
+        self.draw_terrain(0, self.game.config().width() as usize);
+    }
+
+    fn draw_result(&self, result: MatchResult) {
+        let rounds = self.game.config().rounds();
+        let winner = match result.winner() {
+            Some(player) => format!("{} wins", self.profiles[player.index()].name),
+            None => "Draw".to_string(),
+        };
+        let message = if rounds == 1 {
+            format!("{}!", winner)
+        } else if self.rounds_played < rounds {
+            let keymaps = self.local_keymaps();
+            format!(
+                "{} round {} of {}. {} or tap for the next round",
+                winner,
+                self.rounds_played,
+                rounds,
+                fire_key(&keymaps[0])
+            )
+        } else {
+            let most = self.wins.iter().copied().max().unwrap_or(0);
+            let leaders: Vec<PlayerId> = self
+                .game
+                .players()
+                .filter(|player| self.wins[player.index()] == most)
+                .collect();
+            let overall = match leaders.as_slice() {
+                [player] => format!("{} wins", self.profiles[player.index()].name),
+                _ => "Draw".to_string(),
+            };
+            let scores: Vec<String> = self.wins.iter().map(u32::to_string).collect();
+            format!("{} the match {}!", overall, scores.join("-"))
+        };
+        self.draw_banner(&message);
+    }
+
+    // a line of large text across the top of the field
+    fn draw_banner(&self, message: &str) {
+        let context = canvas_context();
+        context.set_fill_style_str("#FFFFFF");
+        context.set_font("24px monospace");
+        context.set_text_align("center");
+        let _ = context.fill_text(message, self.game.config().width() / 2.0, 70.0);
+    }
+
+    // a screen telling everyone to pass the keyboard to `player`, who
+    // presses their fire key to take their turn
+    fn draw_handoff(&self, player: PlayerId) {
+        let context = canvas_context();
+        let config = self.game.config();
+        let (width, height) = (config.width(), config.height());
+        context.set_fill_style_str("rgba(0, 0, 0, 0.85)");
+        context.fill_rect(0.0, 0.0, width, height);
+
+        let profile = &self.profiles[player.index()];
+        context.set_text_align("center");
+        context.set_fill_style_str(profile.color);
+        context.set_font("32px monospace");
+        let _ = context.fill_text(
+            &format!("Pass to {}", profile.name),
+            width / 2.0,
+            height / 2.0 - 16.0,
+        );
+        context.set_fill_style_str("#FFFFFF");
+        context.set_font("16px monospace");
+        let _ = context.fill_text(
+            &format!(
+                "{}, press {} or tap when you're ready",
+                profile.name,
+                fire_key(&hotseat::keymap(player))
+            ),
+            width / 2.0,
+            height / 2.0 + 20.0,
+        );
+    }
+
+    fn handle_player_fire_attempt(&mut self) {
+        // the keyboard belongs to whichever human's turn it is
+        let player = self.game.active_player();
+        if !self.players[player.index()].is_human() {
+            return;
+        }
+        let aim = aim_from_page();
+        self.aims[player.index()] = aim;
+        let action = aim.action();
+        let fired = match &mut self.lockstep {
+            Some(lockstep) => lockstep
+                .fire(&mut self.game, action)
+                .map(|message| net::send(&message))
+                .map_err(|error| error.to_string()),
+            None => self
+                .game
+                .fire(player, action)
+                .map_err(|error| error.to_string()),
+        };
+        if let Err(error) = fired {
+            web_sys::console::log_1(&format!("warbots: can't fire: {}", error).into());
+        }
+    }
+
+    // plays the next move that came over the network, if it is due; if it
+    // can't be played here the match stops, as it would go on differently
+    // for each player
+    fn take_remote_turn(&mut self) {
+        let applied = match (&mut self.lockstep, &mut self.spectator) {
+            (Some(lockstep), _) => lockstep.apply(&mut self.game),
+            (None, Some(spectator)) => spectator.apply(&mut self.game),
+            (None, None) => return,
+        };
+        match applied {
+            Ok(Some(Move::Fire { player, action })) => {
+                self.bot_angles[player.index()] = action.angle;
+                self.redraw_tanks();
+            }
+            Ok(_) => (),
+            Err(error) => self.stop_remote(error),
+        }
+    }
+
+    // whether moves from the relay are waiting to be played
+    fn has_remote_moves(&self) -> bool {
+        self.lockstep.as_ref().is_some_and(Lockstep::has_pending)
+            || self.spectator.as_ref().is_some_and(Spectator::has_pending)
+    }
+
+    fn stop_remote(&mut self, error: NetError) {
+        web_sys::console::log_1(&format!("warbots: {}", error).into());
+        self.lockstep = None;
+        self.spectator = None;
+        net::close();
+        self.draw_banner(&format!("Stopped: {}", error));
+    }
+
+    fn take_bot_turn(&mut self) {
+        let player = self.game.active_player();
+        let action = match &mut self.players[player.index()] {
+            Controller::Human | Controller::Remote => return,
+            Controller::Bot(bot) => bot.decide(&self.game.observation(player)),
+        };
+        let passed = match action {
+            Some(action) => {
+                self.bot_angles[player.index()] = action.angle;
+                self.redraw_tanks();
+                match self.game.fire(player, action) {
+                    Ok(()) => false,
+                    Err(error) => {
+                        web_sys::console::log_1(
+                            &format!(
+                                "warbots: {} can't fire: {}",
+                                self.profiles[player.index()].name,
+                                error
+                            )
+                            .into(),
+                        );
+                        true
+                    }
+                }
+            }
+            None => true,
+        };
+        // a bot that can't fire loses the turn, rather than being asked again
+        // every frame
+        if passed {
+            self.game.skip_turn(player).expect("it is the bot's turn");
+        }
+    }
+
+    fn redraw_tanks(&self) {
+        redraw_tanks(&self.game, |player| self.look(player));
+    }
+
+    // how `player`'s tank is drawn: whoever is aiming now, at the angle in
+    // the page's box, and other humans where they last aimed
+    fn look(&self, player: PlayerId) -> Look {
+        let angle = if !self.players[player.index()].is_human() {
+            self.bot_angles[player.index()]
+        } else if player == self.game.active_player() {
+            get_angle() as f64
+        } else {
+            self.aims[player.index()].angle
+        };
+        Look {
+            angle,
+            color: self.profiles[player.index()].color,
+        }
+    }
+
+    // wipes the aim preview, and draws it again for wherever the page is
+    // aiming if the active player is a human here and the match allows it
+    fn update_preview(&mut self) {
+        if let Some((min_index, max_index)) = self.preview.take() {
+            self.draw_terrain(min_index, max_index);
+            if min_index < HUD_WIDTH {
+                self.draw_hud();
+            }
+        }
+        let player = self.game.active_player();
+        if self.handoff.is_some()
+            || self.spectator.is_some()
+            || !self.players[player.index()].is_human()
+        {
+            return;
+        }
+        let trajectory = match self
+            .game
+            .aim_preview(&aim_from_page().action(), PREVIEW_TICKS)
+        {
+            Some(trajectory) => trajectory,
+            None => return,
+        };
+
+        let context = canvas_context();
+        context.set_fill_style_str(self.profiles[player.index()].color);
+        for point in trajectory.points.iter() {
+            context.fill_rect(point.x() - 1.0, point.y() - 1.0, 2.0, 2.0);
+        }
+        if let Some(impact) = trajectory.impact {
+            context.set_stroke_style_str(self.profiles[player.index()].color);
+            context.set_line_width(1.0);
+            context.begin_path();
+            let _ = context.arc(impact.x(), impact.y(), 4.0, 0.0, std::f64::consts::TAU);
+            context.stroke();
+        }
+        let xs = trajectory.points.iter().map(|point| point.x());
+        let min_x = xs.clone().fold(f64::INFINITY, f64::min);
+        let max_x = xs.fold(f64::NEG_INFINITY, f64::max);
+        // wide enough for the dots and the ring around the impact
+        self.preview = Some((
+            (min_x - 6.0).max(0.0) as usize,
+            (max_x + 6.0).max(0.0) as usize,
+        ));
+    }
+
+    // flames along the surface of the burning columns
+    fn draw_fire(&self, min_index: usize, max_index: usize) {
+        let context = canvas_context();
+        let terrain = self.game.terrain();
+        context.set_fill_style_str("#FF8800");
+        for i in min_index..max_index.min(terrain.width()) {
+            let flame = 2.0 + (i % 3) as f64;
+            context.fill_rect(i as f64, terrain.heights()[i] - flame, 1.0, flame);
+        }
+    }
+
+    // whose turn it is, the wind for this turn as an arrow and a strength
+    // from 0 to 10, and what the active tank has left to fire
+    fn draw_hud(&self) {
+        // the sky behind the text is wiped by redrawing the terrain under it
+        self.draw_terrain(0, HUD_WIDTH);
+        let context = canvas_context();
+        let wind = self.game.wind();
+        let max_wind = self.game.config().max_wind();
+        let strength = if max_wind > 0.0 {
+            (wind.abs() / max_wind * 10.0).round()
+        } else {
+            0.0
+        };
+        let arrow = match strength as u8 {
+            0 => "",
+            _ if wind < 0.0 => "<- ",
+            _ => "-> ",
+        };
+        let profile = &self.profiles[self.game.active_player().index()];
+        context.set_fill_style_str(profile.color);
+        context.set_font("16px monospace");
+        context.set_text_align("left");
+        let _ = context.fill_text(
+            &format!("{}  Wind: {}{}", profile.name, arrow, strength),
+            10.0,
+            20.0,
+        );
+        context.set_fill_style_str("#FFFFFF");
+
+        let inventory = self.game.active_tank().inventory();
+        let ammo: Vec<String> = Weapon::ALL
+            .iter()
+            .filter_map(|&weapon| {
+                inventory
+                    .count(weapon)
+                    .map(|count| format!("{} {}", weapon.name(), count))
+            })
+            .collect();
+        context.set_font("12px monospace");
+        let _ = context.fill_text(&ammo.join("  "), 10.0, 38.0);
+    }
+
+    fn draw_terrain(&self, min_index: usize, max_index: usize) {
+        draw_terrain(self.game.terrain(), min_index, max_index, |player| {
+            self.look(player)
+        });
+    }
+}
+
+/// A mouse button or finger going down on the canvas, moving across it or
+/// coming up; `Cancel` is the mouse leaving the canvas or the browser
+/// taking a touch over.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Mouse {
+    Down,
+    Move,
+    Up,
+    Cancel,
+}
+
+/// How a tank is drawn: its turret's angle in degrees, and its color.
+#[derive(Clone, Copy, Debug)]
+struct Look {
+    angle: f64,
+    color: &'static str,
+}
+
+impl Look {
+    // at `angle`, in the color of `player`'s seat
+    fn standard(player: PlayerId, angle: f64) -> Look {
+        Look {
+            angle,
+            color: PLAYER_COLORS[player.index() % PLAYER_COLORS.len()],
+        }
+    }
+}
+
+// draws columns `min_index` up to `max_index` of `terrain`, caves included,
+// then every tank as `look` says its player's is drawn
+fn draw_terrain(
+    terrain: &Terrain,
+    min_index: usize,
+    max_index: usize,
+    look: impl Fn(PlayerId) -> Look,
+) {
+    let context = canvas_context();
+    let terrain_color = terrain.color_hex();
+    let sky_color = terrain.sky_color_hex();
+
+    for i in min_index..max_index.min(terrain.width()) {
+        let x = i as f64;
+        // one vertical line for each stretch of ground or sky, caves
+        // included
+        for (start, end, solid) in terrain.mask().runs(i) {
+            let (start, end) = (start as f64, end as f64);
+            if solid {
+                draw_line(&context, &terrain_color, x, start, end);
+                continue;
+            }
+            // what is under the surface of the sea is water, not sky
+            let surface = terrain.sea_level().unwrap_or(end).clamp(start, end);
+            draw_line(&context, &sky_color, x, start, surface);
+            draw_line(&context, SEA_COLOR, x, surface, end);
+        }
+    }
+    // re-draw the tanks at their current locations
+    for tank in terrain.tanks() {
+        draw_tank(&context, tank, look(tank.player()));
+    }
+}
+
+// a vertical line down column `x` from `start` to `end`, if there is any of it
+fn draw_line(
+    context: &web_sys::CanvasRenderingContext2d,
+    color: &str,
+    x: f64,
+    start: f64,
+    end: f64,
+) {
+    if start >= end {
+        return;
+    }
+    context.set_stroke_style_str(color);
+    // draw the line several times to get brighter coloring
+    for _ in 0..4 {
+        context.begin_path();
+        context.move_to(x, start);
+        context.line_to(x, end);
+        context.stroke();
+    }
+}
+
+// redraws the strip around each of `game`'s tanks, wide enough to cover the
+// turret
+fn redraw_tanks(game: &Game, look: impl Fn(PlayerId) -> Look) {
+    let config = game.config();
+    for tank in game.terrain().tanks() {
+        let x = tank.location().x();
+        let x0 = (x - config.turret_length()).max(0.0) as usize;
+        let x1 = (x + config.tank_width() + config.turret_length()) as usize;
+        draw_terrain(game.terrain(), x0, x1, &look);
+    }
+}
+
+// paints over where `game`'s projectiles are, before they move: with the
+// sky, or the ground or sea if they were rolling along, digging through it
+// or touching the water
+fn erase_projectiles(game: &Game, look: impl Fn(PlayerId) -> Look) {
+    let context = canvas_context();
+    let size = game.config().projectile_size();
+    for previous in game.projectile_positions() {
+        let column = previous.x() as usize;
+        let below = Point::new(previous.x(), previous.y() + size);
+        if game.terrain().mask().is_solid(below) || game.terrain().is_under_water(below) {
+            let max_index = column + size as usize + 1;
+            draw_terrain(game.terrain(), column.saturating_sub(1), max_index, &look);
+            continue;
+        }
+        context.set_fill_style_str(&game.terrain().sky_color_hex());
+        for _ in 0..4 {
+            context.fill_rect(previous.x(), previous.y(), size, size);
+        }
+    }
+}
+
+// draws `game`'s projectiles where they are now
+fn draw_projectiles(game: &Game) {
+    let context = canvas_context();
+    let size = game.config().projectile_size();
+    context.set_fill_style_str("#FFFFFF");
+    for point in game.projectile_positions() {
+        context.fill_rect(point.x(), point.y(), size, size);
+    }
+}
+
+fn player_name(player: PlayerId) -> String {
+    format!("Player {}", player.index() + 1)
+}
+
+// the key to press to fire with `keymap`, as it is written on the keyboard
+fn fire_key(keymap: &Keymap) -> String {
+    let keys = keymap.keys(Command::Fire);
+    hotseat::key_label(keys.first().copied().unwrap_or(" "))
+}
+
+// where the page's boxes are aiming
+fn aim_from_page() -> Aim {
+    Aim {
+        power: get_power() as f64,
+        angle: get_angle() as f64,
+        weapon: Weapon::from_name(&get_weapon()).unwrap_or_default(),
+    }
+}
+
+fn draw_tank(context: &web_sys::CanvasRenderingContext2d, tank: &Tank, look: Look) {
+    let location = tank.location();
+    let top = location.y() - tank.height();
+    if tank.is_destroyed() {
+        context.set_fill_style_str("#555555");
+        context.fill_rect(location.x(), top, tank.width(), tank.height());
+        return;
+    }
+    context.set_fill_style_str(look.color);
+    context.begin_path();
+    context.fill_rect(location.x(), top, tank.width(), tank.height());
+    draw_turret(context, tank, look);
+
+    // health bar, clear of the turret at any angle
+    let bar_y = top - tank.height() - 4.0;
+    let remaining = tank.health() / tank.max_health();
+    context.set_fill_style_str("#333333");
+    context.fill_rect(location.x(), bar_y, tank.width(), 2.0);
+    context.set_fill_style_str("#00FF00");
+    context.fill_rect(location.x(), bar_y, tank.width() * remaining, 2.0);
+}
+
+fn draw_turret(context: &web_sys::CanvasRenderingContext2d, tank: &Tank, look: Look) {
+    let base: Point = tank.turret_base();
+    let tip = tank.turret_location(look.angle.to_radians());
+    context.set_stroke_style_str(look.color);
+    context.set_line_width(tank.turret_width());
+    context.begin_path();
+    context.move_to(base.x(), base.y());
+    context.line_to(tip.x(), tip.y());
+    context.stroke();
+}
+
+struct Audio {
+    audio_context: web_sys::AudioContext,
+    audio_buffer: web_sys::AudioBuffer,
+    fire_sound: Vec<f32>,
+    collision_sound: Vec<f32>,
+}
+
+impl Audio {
+    fn new() -> Audio {
+        let audio_context = web_sys::AudioContext::new().unwrap();
+        let audio_buffer = audio_context
+            .create_buffer(
+                1,
+                (audio_context.sample_rate() * 2.0) as u32,
+                audio_context.sample_rate(),
+            )
+            .unwrap();
+
+        let mut fire_sound: Vec<f32> = Vec::with_capacity(AUDIO_BUFFER_SIZE);
+        let mut collision_sound: Vec<f32> = Vec::with_capacity(AUDIO_BUFFER_SIZE);
+        for i in 0..AUDIO_BUFFER_SIZE {
+            let i2 = i.wrapping_pow(2);
+            let i3 = i.wrapping_pow(3);
+            let fire_sound_data = (i2.wrapping_sub(i3) as f32) / (i3 as f32);
+            let collision_sound_data = if i / 400 % 5 == 0 { 0.9 } else { -0.9 };
+            fire_sound.push(fire_sound_data);
+            collision_sound.push(collision_sound_data);
+        }
+
+        Audio {
+            audio_context,
+            audio_buffer,
+            fire_sound,
+            collision_sound,
+        }
+    }
+
+    fn play(&self, sample: &[f32]) {
+        let context = &self.audio_context;
+        let buffer = &self.audio_buffer;
+
+        let source = context.create_buffer_source().unwrap();
+
+        buffer.copy_to_channel(sample, 0).unwrap();
+        source.set_buffer(Some(buffer));
+        source
+            .connect_with_audio_node(&context.destination())
+            .unwrap();
+        let _ = context.resume().unwrap();
+        source.start().unwrap();
+    }
+}
+
+fn document() -> web_sys::Document {
+    web_sys::window().unwrap().document().unwrap()
+}
+
+// the browser's local storage, if it lets the page have any
+fn local_storage() -> Option<web_sys::Storage> {
+    web_sys::window()?.local_storage().ok().flatten()
+}
+
+fn canvas() -> web_sys::HtmlCanvasElement {
+    let canvas = document().get_element_by_id("warbots-canvas").unwrap();
+    canvas
+        .dyn_into::<web_sys::HtmlCanvasElement>()
+        .map_err(|_| ())
+        .unwrap()
+}
+
+fn canvas_context() -> web_sys::CanvasRenderingContext2d {
+    canvas()
+        .get_context("2d")
+        .unwrap()
+        .unwrap()
+        .dyn_into::<web_sys::CanvasRenderingContext2d>()
+        .unwrap()
+}
+
+fn request_animation_frame(f: &Closure<dyn FnMut(f64)>) {
+    web_sys::window()
+        .unwrap()
+        .request_animation_frame(f.as_ref().unchecked_ref())
+        .expect("should register `requestAnimationFrame` OK");
+}
+
+#[wasm_bindgen(module = "/www/rust-utils.js")]
+extern "C" {
+    fn get_power() -> u32;
+    fn get_angle() -> u32;
+    fn get_weapon() -> String;
+    fn set_aim(power: f64, angle: f64, weapon: &str);
+}
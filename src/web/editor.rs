@@ -0,0 +1,349 @@
+//! The map editor: shows a `MapEditor`'s map on the canvas and edits it with
+//! the mouse and keyboard.
+//!
+//! The mouse paints or erases ground, drags tanks to move their spawns and
+//! places or removes mines, depending on the tool. While a map is being
+//! tested, the canvas plays an ordinary match on it until Escape.
+
+use super::{
+    canvas, canvas_context, draw_terrain, install_handlers, net, replay, show, Client, Look, Mouse,
+    CLIENT, HUD_WIDTH,
+};
+use crate::sim::{
+    Config, Controller, Game, Map, MapEditor, PlayerId, Point, Replay, SKY_COLORS, TERRAIN_COLORS,
+};
+use crate::utils;
+use std::cell::RefCell;
+use wasm_bindgen::prelude::*;
+
+// turret angle the tanks are drawn with
+const AIM: f64 = 90.0;
+const MIN_BRUSH: f64 = 2.0;
+const MAX_BRUSH: f64 = 60.0;
+
+thread_local! {
+    static EDITOR: RefCell<Option<Editor>> = const { RefCell::new(None) };
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Tool {
+    Paint,
+    Erase,
+    Spawns,
+    Mines,
+}
+
+impl Tool {
+    const ALL: [Tool; 4] = [Tool::Paint, Tool::Erase, Tool::Spawns, Tool::Mines];
+
+    fn name(self) -> &'static str {
+        match self {
+            Tool::Paint => "paint",
+            Tool::Erase => "erase",
+            Tool::Spawns => "spawns",
+            Tool::Mines => "mines",
+        }
+    }
+}
+
+struct Editor {
+    editor: MapEditor,
+    // what test matches are seeded with
+    seed: u64,
+    tool: Tool,
+    // radius of the paint and erase brushes, in px
+    brush: f64,
+    // the mouse button is down with a brush
+    painting: bool,
+    // whose spawn is being dragged
+    dragging: Option<PlayerId>,
+    // a test match on the map is running instead
+    testing: bool,
+}
+
+/// Opens the map editor on `map`, given as JSON, or on the terrain `seed`
+/// generates if there is none. Any match that was running is stopped.
+#[wasm_bindgen]
+pub fn start_editor(seed: u64, map: Option<String>) -> Result<(), JsValue> {
+    utils::set_panic_hook();
+    let map = match map {
+        Some(json) => Map::from_json(&json).map_err(|error| JsValue::from(error.to_string()))?,
+        None => Map::from_terrain("Untitled", Game::new(Config::new(), seed).terrain()),
+    };
+    let editor = MapEditor::new(&map).map_err(|error| JsValue::from(error.to_string()))?;
+    let canvas = canvas();
+    canvas.set_width(editor.config().width() as u32);
+    canvas.set_height(editor.config().height() as u32);
+    CLIENT.with(|c| c.borrow_mut().take());
+    replay::close();
+    net::close();
+
+    let editor = Editor {
+        editor,
+        seed,
+        tool: Tool::Paint,
+        brush: 10.0,
+        painting: false,
+        dragging: None,
+        testing: false,
+    };
+    editor.draw(0, editor.width());
+    EDITOR.with(|e| e.borrow_mut().replace(editor));
+    install_handlers();
+    Ok(())
+}
+
+/// Names of the editor's tools, for `editor_tool`.
+#[wasm_bindgen]
+pub fn editor_tools() -> js_sys::Array {
+    Tool::ALL
+        .iter()
+        .map(|tool| JsValue::from(tool.name()))
+        .collect()
+}
+
+/// Picks what the mouse does in the editor.
+#[wasm_bindgen]
+pub fn editor_tool(name: &str) -> Result<(), JsValue> {
+    let tool = Tool::ALL
+        .iter()
+        .copied()
+        .find(|tool| tool.name() == name)
+        .ok_or_else(|| JsValue::from(format!("unknown tool: {}", name)))?;
+    with_editor(|editor| {
+        editor.tool = tool;
+        editor.draw_hud();
+    });
+    Ok(())
+}
+
+/// The colors the ground can be, for `editor_colors`.
+#[wasm_bindgen]
+pub fn terrain_colors() -> js_sys::Array {
+    TERRAIN_COLORS
+        .iter()
+        .map(|&color| JsValue::from(color))
+        .collect()
+}
+
+/// The colors the sky can be, for `editor_colors`.
+#[wasm_bindgen]
+pub fn sky_colors() -> js_sys::Array {
+    SKY_COLORS
+        .iter()
+        .map(|&color| JsValue::from(color))
+        .collect()
+}
+
+/// Recolors the map being edited.
+#[wasm_bindgen]
+pub fn editor_colors(color: &str, sky_color: &str) -> Result<(), JsValue> {
+    with_editor(|editor| editor.set_colors(color, sky_color))
+        .unwrap_or(Ok(()))
+        .map_err(JsValue::from)
+}
+
+/// The map being edited, as JSON called `name`, or `undefined` if the editor
+/// isn't open.
+#[wasm_bindgen]
+pub fn export_editor_map(name: &str) -> Result<Option<String>, JsValue> {
+    with_editor(|editor| editor.editor.to_map(name).map(|map| map.to_json()))
+        .transpose()
+        .map_err(|error| JsValue::from(error.to_string()))
+}
+
+fn with_editor<R, F: FnOnce(&mut Editor) -> R>(f: F) -> Option<R> {
+    EDITOR.with(|e| e.borrow_mut().as_mut().map(f))
+}
+
+/// Leaves the editor, e.g. because a match is starting.
+pub(super) fn close() {
+    EDITOR.with(|e| e.borrow_mut().take());
+}
+
+/// Handles a key for the editor if it is open, returning whether it did.
+/// While testing, only Escape, which goes back to editing, is the editor's.
+pub(super) fn on_key(key: u32) -> bool {
+    with_editor(|editor| editor.on_key(key)).unwrap_or(false)
+}
+
+pub(super) fn on_mouse(kind: Mouse, point: Point) {
+    with_editor(|editor| {
+        if !editor.testing {
+            editor.on_mouse(kind, point);
+        }
+    });
+}
+
+impl Editor {
+    fn width(&self) -> usize {
+        self.editor.terrain().width()
+    }
+
+    fn on_key(&mut self, key: u32) -> bool {
+        const KEY_ESCAPE: u32 = 27;
+        const KEY_C: u32 = 67;
+        const KEY_E: u32 = 69;
+        const KEY_K: u32 = 75;
+        const KEY_M: u32 = 77;
+        const KEY_P: u32 = 80;
+        const KEY_S: u32 = 83;
+        const KEY_T: u32 = 84;
+        const KEY_OPEN_BRACKET: u32 = 219;
+        const KEY_CLOSE_BRACKET: u32 = 221;
+
+        if self.testing {
+            if key == KEY_ESCAPE {
+                self.stop_test();
+            }
+            return key == KEY_ESCAPE;
+        }
+        match key {
+            KEY_P => self.tool = Tool::Paint,
+            KEY_E => self.tool = Tool::Erase,
+            KEY_S => self.tool = Tool::Spawns,
+            KEY_M => self.tool = Tool::Mines,
+            KEY_OPEN_BRACKET => self.brush = (self.brush - 2.0).max(MIN_BRUSH),
+            KEY_CLOSE_BRACKET => self.brush = (self.brush + 2.0).min(MAX_BRUSH),
+            KEY_C | KEY_K => {
+                let terrain = self.editor.terrain();
+                let (mut color, mut sky_color) = (terrain.color_hex(), terrain.sky_color_hex());
+                if key == KEY_C {
+                    color = next(&TERRAIN_COLORS, &color).to_string();
+                } else {
+                    sky_color = next(&SKY_COLORS, &sky_color).to_string();
+                }
+                let _ = self.set_colors(&color, &sky_color);
+                return true;
+            }
+            KEY_T => {
+                self.start_test();
+                return true;
+            }
+            _ => return true,
+        }
+        self.draw_hud();
+        true
+    }
+
+    fn on_mouse(&mut self, kind: Mouse, point: Point) {
+        match (kind, self.tool) {
+            (Mouse::Down, Tool::Spawns) => self.dragging = self.editor.spawn_at(point),
+            (Mouse::Down, Tool::Mines) => {
+                // clicks on the canvas's very edge can miss the field
+                if let Ok((min_index, max_index)) = self.editor.toggle_mine(point) {
+                    self.draw(min_index, max_index);
+                }
+            }
+            (Mouse::Down, Tool::Paint) | (Mouse::Down, Tool::Erase) => {
+                self.painting = true;
+                self.brush_at(point);
+            }
+            (Mouse::Move, Tool::Spawns) => {
+                if let Some(player) = self.dragging {
+                    // the tank is held by its middle
+                    let x = point.x - self.editor.config().tank_width() / 2.0;
+                    let (min_index, max_index) = self.editor.move_spawn(player, x);
+                    self.draw(min_index, max_index);
+                }
+            }
+            (Mouse::Move, _) if self.painting => self.brush_at(point),
+            (Mouse::Up, _) | (Mouse::Cancel, _) => {
+                self.painting = false;
+                self.dragging = None;
+            }
+            (Mouse::Move, _) => (),
+        }
+    }
+
+    fn brush_at(&mut self, point: Point) {
+        let (min_index, max_index) = match self.tool {
+            Tool::Erase => self.editor.erase(point, self.brush),
+            _ => self.editor.paint(point, self.brush),
+        };
+        self.draw(min_index, max_index);
+    }
+
+    fn set_colors(&mut self, color: &str, sky_color: &str) -> Result<(), String> {
+        self.editor
+            .set_colors(color, sky_color)
+            .map_err(|error| error.to_string())?;
+        self.draw(0, self.width());
+        Ok(())
+    }
+
+    fn start_test(&mut self) {
+        let (game, map) = match (self.editor.test_game(self.seed), self.editor.to_map("test")) {
+            (Ok(game), Ok(map)) => (game, map),
+            (Err(error), _) | (_, Err(error)) => {
+                web_sys::console::log_1(&format!("warbots: can't test the map: {}", error).into());
+                return;
+            }
+        };
+        let players = game.players().map(|_| Controller::Human).collect();
+        self.painting = false;
+        self.dragging = None;
+        self.testing = true;
+        let replay = Replay::new(self.seed, self.editor.settings().clone(), Some(map));
+        let mut client = Client::new(game, players, replay);
+        // whoever is testing the map plays every tank
+        client.hand_over = false;
+        show(client);
+    }
+
+    fn stop_test(&mut self) {
+        CLIENT.with(|c| c.borrow_mut().take());
+        self.testing = false;
+        self.draw(0, self.width());
+    }
+
+    fn draw(&self, min_index: usize, max_index: usize) {
+        self.draw_map(min_index, max_index);
+        if min_index < HUD_WIDTH {
+            self.draw_hud();
+        }
+    }
+
+    // the ground, tanks and mines in the columns
+    fn draw_map(&self, min_index: usize, max_index: usize) {
+        let terrain = self.editor.terrain();
+        draw_terrain(terrain, min_index, max_index, |player| {
+            Look::standard(player, AIM)
+        });
+        let context = canvas_context();
+        context.set_fill_style_str("#FF0000");
+        for mine in terrain.hazards() {
+            let at = mine.position();
+            if at.x() + 2.0 >= min_index as f64 && at.x() - 2.0 < max_index as f64 {
+                context.fill_rect(at.x() - 2.0, at.y() - 2.0, 4.0, 4.0);
+            }
+        }
+    }
+
+    // the tool, brush size and the keys
+    fn draw_hud(&self) {
+        // the sky behind the text is wiped by redrawing the map under it
+        self.draw_map(0, HUD_WIDTH);
+        let context = canvas_context();
+        context.set_fill_style_str("#FFFFFF");
+        context.set_font("16px monospace");
+        context.set_text_align("left");
+        let _ = context.fill_text(
+            &format!("Map editor: {}, brush {}", self.tool.name(), self.brush),
+            10.0,
+            20.0,
+        );
+        context.set_font("12px monospace");
+        let _ = context.fill_text(
+            "P paint  E erase  S spawns  M mines  C/K colors  [ ] brush  T test",
+            10.0,
+            38.0,
+        );
+    }
+}
+
+// the color after `current` in `palette`, going round
+fn next<'a>(palette: &[&'a str], current: &str) -> &'a str {
+    let index = palette.iter().position(|&color| color == current);
+    palette[index.map_or(0, |index| (index + 1) % palette.len())]
+}
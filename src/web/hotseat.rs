@@ -0,0 +1,148 @@
+//! Hot-seat play: several humans taking turns at one computer, each with
+//! their own name, color, keys and aim.
+//!
+//! Keymaps are kept per seat in local storage, so they apply to every match
+//! until they are changed. Names and colors are given when the match starts
+//! and aren't saved with it.
+
+use super::{launch, local_storage, player_name, with_client};
+use crate::sim::{Keymap, MatchSettings, PlayerId, PLAYER_COLORS};
+use std::cell::RefCell;
+use wasm_bindgen::prelude::*;
+
+// where each seat's keymap is kept in local storage
+const KEYS_KEY: &str = "warbots-keys";
+
+thread_local! {
+    // keymaps by seat, loaded from local storage on first use
+    static KEYMAPS: RefCell<Option<Vec<Keymap>>> = const { RefCell::new(None) };
+}
+
+/// What a player is called and the color their tank is drawn in.
+#[derive(Clone, Debug)]
+pub(super) struct Profile {
+    pub(super) name: String,
+    pub(super) color: &'static str,
+}
+
+impl Profile {
+    // "Player 1" and so on, in the seat's own color
+    pub(super) fn standard(player: PlayerId) -> Profile {
+        Profile {
+            name: player_name(player),
+            color: PLAYER_COLORS[player.index() % PLAYER_COLORS.len()],
+        }
+    }
+}
+
+/// Starts a match between humans taking turns at this computer, one for
+/// each of `names`, in turn order. `colors` gives each of them one of
+/// `player_colors`, or can be empty to leave every tank its seat's color.
+#[wasm_bindgen]
+pub fn start_hot_seat(seed: u64, names: Vec<String>, colors: Vec<String>) -> Result<(), JsValue> {
+    if !colors.is_empty() && colors.len() != names.len() {
+        return Err(JsValue::from(format!(
+            "{} colors for {} players",
+            colors.len(),
+            names.len()
+        )));
+    }
+    let mut profiles: Vec<Profile> = (0..names.len())
+        .map(|seat| Profile::standard(PlayerId(seat as u8)))
+        .collect();
+    for (seat, color) in colors.iter().enumerate() {
+        let color = PLAYER_COLORS
+            .iter()
+            .find(|known| known.eq_ignore_ascii_case(color))
+            .ok_or_else(|| JsValue::from(format!("{} isn't one of the player colors", color)))?;
+        if profiles[..seat].iter().any(|taken| taken.color == *color) {
+            return Err(JsValue::from(format!("{} is taken twice", color)));
+        }
+        profiles[seat].color = color;
+    }
+    for (profile, name) in profiles.iter_mut().zip(names.iter()) {
+        if !name.trim().is_empty() {
+            profile.name = name.trim().to_string();
+        }
+    }
+
+    let settings = MatchSettings {
+        players: names.len() as u8,
+        ..MatchSettings::default()
+    };
+    let seats = vec!["human".to_string(); names.len()];
+    launch(seed, &seats, settings, None)?;
+    with_client(|client| {
+        client.profiles = profiles;
+        client.draw_terrain(0, client.game.config().width() as usize);
+    });
+    Ok(())
+}
+
+/// The colors `start_hot_seat` can give players.
+#[wasm_bindgen]
+pub fn player_colors() -> js_sys::Array {
+    PLAYER_COLORS
+        .iter()
+        .map(|&color| JsValue::from(color))
+        .collect()
+}
+
+/// The keys the player in `seat` (counting from 0) plays with, as keymap
+/// JSON for `set_keys`.
+#[wasm_bindgen]
+pub fn keys(seat: u8) -> String {
+    keymap(PlayerId(seat)).to_json()
+}
+
+/// Sets the keys the player in `seat` plays with from now on, in this match
+/// and the next, as keymap JSON from key to command.
+#[wasm_bindgen]
+pub fn set_keys(seat: u8, keymap: &str) -> Result<(), JsValue> {
+    let keymap = Keymap::from_json(keymap).map_err(|error| JsValue::from(error.to_string()))?;
+    let saved = with_keymaps(|keymaps| {
+        if keymaps.len() <= seat as usize {
+            keymaps.resize(seat as usize + 1, Keymap::default());
+        }
+        keymaps[seat as usize] = keymap;
+        serde_json::to_string(keymaps).expect("keymaps are always valid JSON")
+    });
+    if let Some(storage) = local_storage() {
+        let _ = storage.set_item(KEYS_KEY, &saved);
+    }
+    Ok(())
+}
+
+// the keys `player` plays with
+pub(super) fn keymap(player: PlayerId) -> Keymap {
+    with_keymaps(|keymaps| keymaps.get(player.index()).cloned().unwrap_or_default())
+}
+
+fn with_keymaps<R>(f: impl FnOnce(&mut Vec<Keymap>) -> R) -> R {
+    KEYMAPS.with(|k| {
+        let mut keymaps = k.borrow_mut();
+        f(keymaps.get_or_insert_with(load_keymaps))
+    })
+}
+
+// the keymaps saved in local storage, or none if they can't be read
+fn load_keymaps() -> Vec<Keymap> {
+    let saved = match local_storage().map(|storage| storage.get_item(KEYS_KEY)) {
+        Some(Ok(Some(saved))) => saved,
+        _ => return Vec::new(),
+    };
+    let texts: Vec<serde_json::Value> = serde_json::from_str(&saved).unwrap_or_default();
+    texts
+        .iter()
+        .map(|text| Keymap::from_json(&text.to_string()).unwrap_or_default())
+        .collect()
+}
+
+// how `key` is written on the keyboard
+pub(super) fn key_label(key: &str) -> String {
+    match key {
+        " " => "Space".to_string(),
+        _ if key.chars().count() == 1 => key.to_uppercase(),
+        _ => key.to_string(),
+    }
+}
@@ -0,0 +1,346 @@
+//! Networked matches: talks to a `warbots-server` relay over a WebSocket and
+//! hands what it says to the running match's `Lockstep`.
+//!
+//! Only one connection is open at a time. If it drops, it is opened again
+//! after a growing delay and the seat taken back, and each side sends the
+//! moves the other missed. Spectators start watching again from a fresh
+//! snapshot.
+
+use super::{launch_online, launch_spectator, with_client};
+use crate::sim::{ClientMessage, MatchSettings, PlayerId, ServerMessage, PROTOCOL_VERSION};
+use crate::utils;
+use std::cell::RefCell;
+use wasm_bindgen::prelude::*;
+use wasm_bindgen::JsCast;
+
+// delay before the first attempt to reconnect, doubled after each failure
+const MIN_RETRY_MS: i32 = 500;
+const MAX_RETRY_MS: i32 = 8000;
+
+thread_local! {
+    static CONNECTION: RefCell<Option<Connection>> = const { RefCell::new(None) };
+}
+
+struct Connection {
+    url: String,
+    room: String,
+    settings: Option<MatchSettings>,
+    // whether this browser only watches the room
+    watch: bool,
+    socket: web_sys::WebSocket,
+    // counts the sockets opened, so that events from old ones are ignored
+    generation: u32,
+    // the seat the relay gave this browser, once it has
+    seat: Option<Seat>,
+    retry_ms: i32,
+}
+
+#[derive(Clone)]
+struct Seat {
+    seat: u8,
+    token: u64,
+    seed: u64,
+    settings: MatchSettings,
+}
+
+/// Connects to the relay at `url`, e.g. `ws://localhost:9001`, and takes a
+/// seat in `room`. The match starts on the canvas once every seat is
+/// taken. Whoever opens the room picks the settings, as JSON, or the
+/// defaults if `settings` is `undefined`.
+#[wasm_bindgen]
+pub fn join_online(url: &str, room: &str, settings: Option<String>) -> Result<(), JsValue> {
+    utils::set_panic_hook();
+    let settings = settings
+        .map(|settings| MatchSettings::from_json(&settings))
+        .transpose()
+        .map_err(|error| JsValue::from(error.to_string()))?;
+    connect(url, room, settings, false)
+}
+
+/// Connects to the relay at `url` and watches the match in `room` on the
+/// canvas, from wherever it has got to, without playing in it.
+#[wasm_bindgen]
+pub fn watch_online(url: &str, room: &str) -> Result<(), JsValue> {
+    utils::set_panic_hook();
+    connect(url, room, None, true)
+}
+
+fn connect(
+    url: &str,
+    room: &str,
+    settings: Option<MatchSettings>,
+    watch: bool,
+) -> Result<(), JsValue> {
+    close();
+    let generation = 0;
+    let socket = open(url, generation)?;
+    CONNECTION.with(|c| {
+        c.borrow_mut().replace(Connection {
+            url: url.to_string(),
+            room: room.to_string(),
+            settings,
+            watch,
+            socket,
+            generation,
+            seat: None,
+            retry_ms: MIN_RETRY_MS,
+        })
+    });
+    status(&format!("connecting to {}", url));
+    Ok(())
+}
+
+/// Disconnects from the relay. The match stays on the canvas, stopped at the
+/// other players' next move.
+#[wasm_bindgen]
+pub fn leave_online() {
+    close();
+    status("");
+}
+
+// closes the connection for good, if there is one
+pub(super) fn close() {
+    if let Some(connection) = CONNECTION.with(|c| c.borrow_mut().take()) {
+        let _ = connection.socket.close();
+    }
+}
+
+// sends `message` if the relay is connected; if not, rejoining sends what
+// it missed
+pub(super) fn send(message: &ClientMessage) {
+    CONNECTION.with(|c| {
+        if let Some(connection) = c.borrow().as_ref() {
+            if connection.socket.ready_state() == web_sys::WebSocket::OPEN {
+                let _ = connection.socket.send_with_str(&message.to_json());
+            }
+        }
+    });
+}
+
+fn open(url: &str, generation: u32) -> Result<web_sys::WebSocket, JsValue> {
+    let socket = web_sys::WebSocket::new(url)?;
+
+    let onopen = Closure::wrap(Box::new(move || {
+        if is_current(generation) {
+            on_open();
+        }
+    }) as Box<dyn FnMut()>);
+    socket.set_onopen(Some(onopen.as_ref().unchecked_ref()));
+    onopen.forget();
+
+    let onmessage = Closure::wrap(Box::new(move |e: web_sys::MessageEvent| {
+        if !is_current(generation) {
+            return;
+        }
+        match e
+            .data()
+            .as_string()
+            .map(|text| ServerMessage::from_json(&text))
+        {
+            Some(Ok(message)) => on_message(message),
+            Some(Err(error)) => log(&format!("can't read the relay's message: {}", error)),
+            None => log("the relay sent a binary message"),
+        }
+    }) as Box<dyn FnMut(web_sys::MessageEvent)>);
+    socket.set_onmessage(Some(onmessage.as_ref().unchecked_ref()));
+    onmessage.forget();
+
+    let onclose = Closure::wrap(Box::new(move || {
+        if is_current(generation) {
+            on_close();
+        }
+    }) as Box<dyn FnMut()>);
+    socket.set_onclose(Some(onclose.as_ref().unchecked_ref()));
+    onclose.forget();
+
+    Ok(socket)
+}
+
+fn is_current(generation: u32) -> bool {
+    CONNECTION.with(|c| {
+        c.borrow()
+            .as_ref()
+            .is_some_and(|connection| connection.generation == generation)
+    })
+}
+
+// takes a seat, takes the old one back or starts watching
+fn on_open() {
+    let since = with_client(|client| client.lockstep.as_ref().map(|lockstep| lockstep.turns()))
+        .flatten()
+        .unwrap_or(0);
+    let message = CONNECTION.with(|c| {
+        let c = c.borrow();
+        let connection = c.as_ref().expect("the socket is current");
+        if connection.watch {
+            return ClientMessage::Watch {
+                version: PROTOCOL_VERSION,
+                room: connection.room.clone(),
+            };
+        }
+        match &connection.seat {
+            Some(seat) => ClientMessage::Rejoin {
+                version: PROTOCOL_VERSION,
+                room: connection.room.clone(),
+                seat: seat.seat,
+                token: seat.token,
+                since,
+            },
+            None => ClientMessage::Join {
+                version: PROTOCOL_VERSION,
+                room: connection.room.clone(),
+                settings: connection.settings.clone(),
+            },
+        }
+    });
+    send(&message);
+}
+
+fn on_message(message: ServerMessage) {
+    match &message {
+        ServerMessage::Joined {
+            room,
+            seat,
+            token,
+            seed,
+            settings,
+            started,
+            ..
+        } => {
+            CONNECTION.with(|c| {
+                if let Some(connection) = c.borrow_mut().as_mut() {
+                    connection.retry_ms = MIN_RETRY_MS;
+                    connection.seat = Some(Seat {
+                        seat: *seat,
+                        token: *token,
+                        seed: *seed,
+                        settings: settings.clone(),
+                    });
+                }
+            });
+            status(&format!(
+                "in room {} as Player {}{}",
+                room,
+                seat + 1,
+                if *started {
+                    ""
+                } else {
+                    ", waiting for players"
+                }
+            ));
+            if *started {
+                start();
+            }
+        }
+        ServerMessage::Watching {
+            room,
+            turns,
+            snapshot,
+        } => {
+            CONNECTION.with(|c| {
+                if let Some(connection) = c.borrow_mut().as_mut() {
+                    connection.retry_ms = MIN_RETRY_MS;
+                }
+            });
+            status(&format!("watching room {}", room));
+            if let Err(error) = launch_spectator((**snapshot).clone(), *turns) {
+                log(&format!("can't watch the match: {:?}", error));
+            }
+        }
+        ServerMessage::Started => {
+            status("the match has started");
+            start();
+        }
+        ServerMessage::Presence { seat, connected } => status(&format!(
+            "Player {} {}",
+            seat + 1,
+            if *connected { "is here" } else { "dropped out" }
+        )),
+        ServerMessage::Error { message } => {
+            log(message);
+            status(message);
+        }
+        ServerMessage::Moves { .. } => (),
+    }
+    let replies = with_client(|client| {
+        if let Some(spectator) = &mut client.spectator {
+            spectator.receive(&message);
+        }
+        match &mut client.lockstep {
+            Some(lockstep) => lockstep.receive(&message),
+            None => Vec::new(),
+        }
+    });
+    for reply in replies.unwrap_or_default() {
+        send(&reply);
+    }
+}
+
+// puts the match on the canvas, unless it is already there
+fn start() {
+    let running = with_client(|client| client.lockstep.is_some()).unwrap_or(false);
+    let seat = CONNECTION.with(|c| c.borrow().as_ref().and_then(|c| c.seat.clone()));
+    if let (false, Some(seat)) = (running, seat) {
+        if let Err(error) = launch_online(seat.seed, seat.settings, PlayerId(seat.seat)) {
+            log(&format!("can't start the match: {:?}", error));
+        }
+    }
+}
+
+// tries again after a while
+fn on_close() {
+    let retry = CONNECTION.with(|c| {
+        let mut c = c.borrow_mut();
+        let connection = c.as_mut()?;
+        let delay = connection.retry_ms;
+        connection.retry_ms = (delay * 2).min(MAX_RETRY_MS);
+        Some(delay)
+    });
+    let delay = match retry {
+        Some(delay) => delay,
+        None => return,
+    };
+    status(&format!(
+        "lost the relay, reconnecting in {}s",
+        delay as f64 / 1000.0
+    ));
+    let reconnect = Closure::once_into_js(|| {
+        let failed = CONNECTION.with(|c| {
+            let mut c = c.borrow_mut();
+            let connection = match c.as_mut() {
+                Some(connection) => connection,
+                None => return false,
+            };
+            connection.generation += 1;
+            match open(&connection.url, connection.generation) {
+                Ok(socket) => {
+                    connection.socket = socket;
+                    false
+                }
+                Err(error) => {
+                    log(&format!("can't reconnect: {:?}", error));
+                    true
+                }
+            }
+        });
+        // no socket means no close event to try again from, so wait out the
+        // next, longer delay here
+        if failed {
+            on_close();
+        }
+    });
+    let _ = web_sys::window()
+        .unwrap()
+        .set_timeout_with_callback_and_timeout_and_arguments_0(reconnect.unchecked_ref(), delay);
+}
+
+fn log(message: &str) {
+    web_sys::console::log_1(&format!("warbots: {}", message).into());
+}
+
+// shows how the connection is doing next to the join button
+fn status(message: &str) {
+    if let Some(element) = super::document().get_element_by_id("online-status") {
+        element.set_text_content(Some(message));
+    }
+}
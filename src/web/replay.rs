@@ -0,0 +1,337 @@
+//! The replay viewer: plays a `Replay` back on the canvas.
+//!
+//! Space pauses and resumes, the right arrow plays on to the next turn, the
+//! up and down arrows change the speed and Escape closes the viewer.
+//! `replay_seek` jumps straight to any turn.
+
+use super::{
+    canvas, canvas_context, draw_projectiles, draw_terrain, editor, erase_projectiles,
+    install_handlers, net, player_name, redraw_tanks, Look, CLIENT, HUD_WIDTH, MAX_FRAME_MS,
+};
+use crate::sim::{Event, Move, Phase, Playback, PlayerId, Replay, TICK_MS};
+use crate::utils;
+use std::cell::RefCell;
+use wasm_bindgen::prelude::*;
+
+// how many times faster than real time the replay can run
+const SPEEDS: [f64; 7] = [0.25, 0.5, 1.0, 2.0, 4.0, 8.0, 16.0];
+// time between one turn ending and the next shot, so that the shots can be
+// told apart, or before the next round starts
+const TURN_PAUSE_MS: f64 = 500.0;
+
+thread_local! {
+    static VIEWER: RefCell<Option<Viewer>> = const { RefCell::new(None) };
+}
+
+struct Viewer {
+    playback: Playback,
+    paused: bool,
+    speed: f64,
+    // angle each player last fired at, to draw its turret at
+    angles: Vec<f64>,
+    // animation frame timestamp the playback was last advanced at
+    last_timestamp: Option<f64>,
+    // scaled time not yet played
+    pending_ms: f64,
+}
+
+/// Plays back a replay given as JSON, from the start. Any match or map
+/// editor on the canvas is closed.
+#[wasm_bindgen]
+pub fn start_replay(replay: &str) -> Result<(), JsValue> {
+    utils::set_panic_hook();
+    let playback = Replay::from_json(replay)
+        .and_then(Playback::new)
+        .map_err(|error| JsValue::from(error.to_string()))?;
+    let config = playback.game().config();
+    let canvas = canvas();
+    canvas.set_width(config.width() as u32);
+    canvas.set_height(config.height() as u32);
+    CLIENT.with(|c| c.borrow_mut().take());
+    editor::close();
+    net::close();
+
+    let viewer = Viewer {
+        angles: vec![90.0; playback.game().players().count()],
+        playback,
+        paused: false,
+        speed: 1.0,
+        last_timestamp: None,
+        pending_ms: 0.0,
+    };
+    viewer.draw();
+    VIEWER.with(|v| v.borrow_mut().replace(viewer));
+    install_handlers();
+    Ok(())
+}
+
+/// Pauses or resumes the replay.
+#[wasm_bindgen]
+pub fn replay_pause(paused: bool) {
+    with_viewer(|viewer| {
+        viewer.paused = paused;
+        viewer.draw_hud();
+    });
+}
+
+/// Sets how many times faster than real time the replay runs, from 0.25
+/// to 16.
+#[wasm_bindgen]
+pub fn replay_speed(speed: f64) -> Result<(), JsValue> {
+    if !(SPEEDS[0]..=SPEEDS[SPEEDS.len() - 1]).contains(&speed) {
+        return Err(JsValue::from(format!("speed {} is out of range", speed)));
+    }
+    with_viewer(|viewer| {
+        viewer.speed = speed;
+        viewer.draw_hud();
+    });
+    Ok(())
+}
+
+/// Pauses the replay and plays the next turn through.
+#[wasm_bindgen]
+pub fn replay_step() -> Result<(), JsValue> {
+    with_viewer(Viewer::step).unwrap_or(Ok(()))
+}
+
+/// Jumps to just before move `turn` of `round`, both counting from 0.
+#[wasm_bindgen]
+pub fn replay_seek(round: u32, turn: u32) -> Result<(), JsValue> {
+    with_viewer(|viewer| {
+        viewer
+            .playback
+            .seek(round as usize, turn as usize)
+            .map_err(|error| JsValue::from(error.to_string()))?;
+        viewer.restart_drawing();
+        Ok(())
+    })
+    .unwrap_or(Ok(()))
+}
+
+fn with_viewer<R, F: FnOnce(&mut Viewer) -> R>(f: F) -> Option<R> {
+    VIEWER.with(|v| v.borrow_mut().as_mut().map(f))
+}
+
+/// Closes the viewer, e.g. because a match is starting.
+pub(super) fn close() {
+    VIEWER.with(|v| v.borrow_mut().take());
+}
+
+pub(super) fn on_animation_frame(timestamp: f64) {
+    with_viewer(|viewer| viewer.on_animation_frame(timestamp));
+}
+
+/// Handles a key for the viewer if it is open, returning whether it did.
+pub(super) fn on_key(key: u32) -> bool {
+    const KEY_ESCAPE: u32 = 27;
+    if key == KEY_ESCAPE {
+        return VIEWER.with(|v| v.borrow_mut().take()).is_some();
+    }
+    with_viewer(|viewer| viewer.on_key(key)).is_some()
+}
+
+impl Viewer {
+    fn on_key(&mut self, key: u32) {
+        const KEY_SPACE: u32 = 32;
+        const KEY_UP: u32 = 38;
+        const KEY_RIGHT: u32 = 39;
+        const KEY_DOWN: u32 = 40;
+
+        match key {
+            KEY_SPACE => self.paused = !self.paused,
+            KEY_RIGHT => {
+                let _ = self.step();
+            }
+            KEY_UP => {
+                self.speed = SPEEDS
+                    .iter()
+                    .copied()
+                    .find(|&speed| speed > self.speed)
+                    .unwrap_or(self.speed)
+            }
+            KEY_DOWN => {
+                self.speed = SPEEDS
+                    .iter()
+                    .rev()
+                    .copied()
+                    .find(|&speed| speed < self.speed)
+                    .unwrap_or(self.speed)
+            }
+            _ => return,
+        }
+        self.draw_hud();
+    }
+
+    fn on_animation_frame(&mut self, timestamp: f64) {
+        let dt = timestamp - self.last_timestamp.unwrap_or(timestamp);
+        self.last_timestamp = Some(timestamp);
+        if self.paused || self.playback.is_finished() {
+            return;
+        }
+        self.pending_ms += dt.min(MAX_FRAME_MS) * self.speed;
+
+        erase_projectiles(self.playback.game(), |player| self.look(player));
+        let round = self.playback.round();
+        loop {
+            let wait = match self.playback.game().phase() {
+                Phase::Aiming | Phase::Over => TURN_PAUSE_MS,
+                Phase::InFlight | Phase::Resolving | Phase::TurnTransition => TICK_MS,
+            };
+            if self.pending_ms < wait {
+                break;
+            }
+            self.pending_ms -= wait;
+            match self.playback.step() {
+                Ok(true) if self.playback.round() == round => (),
+                Ok(_) => break,
+                Err(error) => {
+                    self.fail(&error.to_string());
+                    break;
+                }
+            }
+        }
+        if self.playback.round() != round {
+            self.restart_drawing();
+            return;
+        }
+        self.handle_events();
+        draw_projectiles(self.playback.game());
+    }
+
+    fn step(&mut self) -> Result<(), JsValue> {
+        self.paused = true;
+        let round = self.playback.round();
+        erase_projectiles(self.playback.game(), |player| self.look(player));
+        if let Err(error) = self.playback.next_turn() {
+            self.fail(&error.to_string());
+            return Err(JsValue::from(error.to_string()));
+        }
+        if self.playback.round() != round {
+            self.restart_drawing();
+        } else {
+            self.handle_events();
+            self.draw_hud();
+        }
+        Ok(())
+    }
+
+    fn handle_events(&mut self) {
+        for event in self.playback.drain_events() {
+            let game = self.playback.game();
+            match event {
+                Event::ShotFired { player, action } => {
+                    self.angles[player.index()] = action.angle;
+                    redraw_tanks(game, |player| self.look(player));
+                }
+                Event::Impact(impact) => self.draw_columns(impact.min_index, impact.max_index),
+                Event::Landslide {
+                    min_index,
+                    max_index,
+                } => self.draw_columns(min_index, max_index),
+                Event::Settled { .. } => redraw_tanks(game, |player| self.look(player)),
+                Event::TurnStarted { .. } => {
+                    redraw_tanks(game, |player| self.look(player));
+                    self.draw_hud();
+                }
+                Event::MatchOver(_) => self.draw_result(),
+                Event::TurnEnded { .. } | Event::ShotLost { .. } => (),
+            }
+        }
+    }
+
+    // after a seek or a new round, when nothing on the canvas can be trusted
+    fn restart_drawing(&mut self) {
+        self.playback.drain_events();
+        self.pending_ms = 0.0;
+        // the turrets point wherever each player last fired in the round
+        for angle in self.angles.iter_mut() {
+            *angle = 90.0;
+        }
+        let round = &self.playback.replay().rounds[self.playback.round()];
+        for made in round.iter().take(self.playback.turn()) {
+            if let Move::Fire { player, action } = made {
+                self.angles[player.index()] = action.angle;
+            }
+        }
+        self.draw();
+    }
+
+    fn fail(&mut self, message: &str) {
+        web_sys::console::log_1(&format!("warbots: replay stopped: {}", message).into());
+        self.paused = true;
+    }
+
+    fn look(&self, player: PlayerId) -> Look {
+        Look::standard(player, self.angles[player.index()])
+    }
+
+    fn draw(&self) {
+        let game = self.playback.game();
+        draw_terrain(game.terrain(), 0, game.terrain().width(), |player| {
+            self.look(player)
+        });
+        draw_projectiles(game);
+        self.draw_hud();
+        if game.is_over() {
+            self.draw_result();
+        }
+    }
+
+    fn draw_columns(&self, min_index: usize, max_index: usize) {
+        let terrain = self.playback.game().terrain();
+        draw_terrain(terrain, min_index, max_index, |player| self.look(player));
+        if min_index < HUD_WIDTH {
+            self.draw_hud();
+        }
+    }
+
+    // where the playback is, how fast it is going, and the keys
+    fn draw_hud(&self) {
+        let game = self.playback.game();
+        // the sky behind the text is wiped by redrawing the terrain under it
+        draw_terrain(game.terrain(), 0, HUD_WIDTH, |player| self.look(player));
+        let context = canvas_context();
+        context.set_fill_style_str("#FFFFFF");
+        context.set_font("16px monospace");
+        context.set_text_align("left");
+        let round = self.playback.round();
+        let status = if self.paused { "  paused" } else { "" };
+        let _ = context.fill_text(
+            &format!(
+                "Replay: round {} of {}, turn {} of {}, {}  {}x{}",
+                round + 1,
+                self.playback.replay().rounds.len(),
+                (self.playback.turn() + 1).min(self.playback.turns(round)),
+                self.playback.turns(round),
+                player_name(game.active_player()),
+                self.speed,
+                status
+            ),
+            10.0,
+            20.0,
+        );
+        context.set_font("12px monospace");
+        let _ = context.fill_text(
+            "Space pause  Right next turn  Up/Down speed  Esc close",
+            10.0,
+            38.0,
+        );
+    }
+
+    fn draw_result(&self) {
+        let game = self.playback.game();
+        let result = match game.result() {
+            Some(result) => result,
+            None => return,
+        };
+        let message = match result.winner() {
+            Some(player) => format!("{} wins the round!", player_name(player)),
+            None => "Draw!".to_string(),
+        };
+        let context = canvas_context();
+        context.set_fill_style_str("#FFFFFF");
+        context.set_font("24px monospace");
+        context.set_text_align("center");
+        let _ = context.fill_text(&message, game.config().width() / 2.0, 70.0);
+    }
+}
@@ -0,0 +1,120 @@
+//! Tests for the built-in computer opponent.
+
+mod common;
+
+use common::missile;
+use warbots::sim::{
+    match_rng, play_bot_turn, Action, Bot, ComputerBot, Config, Difficulty, Game, Observation,
+    PlayerId, ShotResult, ShotUpdate, Stream,
+};
+
+const MATCHES: u64 = 20;
+const SHOTS_PER_MATCH: usize = 4;
+
+// Shots landing within this many px of the enemy's center count as hits: the
+// enemy is inside the blast.
+const HIT_DISTANCE: f64 = 30.0;
+
+/// Fraction of shots that land on a target that never shoots back.
+fn hit_rate(difficulty: Difficulty) -> f64 {
+    let mut hits = 0;
+    let mut shots = 0;
+    for seed in 0..MATCHES {
+        let mut game = Game::new(Config::new(), seed);
+        let mut bot = ComputerBot::new(difficulty, match_rng(seed, Stream::Player(0)));
+        let enemy = game.terrain().tank(PlayerId(1));
+        let enemy_x = enemy.location().x() + enemy.width() / 2.0;
+        for _ in 0..SHOTS_PER_MATCH {
+            shots += 1;
+            if let ShotUpdate::Impact(impact) = play_bot_turn(&mut game, &mut bot).unwrap() {
+                if (impact.point.x() - enemy_x).abs() <= HIT_DISTANCE {
+                    hits += 1;
+                }
+            }
+            if game.is_over() {
+                break;
+            }
+            // skip the target's turn
+            game.skip_turn(PlayerId(1)).unwrap();
+            game.run_to_next_turn();
+        }
+    }
+    hits as f64 / shots as f64
+}
+
+#[test]
+fn harder_bots_hit_more_often() {
+    let easy = hit_rate(Difficulty::Easy);
+    let medium = hit_rate(Difficulty::Medium);
+    let hard = hit_rate(Difficulty::Hard);
+    assert!(easy < medium, "easy {} vs medium {}", easy, medium);
+    assert!(medium < hard, "medium {} vs hard {}", medium, hard);
+}
+
+#[test]
+fn computer_bots_are_reproducible_from_their_seed() {
+    let decide = || {
+        let game = Game::new(Config::new(), 5);
+        let mut bot = ComputerBot::new(Difficulty::Easy, match_rng(5, Stream::Player(1)));
+        bot.decide(&game.observation(PlayerId(1)))
+    };
+    assert_eq!(decide(), decide());
+}
+
+#[test]
+fn computer_bots_pass_with_nobody_to_aim_at() {
+    let game = Game::new(Config::new(), 5);
+    let mut observation = game.observation(PlayerId(1));
+    observation.enemies.clear();
+    let mut bot = ComputerBot::new(Difficulty::Easy, match_rng(5, Stream::Player(1)));
+    assert_eq!(bot.decide(&observation), None);
+}
+
+// The Hard bot's second shot, after deciding its first on `first` and then
+// seeing `then`.
+fn second_shot(first: &Observation, then: &Observation) -> Action {
+    let mut bot = ComputerBot::new(Difficulty::Hard, match_rng(5, Stream::Player(1)));
+    bot.decide(first);
+    bot.decide(then).unwrap()
+}
+
+#[test]
+fn bots_learn_only_from_their_own_shot_of_this_round() {
+    // the bot plays second
+    let mut game = Game::new(Config::new(), 5);
+    game.fire(PlayerId(0), missile(300.0, 60.0)).unwrap();
+    game.run_to_next_turn();
+    let view = game.observation(PlayerId(1));
+    let enemy = view.nearest_enemy().unwrap();
+    let enemy_x = enemy.location().x() + enemy.width() / 2.0;
+    let miss = |action: &Action| (view.predict_impact(action).unwrap().x() - enemy_x).abs();
+
+    let aimed = ComputerBot::new(Difficulty::Hard, match_rng(5, Stream::Player(1)))
+        .decide(&view)
+        .unwrap();
+    let unchanged = second_shot(&view, &view);
+
+    // it sees its shot land where its misjudged power really sends it
+    let mut own = view.clone();
+    own.shots.push(ShotResult {
+        player: PlayerId(1),
+        action: aimed,
+        impact: view.predict_impact(&aimed),
+    });
+    let corrected = second_shot(&view, &own);
+    assert!(
+        miss(&corrected) < miss(&aimed),
+        "corrected {} vs first {}",
+        miss(&corrected),
+        miss(&aimed)
+    );
+
+    // the same shot after other shots than the bot saw is from another round
+    let mut other_round = own.clone();
+    other_round.shots[0].action = missile(250.0, 60.0);
+    assert_eq!(second_shot(&view, &other_round), unchanged);
+
+    let mut other_player = own.clone();
+    other_player.shots[1].player = PlayerId(0);
+    assert_eq!(second_shot(&view, &other_player), unchanged);
+}
@@ -0,0 +1,129 @@
+//! Tests for playing matches between bots, and the `warbots-sim` binary.
+
+use std::process::Command;
+use warbots::sim::{
+    match_rng, play_match, Bot, BotRegistry, Map, MatchError, MatchSettings, Playback, Replay,
+    Stream,
+};
+
+fn bots(seed: u64, names: &[&str]) -> Vec<Box<dyn Bot>> {
+    let registry = BotRegistry::new();
+    names
+        .iter()
+        .enumerate()
+        .map(|(seat, name)| {
+            registry
+                .create(name, match_rng(seed, Stream::Player(seat as u8)))
+                .unwrap()
+        })
+        .collect()
+}
+
+#[test]
+fn matches_between_bots_are_reproducible() {
+    let settings = MatchSettings {
+        rounds: 3,
+        ..MatchSettings::default()
+    };
+    let report = play_match(&settings, 12, None, &mut bots(12, &["easy", "hard"])).unwrap();
+    let again = play_match(&settings, 12, None, &mut bots(12, &["easy", "hard"])).unwrap();
+    assert_eq!(report.rounds, again.rounds);
+    assert_eq!(report.turns, again.turns);
+    assert_eq!(report.replay, again.replay);
+
+    assert_eq!(report.rounds.len(), 3);
+    let won = report
+        .rounds
+        .iter()
+        .filter(|winner| winner.is_some())
+        .count();
+    assert_eq!(report.wins.iter().sum::<u32>() as usize, won);
+    assert!(report.turns > 0);
+}
+
+#[test]
+fn the_report_replays_the_match() {
+    let map = Map::from_library("Canyon").unwrap();
+    let settings = MatchSettings {
+        width: map.width,
+        height: map.height,
+        rounds: 2,
+        ..MatchSettings::default()
+    };
+    let report = play_match(
+        &settings,
+        3,
+        Some(&map),
+        &mut bots(3, &["medium", "lobber"]),
+    )
+    .unwrap();
+
+    let mut playback = Playback::new(report.replay.clone()).unwrap();
+    while playback.step().unwrap() {}
+    assert!(playback.is_finished());
+    assert_eq!(
+        playback.game().result().and_then(|result| result.winner()),
+        report.rounds[1]
+    );
+}
+
+#[test]
+fn every_player_needs_a_bot() {
+    let settings = MatchSettings {
+        players: 3,
+        ..MatchSettings::default()
+    };
+    let error = play_match(&settings, 0, None, &mut bots(0, &["easy", "easy"])).unwrap_err();
+    assert_eq!(
+        error,
+        MatchError::Seats {
+            players: 3,
+            bots: 2
+        }
+    );
+    assert_eq!(
+        error.to_string(),
+        "the settings are for 3 players, but there are 2 bots"
+    );
+}
+
+#[test]
+fn the_binary_reports_matches_and_writes_replays() {
+    let dir = std::env::temp_dir().join(format!("warbots-sim-{}", std::process::id()));
+    let output = Command::new(env!("CARGO_BIN_EXE_warbots-sim"))
+        .args(["--seeds", "4,9", "--replays"])
+        .arg(&dir)
+        .args(["easy", "lobber"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("match 1 (seed 4): "), "{}", stdout);
+    assert!(stdout.contains("match 2 (seed 9): "), "{}", stdout);
+    assert!(stdout.contains("easy (1): "), "{}", stdout);
+    assert!(stdout.contains("draws: "), "{}", stdout);
+    for seed in &[4, 9] {
+        let replay = std::fs::read_to_string(dir.join(format!("match-{}.json", seed))).unwrap();
+        Replay::from_json(&replay).unwrap();
+    }
+    std::fs::remove_dir_all(&dir).unwrap();
+
+    let unknown = Command::new(env!("CARGO_BIN_EXE_warbots-sim"))
+        .args(["easy", "sniper"])
+        .output()
+        .unwrap();
+    assert_eq!(unknown.status.code(), Some(2));
+
+    // a second match on a seed would overwrite the first's replay
+    let twice = Command::new(env!("CARGO_BIN_EXE_warbots-sim"))
+        .args(["--seeds", "4,9,4", "easy", "lobber"])
+        .output()
+        .unwrap();
+    assert_eq!(twice.status.code(), Some(2));
+    let stderr = String::from_utf8(twice.stderr).unwrap();
+    assert!(
+        stderr.contains("--seeds lists 4 more than once"),
+        "{}",
+        stderr
+    );
+}
@@ -0,0 +1,99 @@
+//! Tests for driving tanks with bots.
+
+use warbots::sim::{
+    match_rng, play_bot_turn, Action, Bot, BotRegistry, Config, Controller, Game, MatchRng,
+    Observation, PlayerId, ShotUpdate, Stream, Weapon,
+};
+
+fn rng() -> MatchRng {
+    match_rng(0, Stream::Player(0))
+}
+
+/// Records what it was shown and fires a fixed shot.
+struct Recorder {
+    seen: Vec<(PlayerId, usize)>,
+}
+
+impl Bot for Recorder {
+    fn name(&self) -> &str {
+        "recorder"
+    }
+
+    fn decide(&mut self, observation: &Observation) -> Option<Action> {
+        self.seen
+            .push((observation.player, observation.shots.len()));
+        Some(Action {
+            power: 250.0,
+            angle: 120.0,
+            weapon: Weapon::Missile,
+        })
+    }
+}
+
+#[test]
+fn bots_see_their_own_side_and_previous_shots() {
+    let mut game = Game::new(Config::new(), 3);
+    let mut bot = Recorder { seen: Vec::new() };
+    for _ in 0..4 {
+        play_bot_turn(&mut game, &mut bot).unwrap();
+    }
+    assert_eq!(
+        bot.seen,
+        vec![
+            (PlayerId(0), 0),
+            (PlayerId(1), 1),
+            (PlayerId(0), 2),
+            (PlayerId(1), 3)
+        ]
+    );
+
+    let observation = game.observation(PlayerId(1));
+    // tanks start out turned towards each other
+    let towards = observation.enemies[0].location().x() - observation.own_tank.location().x();
+    assert_eq!(observation.own_tank.facing(), towards.signum());
+    assert_eq!(observation.heights.len(), game.terrain().width());
+    let first = &observation.shots[0];
+    assert_eq!(first.player, PlayerId(0));
+    assert_eq!(first.action.power, 250.0);
+}
+
+#[test]
+fn out_of_range_actions_are_clamped() {
+    let mut game = Game::new(Config::new(), 3);
+    game.fire(
+        PlayerId(0),
+        Action {
+            power: f64::NAN,
+            angle: 720.0,
+            weapon: Weapon::Missile,
+        },
+    )
+    .unwrap();
+    let action = game.shots()[0].action;
+    assert_eq!(action.power, 0.0);
+    assert_eq!(action.angle, 180.0);
+}
+
+#[test]
+fn registry_resolves_humans_and_bots_by_name() {
+    let mut registry = BotRegistry::new();
+    registry.register("recorder", |_| Box::new(Recorder { seen: Vec::new() }));
+
+    assert!(registry.controller("human", rng()).unwrap().is_human());
+    match registry.controller("recorder", rng()) {
+        Some(Controller::Bot(bot)) => assert_eq!(bot.name(), "recorder"),
+        other => panic!("expected the recorder bot, got {:?}", other),
+    }
+    assert!(registry.controller("nobody", rng()).is_none());
+}
+
+#[test]
+fn lobber_lands_on_the_field() {
+    let mut game = Game::new(Config::new(), 1);
+    let own_x = game.active_tank().location().x();
+    let mut lobber = BotRegistry::new().create("lobber", rng()).unwrap();
+    match play_bot_turn(&mut game, lobber.as_mut()).unwrap() {
+        ShotUpdate::Impact(impact) => assert!((impact.point.x() - own_x).abs() > 100.0),
+        other => panic!("expected an impact, got {:?}", other),
+    }
+}
@@ -0,0 +1,312 @@
+//! Tests for watching networked matches: the relay's broadcasts and
+//! spectators joining from a snapshot.
+
+mod common;
+
+use common::{join, to};
+use warbots::sim::{
+    match_rng, play_match, round_seed, Bot, BotRegistry, Broadcast, ClientMessage, ConnectionId,
+    Event, Game, Lobby, MatchSettings, Move, PlayerId, ServerMessage, Spectator, Stream, Turn,
+    PROTOCOL_VERSION,
+};
+
+fn watch(room: &str) -> ClientMessage {
+    ClientMessage::Watch {
+        version: PROTOCOL_VERSION,
+        room: room.to_string(),
+    }
+}
+
+fn error(message: &str) -> ServerMessage {
+    ServerMessage::Error {
+        message: message.to_string(),
+    }
+}
+
+fn two_rounds() -> MatchSettings {
+    MatchSettings {
+        rounds: 2,
+        ..MatchSettings::default()
+    }
+}
+
+// every move of the match between an easy and a hard bot, as the relay
+// would be sent them
+fn turns(settings: &MatchSettings, seed: u64) -> Vec<Turn> {
+    let mut bots: Vec<Box<dyn Bot>> = ["easy", "hard"]
+        .iter()
+        .enumerate()
+        .map(|(seat, name)| {
+            BotRegistry::new()
+                .create(name, match_rng(seed, Stream::Player(seat as u8)))
+                .unwrap()
+        })
+        .collect();
+    let report = play_match(settings, seed, None, &mut bots).unwrap();
+    let config = settings.config().unwrap();
+    let mut turns = Vec::new();
+    for (round, moves) in report.replay.rounds.iter().enumerate() {
+        let mut game = Game::new(config.clone(), round_seed(seed, round as u32));
+        for &made in moves {
+            turns.push(Turn {
+                checksum: game.checksum(),
+                made,
+            });
+            match made {
+                Move::Fire { player, action } => game.fire(player, action).unwrap(),
+                Move::Skip { player } => game.skip_turn(player).unwrap(),
+            }
+            game.run_to_next_turn();
+        }
+    }
+    turns
+}
+
+// a browser watching a match, from the snapshot it was handed
+struct Viewer {
+    spectator: Spectator,
+    seed: u64,
+    round: u32,
+    game: Game,
+    events: Vec<Event>,
+}
+
+impl Viewer {
+    fn new(watching: &ServerMessage) -> Viewer {
+        let (turns, snapshot) = match watching {
+            ServerMessage::Watching {
+                turns, snapshot, ..
+            } => (*turns, snapshot),
+            other => panic!("{:?}", other),
+        };
+        Viewer {
+            spectator: Spectator::new(turns),
+            seed: snapshot.replay.seed,
+            round: snapshot.replay.rounds.len() as u32,
+            game: snapshot.game().unwrap(),
+            events: Vec::new(),
+        }
+    }
+
+    // plays every move it has been sent
+    fn receive(&mut self, message: &ServerMessage) {
+        self.spectator.receive(message);
+        loop {
+            if self.game.is_over() && self.spectator.has_pending() {
+                self.round += 1;
+                let config = self.game.config().clone();
+                self.game = Game::new(config, round_seed(self.seed, self.round));
+            }
+            if self.spectator.apply(&mut self.game).unwrap().is_none() {
+                break;
+            }
+            self.game.run_to_next_turn();
+            self.events.extend(self.game.drain_events());
+        }
+    }
+}
+
+#[test]
+fn spectators_join_mid_match_from_a_snapshot() {
+    let mut lobby = Lobby::new(8);
+    let first = lobby.handle(0, join("den", Some(two_rounds())));
+    let seed = match &to(&first, 0)[0] {
+        ServerMessage::Joined { seed, .. } => *seed,
+        other => panic!("{:?}", other),
+    };
+    // one spectator is there from before the match starts
+    let early = lobby.handle(5, watch("den"));
+    let mut viewers = vec![(5, Viewer::new(&to(&early, 5)[0]))];
+    assert_eq!(
+        viewers[0].1.game.checksum(),
+        Game::new(two_rounds().config().unwrap(), round_seed(seed, 0)).checksum()
+    );
+    let second = lobby.handle(1, join("den", None));
+    assert_eq!(
+        to(&second, 5),
+        vec![
+            ServerMessage::Presence {
+                seat: 1,
+                connected: true
+            },
+            ServerMessage::Started
+        ]
+    );
+
+    let turns = turns(&two_rounds(), seed);
+    // the other arrives in the second round, a move into it
+    let mut broadcast = Broadcast::new(seed, two_rounds(), None, vec!["remote".into(); 2]).unwrap();
+    let late_turn = (0..turns.len())
+        .find(|&turn| {
+            broadcast.play(&turns[turn]).unwrap();
+            broadcast.snapshot().replay.rounds.len() == 1 && broadcast.game().moves().len() == 1
+        })
+        .unwrap()
+        + 1;
+
+    for (turn, &relayed) in turns.iter().enumerate() {
+        if turn == late_turn {
+            let late = lobby.handle(6, watch("den"));
+            let viewer = Viewer::new(&to(&late, 6)[0]);
+            match &to(&late, 6)[0] {
+                ServerMessage::Watching {
+                    room,
+                    turns,
+                    snapshot,
+                } => {
+                    assert_eq!(room, "den");
+                    assert_eq!(*turns, late_turn);
+                    assert_eq!(snapshot.replay.rounds.len(), 1);
+                    assert_eq!(snapshot.round.moves.len(), 1);
+                    assert_eq!(**snapshot, broadcast.snapshot());
+                }
+                other => panic!("{:?}", other),
+            }
+            assert_eq!(viewer.game.checksum(), broadcast.game().checksum());
+            viewers.push((6, viewer));
+        }
+        let mover = relayed.made.player().0 as ConnectionId;
+        let sends = lobby.handle(
+            mover,
+            ClientMessage::Move {
+                turn,
+                checksum: relayed.checksum,
+                made: relayed.made,
+            },
+        );
+        assert!(to(&sends, mover).is_empty(), "{:?}", sends);
+        for (connection, viewer) in viewers.iter_mut() {
+            for message in to(&sends, *connection) {
+                viewer.receive(&message);
+            }
+        }
+    }
+
+    // both see the match to its end, and every event of it after they
+    // started watching
+    let (_, early) = &viewers[0];
+    let (_, late) = &viewers[1];
+    assert!(early.game.is_over() && early.round == 1);
+    assert_eq!(early.game.checksum(), late.game.checksum());
+    assert_eq!(early.game.moves(), late.game.moves());
+    let count =
+        |viewer: &Viewer, of: fn(&Event) -> bool| viewer.events.iter().filter(|e| of(e)).count();
+    let shots = turns
+        .iter()
+        .filter(|turn| matches!(turn.made, Move::Fire { .. }))
+        .count();
+    assert_eq!(
+        count(early, |e| matches!(e, Event::ShotFired { .. })),
+        shots
+    );
+    assert_eq!(count(early, |e| matches!(e, Event::MatchOver(_))), 2);
+    assert_eq!(count(late, |e| matches!(e, Event::MatchOver(_))), 1);
+    assert!(count(early, |e| matches!(e, Event::Impact(_))) > 0);
+    assert_eq!(
+        count(early, |e| matches!(e, Event::TurnStarted { .. })),
+        turns.len()
+    );
+}
+
+#[test]
+fn spectators_have_no_seat() {
+    let mut lobby = Lobby::new(2);
+    assert_eq!(
+        lobby.handle(5, watch("den")),
+        vec![(5, error("there is no room den"))]
+    );
+    lobby.handle(0, join("den", None));
+    assert!(matches!(
+        to(&lobby.handle(5, watch("den")), 5).as_slice(),
+        [ServerMessage::Watching { turns: 0, .. }]
+    ));
+    assert_eq!(
+        lobby.handle(5, join("den", None)),
+        vec![(5, error("already in room den"))]
+    );
+    assert_eq!(
+        lobby.handle(5, watch("den")),
+        vec![(5, error("already in room den"))]
+    );
+    let made = Move::Skip {
+        player: PlayerId(0),
+    };
+    let moved = ClientMessage::Move {
+        turn: 0,
+        checksum: 0,
+        made,
+    };
+    assert_eq!(
+        lobby.handle(5, moved),
+        vec![(5, error("join a room before moving"))]
+    );
+
+    // leaving says nothing, and nothing more reaches them
+    assert!(lobby.disconnect(5).is_empty());
+    assert!(to(&lobby.handle(1, join("den", None)), 5).is_empty());
+
+    // when the players leave, the room closes under its spectators
+    lobby.handle(6, watch("den"));
+    lobby.disconnect(0);
+    assert_eq!(lobby.disconnect(1), vec![(6, error("room den has closed"))]);
+    assert!(lobby.rooms().is_empty());
+    assert!(lobby.disconnect(6).is_empty());
+}
+
+#[test]
+fn rooms_whose_moves_dont_fit_their_match_stop_being_watchable() {
+    let mut lobby = Lobby::new(3);
+    lobby.handle(0, join("den", None));
+    lobby.handle(1, join("den", None));
+    lobby.handle(5, watch("den"));
+    let skip = ClientMessage::Move {
+        turn: 0,
+        checksum: 1,
+        made: Move::Skip {
+            player: PlayerId(0),
+        },
+    };
+    let sends = lobby.handle(0, skip);
+    // the players carry on, as their copies are the ones that count
+    assert!(matches!(
+        to(&sends, 1).as_slice(),
+        [ServerMessage::Moves { from: 0, .. }]
+    ));
+    match to(&sends, 5).as_slice() {
+        [ServerMessage::Error { message }] => {
+            assert!(
+                message.starts_with("can't follow room den any more: out of sync at turn 1"),
+                "{}",
+                message
+            )
+        }
+        other => panic!("{:?}", other),
+    }
+    assert_eq!(
+        lobby.handle(6, watch("den")),
+        vec![(6, error("room den can't be watched"))]
+    );
+}
+
+#[test]
+fn snapshots_travel_as_json() {
+    let broadcast =
+        Broadcast::new(4, MatchSettings::default(), None, vec!["remote".into(); 2]).unwrap();
+    let watching = ServerMessage::Watching {
+        room: "den".to_string(),
+        turns: 0,
+        snapshot: Box::new(broadcast.snapshot()),
+    };
+    let json = watching.to_json();
+    assert!(
+        json.starts_with(r#"{"type":"watching","room":"den","turns":0,"#),
+        "{}",
+        json
+    );
+    assert_eq!(ServerMessage::from_json(&json), Ok(watching));
+    let json = format!(
+        r#"{{"type":"watch","version":{},"room":"den"}}"#,
+        PROTOCOL_VERSION
+    );
+    assert_eq!(ClientMessage::from_json(&json), Ok(watch("den")));
+}
@@ -0,0 +1,54 @@
+//! Fixtures shared by the integration tests. Each test crate uses only some
+//! of them.
+#![allow(dead_code)]
+
+use warbots::sim::{
+    Action, ClientMessage, Config, ConnectionId, MatchSettings, ServerMessage, Weapon,
+    PROTOCOL_VERSION,
+};
+
+/// A `width` by `height` field without wind, so that shots land where the
+/// tests expect.
+pub fn calm_field(width: u32, height: u32) -> Config {
+    MatchSettings {
+        width,
+        height,
+        max_wind: 0.0,
+        ..MatchSettings::default()
+    }
+    .config()
+    .unwrap()
+}
+
+/// The standard field without wind.
+pub fn calm() -> Config {
+    let settings = MatchSettings::default();
+    calm_field(settings.width, settings.height)
+}
+
+pub fn missile(power: f64, angle: f64) -> Action {
+    Action {
+        power,
+        angle,
+        weapon: Weapon::Missile,
+    }
+}
+
+/// Asks the relay for a seat in `room`, opening it with `settings` if it
+/// isn't open yet.
+pub fn join(room: &str, settings: Option<MatchSettings>) -> ClientMessage {
+    ClientMessage::Join {
+        version: PROTOCOL_VERSION,
+        room: room.to_string(),
+        settings,
+    }
+}
+
+/// The messages in `sends` for `connection`, in order.
+pub fn to(sends: &[(ConnectionId, ServerMessage)], connection: ConnectionId) -> Vec<ServerMessage> {
+    sends
+        .iter()
+        .filter(|(to, _)| *to == connection)
+        .map(|(_, message)| message.clone())
+        .collect()
+}
@@ -0,0 +1,197 @@
+//! Tests for the keymaps, drags and aims of humans playing at one computer.
+
+use warbots::sim::{
+    Aim, Command, Config, Drag, Game, Inventory, Keymap, KeymapError, PlayerId, Point, Weapon,
+};
+
+#[test]
+fn standard_keymaps_bind_every_command() {
+    for keymap in &[Keymap::arrows(), Keymap::letters()] {
+        for &command in Command::ALL.iter() {
+            assert!(!keymap.keys(command).is_empty(), "{:?}", command);
+        }
+    }
+    assert_eq!(Keymap::default(), Keymap::arrows());
+    assert_eq!(Keymap::arrows().keys(Command::Fire), vec![" "]);
+}
+
+#[test]
+fn keymaps_round_trip_through_json() {
+    let keymap = Keymap::letters();
+    let json = keymap.to_json();
+    assert!(json.contains(r#""a":"angle-down""#), "{}", json);
+    assert_eq!(Keymap::from_json(&json), Ok(keymap));
+}
+
+#[test]
+fn keys_can_share_a_command() {
+    let keymap = Keymap::from_json(
+        r#"{"j": "angle-down", "l": "angle-up", "i": "power-up", "k": "power-down",
+            "u": "next-weapon", "Enter": "fire", "o": "fire"}"#,
+    )
+    .unwrap();
+    assert_eq!(keymap.keys(Command::Fire), vec!["Enter", "o"]);
+    assert_eq!(keymap.command("Enter"), Some(Command::Fire));
+    assert_eq!(keymap.command("o"), Some(Command::Fire));
+}
+
+#[test]
+fn keymaps_must_bind_every_command() {
+    let missing_fire = r#"{"a": "angle-down", "d": "angle-up", "w": "power-up",
+                           "s": "power-down", "q": "next-weapon"}"#;
+    assert_eq!(
+        Keymap::from_json(missing_fire),
+        Err(KeymapError::Unbound(Command::Fire))
+    );
+    assert_eq!(
+        Keymap::new(&[("a", Command::AngleDown)]),
+        Err(KeymapError::Unbound(Command::AngleUp))
+    );
+    assert_eq!(
+        KeymapError::Unbound(Command::NextWeapon).to_string(),
+        "the keymap has no key for next-weapon"
+    );
+}
+
+#[test]
+fn keymaps_reject_unknown_commands() {
+    let error = Keymap::from_json(r#"{"x": "jump"}"#).unwrap_err();
+    assert!(matches!(error, KeymapError::Parse(_)), "{:?}", error);
+    assert!(Keymap::from_json("[]").is_err());
+}
+
+#[test]
+fn letters_work_with_shift() {
+    let keymap = Keymap::letters();
+    assert_eq!(keymap.command("f"), Some(Command::Fire));
+    assert_eq!(keymap.command("F"), Some(Command::Fire));
+    assert_eq!(keymap.command("ArrowUp"), None);
+    assert_eq!(Keymap::arrows().command("arrowup"), None);
+}
+
+#[test]
+fn aims_stay_within_the_limits() {
+    let config = Config::default();
+    let inventory = Inventory::standard();
+    let mut aim = Aim {
+        power: config.max_power() as f64,
+        angle: config.min_angle() as f64,
+        weapon: Weapon::Missile,
+    };
+    aim.apply(Command::PowerUp, &config, &inventory);
+    aim.apply(Command::AngleDown, &config, &inventory);
+    assert_eq!(aim.power, config.max_power() as f64);
+    assert_eq!(aim.angle, config.min_angle() as f64);
+
+    aim.apply(Command::PowerDown, &config, &inventory);
+    aim.apply(Command::AngleUp, &config, &inventory);
+    aim.apply(Command::Fire, &config, &inventory);
+    assert_eq!(aim.power, config.max_power() as f64 - 1.0);
+    assert_eq!(aim.angle, config.min_angle() as f64 + 1.0);
+
+    let action = aim.action();
+    assert_eq!(action.power, aim.power);
+    assert_eq!(action.angle, aim.angle);
+    assert_eq!(action.weapon, Weapon::Missile);
+}
+
+#[test]
+fn next_weapon_skips_what_has_run_out() {
+    let config = Config::default();
+    let inventory = Inventory::new(&[(Weapon::Digger, 1), (Weapon::Napalm, 2)]);
+    let mut aim = Aim::default();
+    let mut picked = Vec::new();
+    for _ in 0..4 {
+        aim.apply(Command::NextWeapon, &config, &inventory);
+        picked.push(aim.weapon);
+    }
+    assert_eq!(
+        picked,
+        vec![
+            Weapon::Digger,
+            Weapon::Napalm,
+            Weapon::Missile,
+            Weapon::Digger
+        ]
+    );
+}
+
+#[test]
+fn turrets_point_the_way_they_are_dragged() {
+    let game = Game::new(Config::default(), 3);
+    for player in [PlayerId(0), PlayerId(1)].iter() {
+        let tank = game.terrain().tank(*player);
+        let base = tank.turret_base();
+        let drag = Drag::new(Point::new(400.0, 300.0));
+        // up and away from the tank, then straight up and over its back
+        for &(dx, dy) in &[
+            (-tank.facing() * 30.0, -40.0),
+            (0.0, -50.0),
+            (tank.facing() * 50.0, -20.0),
+        ] {
+            let mut aim = Aim::default();
+            let to = Point::new(400.0 + dx, 300.0 + dy);
+            drag.apply(to, tank.facing(), game.config(), &mut aim);
+            let tip = tank.turret_location(aim.angle.to_radians());
+            let (tx, ty) = (tip.x() - base.x(), tip.y() - base.y());
+            let along = (tx * dx + ty * dy) / (tx.hypot(ty) * dx.hypot(dy));
+            assert!(along > 0.999, "{:?} {} {}", player, dx, dy);
+        }
+    }
+}
+
+#[test]
+fn dragging_further_fires_harder() {
+    let config = Config::default();
+    let drag = Drag::new(Point::new(100.0, 100.0));
+    let mut aim = Aim::default();
+    drag.apply(
+        Point::new(100.0, 100.0 - Drag::FULL_POWER / 2.0),
+        1.0,
+        &config,
+        &mut aim,
+    );
+    assert_eq!(aim.power, (config.max_power() / 2) as f64);
+    assert_eq!(aim.angle, 90.0);
+    drag.apply(
+        Point::new(100.0, 100.0 - Drag::FULL_POWER * 3.0),
+        1.0,
+        &config,
+        &mut aim,
+    );
+    assert_eq!(aim.power, config.max_power() as f64);
+}
+
+#[test]
+fn drags_stay_within_the_limits() {
+    let config = Config::default();
+    let drag = Drag::new(Point::new(100.0, 100.0));
+    let mut aim = Aim::default();
+    // down and forwards, as the turret faces at its lowest angle, and down
+    // and back
+    drag.apply(Point::new(50.0, 120.0), 1.0, &config, &mut aim);
+    assert_eq!(aim.angle, config.min_angle() as f64);
+    drag.apply(Point::new(150.0, 120.0), 1.0, &config, &mut aim);
+    assert_eq!(aim.angle, config.max_angle() as f64);
+}
+
+#[test]
+fn taps_dont_aim() {
+    let config = Config::default();
+    let drag = Drag::new(Point::new(100.0, 100.0));
+    let tap = Point::new(102.0, 101.0);
+    assert!(!drag.is_aiming(tap));
+    let mut aim = Aim {
+        weapon: Weapon::Napalm,
+        ..Aim::default()
+    };
+    drag.apply(tap, 1.0, &config, &mut aim);
+    assert_eq!(
+        aim,
+        Aim {
+            weapon: Weapon::Napalm,
+            ..Aim::default()
+        }
+    );
+    assert!(drag.is_aiming(Point::new(100.0, 100.0 - Drag::DEAD_ZONE - 1.0)));
+}
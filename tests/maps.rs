@@ -0,0 +1,227 @@
+//! Tests for saving battlefields as maps and playing on them.
+
+mod common;
+
+use common::{calm_field, missile};
+use warbots::sim::{
+    Column, DamageCause, Event, Game, Hazard, Map, MapEditor, MapError, PlayerId, Point,
+    ShotUpdate, MAP_VERSION,
+};
+
+/// Level ground 400px down across a 900x500 field, with no hazards.
+fn flat() -> Map {
+    Map {
+        version: MAP_VERSION,
+        name: "Flat".to_string(),
+        width: 900,
+        height: 500,
+        ground: vec![Column::Surface(400); 900],
+        color_hex: "#27FF00".to_string(),
+        sky_color_hex: "#000000".to_string(),
+        spawns: vec![100.0, 700.0],
+        hazards: Vec::new(),
+        sea_level: None,
+    }
+}
+
+#[test]
+fn a_battlefield_round_trips_through_a_map_file() {
+    let mut game = Game::new(calm_field(900, 500), 7);
+    // blow a crater so that the map isn't just the generated terrain
+    game.fire(PlayerId(0), missile(200.0, 90.0)).unwrap();
+    game.run_to_next_turn();
+
+    let map = Map::from_terrain("Crater", game.terrain());
+    assert_eq!(Map::from_json(&map.to_json()), Ok(map.clone()));
+
+    let replayed = Game::with_map(game.config().clone(), &map, 1).unwrap();
+    assert_eq!(replayed.terrain().mask(), game.terrain().mask());
+    assert_eq!(replayed.terrain().color_hex(), game.terrain().color_hex());
+    for (a, b) in replayed
+        .terrain()
+        .tanks()
+        .iter()
+        .zip(game.terrain().tanks())
+    {
+        assert_eq!(a.location(), b.location());
+    }
+}
+
+#[test]
+fn every_library_map_can_be_played() {
+    let library = Map::library();
+    assert!(library.len() >= 3);
+    for map in library.iter() {
+        let mut game = Game::with_map(calm_field(map.width, map.height), map, 3).unwrap();
+        assert_eq!(game.terrain().mask(), &map.mask(), "{}", map.name);
+        game.fire(PlayerId(0), missile(300.0, 60.0)).unwrap();
+        game.run_to_next_turn();
+        assert_eq!(game.active_player(), PlayerId(1), "{}", map.name);
+    }
+
+    // the bridge stands over open sky
+    let bridge = Map::from_library("Sky Bridge").unwrap();
+    let runs = bridge.mask().runs(450);
+    assert_eq!(runs.iter().filter(|&&(_, _, solid)| solid).count(), 2);
+}
+
+#[test]
+fn maps_that_cant_be_played_are_refused() {
+    let json = flat().to_json().replace("\"version\":1", "\"version\":2");
+    assert_eq!(Map::from_json(&json), Err(MapError::UnsupportedVersion(2)));
+
+    let mut map = flat();
+    map.ground.pop();
+    assert!(matches!(map.validate(), Err(MapError::Invalid(_))));
+
+    let mut map = flat();
+    map.ground[10] = Column::Runs(vec![[300, 350], [320, 500]]);
+    assert!(matches!(map.validate(), Err(MapError::Invalid(_))));
+
+    let mut map = flat();
+    map.spawns = vec![100.0, 700.0, 105.0];
+    assert_eq!(
+        map.validate(),
+        Err(MapError::Invalid(
+            "spawns at 100 and 105 are closer than a tank is wide".to_string()
+        ))
+    );
+
+    let mut map = bay();
+    map.sea_level = Some(390.0);
+    assert_eq!(
+        map.validate(),
+        Err(MapError::Invalid(
+            "spawn at 100 is under the sea".to_string()
+        ))
+    );
+
+    let error = Game::with_map(calm_field(1200, 500), &flat(), 1).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "map doesn't fit the match: the map is 900x500 but the match is 1200x500"
+    );
+}
+
+#[test]
+fn mines_go_off_when_a_blast_reaches_them() {
+    let mut map = flat();
+    map.hazards.push(Hazard::Mine {
+        x: 105.0,
+        y: 410.0,
+        radius: 40.0,
+        damage: 30.0,
+    });
+    let mut game = Game::with_map(calm_field(900, 500), &map, 1).unwrap();
+
+    // straight up comes back down on the shooter, right over the mine
+    game.fire(PlayerId(0), missile(200.0, 90.0)).unwrap();
+    let impact = match game.run_to_next_turn() {
+        Some(ShotUpdate::Impact(impact)) => impact,
+        other => panic!("expected an impact, got {:?}", other),
+    };
+    assert!(game.terrain().hazards().is_empty());
+    let blasts = impact
+        .damage
+        .iter()
+        .filter(|hit| hit.player == PlayerId(0) && hit.cause == DamageCause::Blast)
+        .count();
+    assert_eq!(blasts, 2);
+    // the mine's blast is wider than the missile's
+    assert!(impact.max_index - impact.min_index > 70);
+}
+
+/// `flat` with a bay flooded by the sea between the tanks.
+fn bay() -> Map {
+    let mut map = flat();
+    map.ground[250..650].fill(Column::Surface(480));
+    map.sea_level = Some(420.0);
+    map
+}
+
+#[test]
+fn shots_that_fall_into_the_sea_are_lost() {
+    // the power that lands in the middle of the bay when it is dry
+    let mut dry = bay();
+    dry.sea_level = None;
+    let dry = Game::with_map(calm_field(900, 500), &dry, 1).unwrap();
+    let observation = dry.observation(PlayerId(0));
+    let power = (50..200)
+        .map(|power| power as f64)
+        .find(|&power| {
+            observation
+                .predict_impact(&missile(power, 135.0))
+                .is_some_and(|point| (400.0..500.0).contains(&point.x()))
+        })
+        .unwrap();
+
+    let mut game = Game::with_map(calm_field(900, 500), &bay(), 1).unwrap();
+    let shot = missile(power, 135.0);
+    assert_eq!(game.observation(PlayerId(0)).predict_impact(&shot), None);
+    game.fire(PlayerId(0), shot).unwrap();
+    assert_eq!(game.run_to_next_turn(), Some(ShotUpdate::OutOfBounds));
+    assert_eq!(game.shots()[0].impact, None);
+    assert_eq!(game.active_player(), PlayerId(1));
+}
+
+#[test]
+fn tanks_that_drop_into_the_sea_sink() {
+    let mut map = bay();
+    map.sea_level = Some(405.0);
+    let mut game = Game::with_map(calm_field(900, 500), &map, 1).unwrap();
+
+    // straight up comes back down on the shooter, and blows the ground out
+    // from under it
+    game.fire(PlayerId(0), missile(200.0, 90.0)).unwrap();
+    game.run_to_next_turn();
+    let tank = game.terrain().tank(PlayerId(0));
+    assert!(tank.is_destroyed());
+    assert!(game.terrain().is_under_water(tank.location()));
+    let sunk = game.drain_events().iter().any(|event| match event {
+        Event::Impact(impact) => impact
+            .damage
+            .iter()
+            .any(|hit| hit.player == PlayerId(0) && hit.cause == DamageCause::Sunk),
+        Event::Settled { damage } => damage
+            .iter()
+            .any(|hit| hit.player == PlayerId(0) && hit.cause == DamageCause::Sunk),
+        _ => false,
+    });
+    assert!(sunk);
+    assert_eq!(game.result().unwrap().winner(), Some(PlayerId(1)));
+}
+
+#[test]
+fn edits_end_up_in_the_exported_map() {
+    let mut editor = MapEditor::new(&flat()).unwrap();
+
+    // a hill, with a tunnel through it
+    editor.paint(Point::new(450.0, 400.0), 40.0);
+    let (min_index, max_index) = editor.erase(Point::new(450.0, 380.0), 8.0);
+    assert!(min_index <= 442 && max_index >= 458);
+    assert_eq!(editor.terrain().heights()[450], 360.0);
+
+    // drag the second tank onto the hill
+    let tank = editor.terrain().tank(PlayerId(1)).center();
+    assert_eq!(editor.spawn_at(tank), Some(PlayerId(1)));
+    editor.move_spawn(PlayerId(1), 445.0);
+
+    // a mine placed twice is taken away again, and none go off the field
+    editor.toggle_mine(Point::new(200.0, 420.0)).unwrap();
+    editor.toggle_mine(Point::new(600.0, 420.0)).unwrap();
+    editor.toggle_mine(Point::new(601.0, 421.0)).unwrap();
+    assert!(editor.toggle_mine(Point::new(450.0, -3.0)).is_err());
+    assert!(editor.toggle_mine(Point::new(900.0, 420.0)).is_err());
+    assert!(editor.set_colors("#FFFFFF", "#000000").is_ok());
+    assert!(editor.set_colors("#123456", "#000000").is_err());
+
+    let map = Map::from_json(&editor.to_map("Hill").unwrap().to_json()).unwrap();
+    assert_eq!(map.spawns, vec![100.0, 445.0]);
+    assert_eq!(map.color_hex, "#FFFFFF");
+    assert_eq!(map.hazards.len(), 1);
+    assert!(matches!(map.ground[450], Column::Runs(ref runs) if runs.len() == 2));
+
+    // and the map can be played as it is
+    let game = editor.test_game(1).unwrap();
+    assert_eq!(game.terrain().tank(PlayerId(1)).location().y(), 360.0);
+}
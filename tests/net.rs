@@ -0,0 +1,536 @@
+//! Tests for networked matches: the relay's lobby, lockstep clients and the
+//! `warbots-server` binary.
+
+mod common;
+
+use common::{join, to};
+use std::io::{BufRead, BufReader};
+use std::net::TcpStream;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+use tungstenite::stream::MaybeTlsStream;
+use tungstenite::{Message, WebSocket};
+use warbots::sim::{
+    match_rng, play_match, round_seed, Action, Bot, BotRegistry, ClientMessage, Config,
+    ConnectionId, Game, Lobby, Lockstep, MatchSettings, Move, NetError, PlayerId, ServerMessage,
+    Stream, Turn, Weapon, PROTOCOL_VERSION,
+};
+
+fn skip(player: u8) -> ClientMessage {
+    ClientMessage::Move {
+        turn: 0,
+        checksum: 1,
+        made: Move::Skip {
+            player: PlayerId(player),
+        },
+    }
+}
+
+#[test]
+fn lobbies_seat_players_and_start_full_rooms() {
+    let mut lobby = Lobby::new(1);
+    let first = lobby.handle(10, join("den", None));
+    let (seed, token) = match to(&first, 10).as_slice() {
+        [ServerMessage::Joined {
+            room,
+            seat: 0,
+            seed,
+            token,
+            started: false,
+            turns: 0,
+            ..
+        }] => {
+            assert_eq!(room, "den");
+            (*seed, *token)
+        }
+        other => panic!("{:?}", other),
+    };
+    assert_eq!(lobby.rooms(), vec!["den"]);
+
+    let second = lobby.handle(11, join("den", None));
+    match to(&second, 11).as_slice() {
+        [ServerMessage::Joined {
+            seat: 1,
+            seed: same,
+            token: other,
+            started: true,
+            ..
+        }, ServerMessage::Started] => {
+            assert_eq!(*same, seed);
+            assert_ne!(*other, token);
+        }
+        other => panic!("{:?}", other),
+    }
+    assert_eq!(
+        to(&second, 10),
+        vec![
+            ServerMessage::Presence {
+                seat: 1,
+                connected: true
+            },
+            ServerMessage::Started
+        ]
+    );
+
+    assert!(matches!(
+        to(&lobby.handle(12, join("den", None)), 12).as_slice(),
+        [ServerMessage::Error { message }] if message == "room den is full"
+    ));
+    assert!(matches!(
+        to(&lobby.handle(10, join("attic", None)), 10).as_slice(),
+        [ServerMessage::Error { message }] if message == "already in room den"
+    ));
+    let old = ClientMessage::Join {
+        version: PROTOCOL_VERSION + 1,
+        room: "attic".to_string(),
+        settings: None,
+    };
+    assert!(matches!(
+        to(&lobby.handle(12, old), 12).as_slice(),
+        [ServerMessage::Error { .. }]
+    ));
+
+    // whoever opens a room picks its settings
+    let crowded = MatchSettings {
+        players: 3,
+        ..MatchSettings::default()
+    };
+    let opened = lobby.handle(
+        12,
+        ClientMessage::Join {
+            version: PROTOCOL_VERSION,
+            room: "hall".to_string(),
+            settings: Some(crowded.clone()),
+        },
+    );
+    assert!(matches!(
+        to(&opened, 12).as_slice(),
+        [ServerMessage::Joined { settings, .. }] if *settings == crowded
+    ));
+    assert!(!to(&lobby.handle(13, join("hall", None)), 13).contains(&ServerMessage::Started));
+}
+
+#[test]
+fn lobbies_relay_moves_in_turn_from_their_own_seat() {
+    let mut lobby = Lobby::new(2);
+    lobby.handle(10, join("den", None));
+    assert!(matches!(
+        to(&lobby.handle(10, skip(0)), 10).as_slice(),
+        [ServerMessage::Error { message }] if message == "the match hasn't started"
+    ));
+    lobby.handle(11, join("den", None));
+
+    assert!(matches!(
+        to(&lobby.handle(11, skip(0)), 11).as_slice(),
+        [ServerMessage::Error { message }] if message == "seat 1 can't move for player 0"
+    ));
+    assert!(matches!(
+        to(&lobby.handle(12, skip(0)), 12).as_slice(),
+        [ServerMessage::Error { .. }]
+    ));
+
+    let relayed = lobby.handle(10, skip(0));
+    assert!(to(&relayed, 10).is_empty());
+    assert_eq!(
+        to(&relayed, 11),
+        vec![ServerMessage::Moves {
+            from: 0,
+            moves: vec![Turn {
+                checksum: 1,
+                made: Move::Skip {
+                    player: PlayerId(0)
+                }
+            }]
+        }]
+    );
+    // the same turn again
+    assert!(matches!(
+        to(&lobby.handle(10, skip(0)), 10).as_slice(),
+        [ServerMessage::Error { message }] if message.contains("out of order")
+    ));
+}
+
+#[test]
+fn players_rejoin_their_seat_and_catch_up() {
+    let mut lobby = Lobby::new(3);
+    lobby.handle(10, join("den", None));
+    let token = match to(&lobby.handle(11, join("den", None)), 11).first() {
+        Some(ServerMessage::Joined { token, .. }) => *token,
+        other => panic!("{:?}", other),
+    };
+    assert_eq!(
+        lobby.disconnect(11),
+        vec![(
+            10,
+            ServerMessage::Presence {
+                seat: 1,
+                connected: false
+            }
+        )]
+    );
+    lobby.handle(10, skip(0));
+
+    let rejoin = |token| ClientMessage::Rejoin {
+        version: PROTOCOL_VERSION,
+        room: "den".to_string(),
+        seat: 1,
+        token,
+        since: 0,
+    };
+    assert!(matches!(
+        to(&lobby.handle(12, rejoin(token ^ 1)), 12).as_slice(),
+        [ServerMessage::Error { message }] if message == "seat 1 of room den isn't yours"
+    ));
+    let rejoined = lobby.handle(12, rejoin(token));
+    match to(&rejoined, 12).as_slice() {
+        [ServerMessage::Joined {
+            seat: 1,
+            started: true,
+            turns: 1,
+            ..
+        }, ServerMessage::Moves { from: 0, moves }] => assert_eq!(moves.len(), 1),
+        other => panic!("{:?}", other),
+    }
+    assert_eq!(
+        to(&rejoined, 10),
+        vec![ServerMessage::Presence {
+            seat: 1,
+            connected: true
+        }]
+    );
+
+    // the room closes once everyone has left
+    lobby.disconnect(10);
+    lobby.disconnect(12);
+    assert!(lobby.rooms().is_empty());
+    assert!(matches!(
+        to(&lobby.handle(13, rejoin(token)), 13).as_slice(),
+        [ServerMessage::Error { message }] if message == "there is no room den"
+    ));
+}
+
+// one computer in a networked match, with a bot at the keyboard
+struct Peer {
+    connection: ConnectionId,
+    connected: bool,
+    token: u64,
+    lockstep: Lockstep,
+    bot: Box<dyn Bot>,
+    seed: u64,
+    round: u32,
+    game: Game,
+    // the moves of every round, as a replay records them
+    rounds: Vec<Vec<Move>>,
+}
+
+impl Peer {
+    fn new(connection: ConnectionId, joined: &ServerMessage, bot: &str) -> Peer {
+        let (seat, token, seed, settings) = match joined {
+            ServerMessage::Joined {
+                seat,
+                token,
+                seed,
+                settings,
+                ..
+            } => (*seat, *token, *seed, settings),
+            other => panic!("{:?}", other),
+        };
+        let config = settings.config().unwrap();
+        Peer {
+            connection,
+            connected: true,
+            token,
+            lockstep: Lockstep::new(PlayerId(seat)),
+            bot: BotRegistry::new()
+                .create(bot, match_rng(seed, Stream::Player(seat)))
+                .unwrap(),
+            seed,
+            round: 0,
+            game: Game::new(config, round_seed(seed, 0)),
+            rounds: Vec::new(),
+        }
+    }
+
+    fn done(&self) -> bool {
+        self.game.is_over() && self.round + 1 == self.game.config().rounds()
+    }
+
+    // plays on as far as it can without hearing from the others, and
+    // returns what to send them
+    fn play(&mut self) -> Result<Vec<ClientMessage>, NetError> {
+        let mut sends = Vec::new();
+        loop {
+            if self.done() {
+                return Ok(sends);
+            }
+            if self.game.is_over() {
+                self.rounds.push(self.game.moves().to_vec());
+                self.round += 1;
+                let config = self.game.config().clone();
+                self.game = Game::new(config, round_seed(self.seed, self.round));
+            } else if self.lockstep.apply(&mut self.game)?.is_none() {
+                let player = self.game.active_player();
+                if player != self.lockstep.seat() {
+                    return Ok(sends);
+                }
+                let fired = match self.bot.decide(&self.game.observation(player)) {
+                    Some(action) => self.lockstep.fire(&mut self.game, action).ok(),
+                    None => None,
+                };
+                let message = match fired {
+                    Some(message) => message,
+                    None => self.lockstep.skip(&mut self.game)?,
+                };
+                sends.push(message);
+            }
+            self.game.run_to_next_turn();
+        }
+    }
+}
+
+// hands `message` to the lobby, and what it sends back to the peers, until
+// nobody has anything more to say
+fn deliver(lobby: &mut Lobby, peers: &mut [Peer], from: ConnectionId, message: ClientMessage) {
+    let mut queue = vec![(from, message)];
+    while let Some((from, message)) = queue.pop() {
+        for (connection, reply) in lobby.handle(from, message) {
+            if let Some(peer) = peers
+                .iter_mut()
+                .find(|peer| peer.connected && peer.connection == connection)
+            {
+                for answer in peer.lockstep.receive(&reply) {
+                    queue.push((connection, answer));
+                }
+            }
+        }
+    }
+}
+
+#[test]
+fn lockstep_clients_play_the_match_the_relay_hands_out() {
+    let mut lobby = Lobby::new(4);
+    let settings = MatchSettings {
+        rounds: 2,
+        ..MatchSettings::default()
+    };
+    let first = lobby.handle(
+        0,
+        ClientMessage::Join {
+            version: PROTOCOL_VERSION,
+            room: "den".to_string(),
+            settings: Some(settings.clone()),
+        },
+    );
+    let second = lobby.handle(1, join("den", None));
+    let mut peers = vec![
+        Peer::new(0, &to(&first, 0)[0], "easy"),
+        Peer::new(1, &to(&second, 1)[0], "hard"),
+    ];
+
+    for step in 0.. {
+        assert!(step < 10_000, "the match stalled");
+        if peers.iter().all(Peer::done) {
+            break;
+        }
+        if step == 8 {
+            let rejoin = ClientMessage::Rejoin {
+                version: PROTOCOL_VERSION,
+                room: "den".to_string(),
+                seat: 1,
+                token: peers[1].token,
+                since: peers[1].lockstep.turns(),
+            };
+            peers[1].connection = 2;
+            peers[1].connected = true;
+            deliver(&mut lobby, &mut peers, 2, rejoin);
+        }
+        for i in 0..peers.len() {
+            // the second player's connection drops for a while, just as
+            // they move, so the relay has to be sent it again
+            if step == 3 && i == 1 {
+                lobby.disconnect(1);
+                peers[1].connected = false;
+            }
+            let sends = peers[i].play().unwrap();
+            if peers[i].connected {
+                let from = peers[i].connection;
+                for message in sends {
+                    deliver(&mut lobby, &mut peers, from, message);
+                }
+            }
+        }
+    }
+
+    assert_eq!(peers[0].game.checksum(), peers[1].game.checksum());
+    let seed = peers[0].seed;
+    let mut bots: Vec<Box<dyn Bot>> = ["easy", "hard"]
+        .iter()
+        .enumerate()
+        .map(|(seat, name)| {
+            BotRegistry::new()
+                .create(name, match_rng(seed, Stream::Player(seat as u8)))
+                .unwrap()
+        })
+        .collect();
+    let report = play_match(&settings, seed, None, &mut bots).unwrap();
+    for peer in peers.iter_mut() {
+        peer.rounds.push(peer.game.moves().to_vec());
+        assert_eq!(peer.rounds, report.replay.rounds);
+    }
+}
+
+#[test]
+fn diverged_clients_stop_at_the_first_move_that_differs() {
+    let config = Config::new();
+    let mut game = Game::new(config.clone(), 5);
+    let same = Game::new(config.clone(), 5);
+    let mut other = Game::new(config, 6);
+    assert_eq!(game.checksum(), same.checksum());
+    assert_ne!(game.checksum(), other.checksum());
+
+    let mut sender = Lockstep::new(PlayerId(0));
+    let mut receiver = Lockstep::new(PlayerId(1));
+    let action = Action {
+        power: 5000.0,
+        angle: 45.0,
+        weapon: Weapon::Missile,
+    };
+    let (checksum, made) = match sender.fire(&mut game, action).unwrap() {
+        ClientMessage::Move {
+            turn: 0,
+            checksum,
+            made,
+        } => (checksum, made),
+        other => panic!("{:?}", other),
+    };
+    // clamped, as `Game::fire` played it
+    assert_eq!(made, game.moves()[0]);
+    assert_ne!(game.checksum(), checksum);
+
+    receiver.receive(&ServerMessage::Moves {
+        from: 0,
+        moves: vec![Turn { checksum, made }],
+    });
+    assert!(receiver.has_pending());
+    match receiver.apply(&mut other) {
+        Err(NetError::Desync {
+            turn: 0,
+            expected,
+            actual,
+        }) => assert_eq!(
+            (expected, actual),
+            (checksum, Game::new(Config::new(), 6).checksum())
+        ),
+        other => panic!("{:?}", other),
+    }
+    assert_eq!(other.moves(), &[]);
+
+    // it isn't the receiver's turn, and the move can't be skipped past
+    assert!(matches!(
+        receiver.skip(&mut other),
+        Err(NetError::Refused { turn: 1, .. })
+    ));
+}
+
+#[test]
+fn messages_are_tagged_json() {
+    let message = ClientMessage::Move {
+        turn: 3,
+        checksum: 42,
+        made: Move::Skip {
+            player: PlayerId(1),
+        },
+    };
+    let json = message.to_json();
+    assert!(json.contains("\"type\":\"move\""), "{}", json);
+    assert_eq!(ClientMessage::from_json(&json).unwrap(), message);
+
+    let joined =
+        ServerMessage::from_json(r#"{"type": "presence", "seat": 1, "connected": false}"#).unwrap();
+    assert_eq!(
+        joined,
+        ServerMessage::Presence {
+            seat: 1,
+            connected: false
+        }
+    );
+    assert!(ClientMessage::from_json(r#"{"type": "cheat"}"#).is_err());
+}
+
+fn connect(address: &str) -> WebSocket<MaybeTlsStream<TcpStream>> {
+    let (socket, _) = tungstenite::connect(address).unwrap();
+    if let MaybeTlsStream::Plain(stream) = socket.get_ref() {
+        stream
+            .set_read_timeout(Some(Duration::from_secs(10)))
+            .unwrap();
+    }
+    socket
+}
+
+fn send(socket: &mut WebSocket<MaybeTlsStream<TcpStream>>, message: &ClientMessage) {
+    socket.send(Message::Text(message.to_json())).unwrap();
+}
+
+fn receive(socket: &mut WebSocket<MaybeTlsStream<TcpStream>>) -> ServerMessage {
+    loop {
+        if let Message::Text(text) = socket.read().unwrap() {
+            return ServerMessage::from_json(&text).unwrap();
+        }
+    }
+}
+
+#[test]
+fn the_binary_relays_between_websockets() {
+    let mut server = Command::new(env!("CARGO_BIN_EXE_warbots-server"))
+        .args(["--port", "0", "--seed", "9"])
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut line = String::new();
+    BufReader::new(server.stdout.take().unwrap())
+        .read_line(&mut line)
+        .unwrap();
+    let address = line.trim().trim_start_matches("listening on ").to_string();
+    assert!(address.starts_with("ws://127.0.0.1:"), "{}", line);
+
+    let mut first = connect(&address);
+    send(&mut first, &join("den", None));
+    assert!(matches!(
+        receive(&mut first),
+        ServerMessage::Joined { seat: 0, .. }
+    ));
+    let mut second = connect(&address);
+    send(&mut second, &join("den", None));
+    assert!(matches!(
+        receive(&mut second),
+        ServerMessage::Joined { seat: 1, .. }
+    ));
+    assert_eq!(receive(&mut second), ServerMessage::Started);
+    assert!(matches!(
+        receive(&mut first),
+        ServerMessage::Presence { seat: 1, .. }
+    ));
+    assert_eq!(receive(&mut first), ServerMessage::Started);
+
+    send(&mut first, &skip(0));
+    assert!(matches!(
+        receive(&mut second),
+        ServerMessage::Moves { from: 0, .. }
+    ));
+    second.send(Message::Text("hello".to_string())).unwrap();
+    assert!(matches!(
+        receive(&mut second),
+        ServerMessage::Error { message } if message.starts_with("invalid message")
+    ));
+
+    second.close(None).unwrap();
+    assert_eq!(
+        receive(&mut first),
+        ServerMessage::Presence {
+            seat: 1,
+            connected: false
+        }
+    );
+    server.kill().unwrap();
+    server.wait().unwrap();
+}
@@ -0,0 +1,229 @@
+//! Tests for bots loaded from WebAssembly plugins.
+
+use warbots::sim::{
+    match_rng, play_bot_turn, play_match, Bot, BotRegistry, Config, Game, MatchSettings, PlayerId,
+    Plugin, PluginError, PluginLimits, Stream, Weapon,
+};
+
+const SHOT: &str = r#"{"power": 300, "angle": 60, "weapon": "mirv"}"#;
+
+/// A plugin speaking `abi` whose `warbots_decide` runs `decide`. Its memory
+/// holds `SHOT` at 0 and `null` at 100, and observations are written at 1024.
+fn plugin_source(abi: i32, decide: &str) -> String {
+    format!(
+        r#"(module
+  (memory (export "memory") 4)
+  (data (i32.const 0) "{shot}")
+  (data (i32.const 100) "null")
+  (func (export "warbots_abi") (result i32) i32.const {abi})
+  (func (export "warbots_alloc") (param i32) (result i32) i32.const 1024)
+  (func (export "warbots_decide") (param $ptr i32) (param $len i32) (result i64)
+    {decide}))"#,
+        shot = SHOT.replace('"', "\\\""),
+        abi = abi,
+        decide = decide
+    )
+}
+
+fn load(source: &str, limits: PluginLimits) -> Result<Plugin, PluginError> {
+    Plugin::new("plugin", &wat::parse_str(source).unwrap(), limits)
+}
+
+fn limits() -> PluginLimits {
+    PluginLimits {
+        fuel: 100_000,
+        ..PluginLimits::default()
+    }
+}
+
+// the packed location of `SHOT`, or of `null`
+fn shot() -> String {
+    format!("i64.const {}", SHOT.len())
+}
+const PASS: &str = "i64.const 0x6400000004";
+
+#[test]
+fn plugins_are_sent_the_observation_and_fire_their_reply() {
+    // fires only if what it was sent looks like a JSON object
+    let decide = format!(
+        "(if (result i64) (i32.eq (i32.load8_u (local.get $ptr)) (i32.const 123))
+           (then {}) (else {}))",
+        shot(),
+        PASS
+    );
+    let plugin = load(&plugin_source(1, &decide), limits()).unwrap();
+    assert_eq!(plugin.name(), "plugin");
+
+    let mut game = Game::new(Config::new(), 2);
+    let mut bot = plugin.bot(match_rng(2, Stream::Player(0)));
+    play_bot_turn(&mut game, &mut bot).unwrap();
+    assert!(bot.fault().is_none());
+    let fired = game.shots()[0].action;
+    assert_eq!((fired.power, fired.angle), (300.0, 60.0));
+    assert_eq!(fired.weapon, Weapon::Mirv);
+}
+
+#[test]
+fn plugins_can_pass() {
+    let plugin = load(&plugin_source(1, PASS), limits()).unwrap();
+    let mut game = Game::new(Config::new(), 2);
+    let mut bot = plugin.bot(match_rng(2, Stream::Player(0)));
+    play_bot_turn(&mut game, &mut bot).unwrap();
+    assert!(game.shots().is_empty());
+    assert_eq!(game.active_player(), PlayerId(1));
+    assert!(bot.fault().is_none());
+}
+
+#[test]
+fn plugins_that_misbehave_pass_from_then_on() {
+    let game = Game::new(Config::new(), 2);
+    let observation = game.observation(PlayerId(0));
+    let cases = vec![
+        (
+            "(loop $forever (br $forever)) unreachable",
+            PluginError::OutOfFuel,
+        ),
+        ("unreachable", PluginError::Trap(String::new())),
+        (
+            "i64.const 0xFFFF000000000010",
+            PluginError::Protocol(String::new()),
+        ),
+        // reads the observation back as its reply
+        (
+            "(i64.or (i64.shl (i64.extend_i32_u (local.get $ptr)) (i64.const 32))
+                     (i64.extend_i32_u (local.get $len)))",
+            PluginError::Protocol(String::new()),
+        ),
+    ];
+    for (decide, expected) in cases {
+        let plugin = load(&plugin_source(1, decide), limits()).unwrap();
+        let mut bot = plugin.bot(match_rng(2, Stream::Player(0)));
+        assert_eq!(bot.decide(&observation), None, "{}", decide);
+        let fault = bot.fault().cloned().unwrap();
+        assert_eq!(
+            std::mem::discriminant(&fault),
+            std::mem::discriminant(&expected),
+            "{}",
+            fault
+        );
+        assert_eq!(bot.decide(&observation), None);
+    }
+}
+
+#[test]
+fn plugins_cant_grow_past_their_memory_cap() {
+    // passes if it was refused the memory, fires if it got it
+    let decide = format!(
+        "(if (result i64) (i32.eq (memory.grow (i32.const 1000)) (i32.const -1))
+           (then {}) (else {}))",
+        PASS,
+        shot()
+    );
+    let source = plugin_source(1, &decide);
+    let game = Game::new(Config::new(), 2);
+    let observation = game.observation(PlayerId(0));
+
+    let mut capped = load(&source, limits())
+        .unwrap()
+        .bot(match_rng(2, Stream::Player(0)));
+    assert_eq!(capped.decide(&observation), None);
+    assert!(capped.fault().is_none());
+
+    // growing memory burns fuel for every byte too
+    let roomy = PluginLimits {
+        fuel: 10_000_000,
+        memory: 128 * 1024 * 1024,
+        ..limits()
+    };
+    let mut uncapped = load(&source, roomy)
+        .unwrap()
+        .bot(match_rng(2, Stream::Player(0)));
+    assert!(uncapped.decide(&observation).is_some());
+
+    let greedy = plugin_source(1, PASS).replace(
+        "(memory (export \"memory\") 4)",
+        "(memory (export \"memory\") 1000)",
+    );
+    assert!(matches!(
+        load(&greedy, limits()),
+        Err(PluginError::Invalid(_))
+    ));
+}
+
+#[test]
+fn plugins_that_dont_fit_the_abi_are_refused() {
+    assert!(matches!(
+        Plugin::new("junk", b"not wasm", limits()),
+        Err(PluginError::Invalid(_))
+    ));
+    assert_eq!(
+        load(&plugin_source(2, PASS), limits()).unwrap_err(),
+        PluginError::UnsupportedAbi(2)
+    );
+
+    let importer = plugin_source(1, PASS).replace(
+        "(module",
+        "(module (import \"env\" \"clock\" (func (result i64)))",
+    );
+    let error = load(&importer, limits()).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "plugins can't import anything, but this one imports env.clock"
+    );
+
+    let unexported = plugin_source(1, PASS).replace("(export \"warbots_alloc\")", "");
+    assert_eq!(
+        load(&unexported, limits()).unwrap_err().to_string(),
+        "plugin export warbots_alloc must be a function (i32) -> i32"
+    );
+
+    let tiny = PluginLimits {
+        module_size: 16,
+        ..limits()
+    };
+    assert!(matches!(
+        load(&plugin_source(1, PASS), tiny),
+        Err(PluginError::Invalid(_))
+    ));
+}
+
+#[test]
+fn registered_plugins_play_matches() {
+    let spinner = load(
+        &plugin_source(1, "(loop $forever (br $forever)) unreachable"),
+        limits(),
+    )
+    .unwrap();
+    let mut registry = BotRegistry::new();
+    registry.register_plugin(spinner);
+    assert!(registry.names().contains(&"plugin"));
+
+    // a plugin that never replies can't stall the match
+    let mut bots = vec![
+        registry
+            .create("plugin", match_rng(6, Stream::Player(0)))
+            .unwrap(),
+        registry
+            .create("hard", match_rng(6, Stream::Player(1)))
+            .unwrap(),
+    ];
+    let report = play_match(&MatchSettings::default(), 6, None, &mut bots).unwrap();
+    assert_eq!(report.winner(), Some(PlayerId(1)));
+}
+
+#[test]
+fn the_binary_loads_plugins() {
+    let path = std::env::temp_dir().join(format!("passer-{}.wasm", std::process::id()));
+    std::fs::write(&path, wat::parse_str(plugin_source(1, PASS)).unwrap()).unwrap();
+    let name = path.file_stem().unwrap().to_str().unwrap().to_string();
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_warbots-sim"))
+        .arg(&name)
+        .args(["easy", "--plugin"])
+        .arg(&path)
+        .output()
+        .unwrap();
+    std::fs::remove_file(&path).unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(output.status.success(), "{}", stdout);
+    assert!(stdout.contains("easy (2) wins"), "{}", stdout);
+}
@@ -0,0 +1,82 @@
+//! Tests for showing players where their shots will go.
+
+mod common;
+
+use common::missile;
+use warbots::sim::{Game, MatchSettings, Phase};
+
+fn previewing() -> Game {
+    let settings = MatchSettings {
+        aim_preview: true,
+        ..MatchSettings::default()
+    };
+    Game::new(settings.config().unwrap(), 7)
+}
+
+#[test]
+fn previews_are_off_unless_the_settings_allow_them() {
+    let game = Game::new(MatchSettings::default().config().unwrap(), 7);
+    assert!(!game.config().aim_preview());
+    assert_eq!(game.aim_preview(&missile(60.0, 45.0), 10), None);
+    assert!(previewing().aim_preview(&missile(60.0, 45.0), 10).is_some());
+
+    let settings = MatchSettings::from_toml("aim_preview = true").unwrap();
+    assert!(settings.aim_preview);
+    assert_eq!(MatchSettings::from_json(&settings.to_json()), Ok(settings));
+}
+
+#[test]
+fn previews_land_where_the_shot_would() {
+    let game = previewing();
+    let player = game.active_player();
+    let observation = game.observation(player);
+    let tank = game.terrain().tank(player);
+    for &(power, angle) in &[(40.0, 30.0), (60.0, 45.0), (80.0, 70.0)] {
+        let action = missile(power, angle);
+        let preview = game.aim_preview(&action, 10).unwrap();
+        assert_eq!(preview.impact, observation.predict_impact(&action));
+        assert_eq!(preview, observation.trajectory(&action, 10));
+        let launch = tank.launch(&action, game.config(), game.wind());
+        assert_eq!(preview.points[0], launch.position());
+        if let Some(impact) = preview.impact {
+            assert_eq!(preview.points.last(), Some(&impact));
+        }
+    }
+}
+
+#[test]
+fn previews_sample_as_often_as_asked() {
+    let game = previewing();
+    let action = missile(70.0, 50.0);
+    let every_tick = game.aim_preview(&action, 1).unwrap();
+    let sparse = game.aim_preview(&action, 10).unwrap();
+    assert_eq!(every_tick.impact, sparse.impact);
+    assert!(sparse.points.len() < every_tick.points.len());
+    // every tenth tick, plus the launch point and where it ends
+    for (n, point) in sparse.points[..sparse.points.len() - 1].iter().enumerate() {
+        assert_eq!(*point, every_tick.points[n * 10]);
+    }
+    assert_eq!(sparse.points.last(), every_tick.points.last());
+}
+
+#[test]
+fn shots_leaving_the_field_have_no_impact() {
+    let game = previewing();
+    let config = game.config();
+    let straight_up = missile(config.max_power() as f64, 90.0);
+    let preview = game.aim_preview(&straight_up, 5).unwrap();
+    assert_eq!(preview.impact, None);
+    assert!(preview.points.iter().all(|&point| config.in_bounds(point)));
+}
+
+#[test]
+fn previews_are_only_shown_while_aiming() {
+    let mut game = previewing();
+    let action = missile(60.0, 45.0);
+    game.fire(game.active_player(), action).unwrap();
+    assert_ne!(game.phase(), Phase::Aiming);
+    assert_eq!(game.aim_preview(&action, 10), None);
+    game.run_to_next_turn();
+    assert_eq!(game.phase(), Phase::Aiming);
+    assert!(game.aim_preview(&action, 10).is_some());
+}
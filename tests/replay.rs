@@ -0,0 +1,203 @@
+//! Tests for recording matches and playing them back.
+
+mod common;
+
+use common::missile;
+use warbots::sim::{
+    match_rng, play_bot_turn, round_seed, ComputerBot, Difficulty, Game, GameError, Map,
+    MatchSettings, Move, Playback, PlayerId, Replay, ReplayError, Stream, REPLAY_VERSION,
+};
+
+fn settings() -> MatchSettings {
+    MatchSettings {
+        rounds: 2,
+        ..MatchSettings::default()
+    }
+}
+
+/// Plays every round of a match between two bots, skipping every third turn,
+/// and returns its replay with the game each round ended as.
+fn play_match(seed: u64) -> (Replay, Vec<Game>) {
+    let settings = settings();
+    let config = settings.config().unwrap();
+    let mut replay = Replay::new(seed, settings, None);
+    let mut bot = ComputerBot::new(Difficulty::Easy, match_rng(seed, Stream::Player(0)));
+    let mut games = Vec::new();
+    for round in 0..config.rounds() {
+        let mut game = Game::new(config.clone(), round_seed(seed, round));
+        for turn in 0..200 {
+            if game.is_over() {
+                break;
+            }
+            if turn % 3 == 2 {
+                game.skip_turn(game.active_player()).unwrap();
+                game.run_to_next_turn();
+            } else {
+                play_bot_turn(&mut game, &mut bot).unwrap();
+            }
+        }
+        replay.record(&game);
+        games.push(game);
+    }
+    (replay, games)
+}
+
+#[test]
+fn a_replay_plays_the_match_out_again() {
+    let (replay, games) = play_match(11);
+    let replay = Replay::from_json(&replay.to_json()).unwrap();
+    assert_eq!(replay.rounds.len(), 2);
+    assert!(replay.rounds[0]
+        .iter()
+        .any(|made| matches!(made, Move::Skip { .. })));
+
+    let mut playback = Playback::new(replay.clone()).unwrap();
+    for (round, game) in games.iter().enumerate() {
+        playback.seek(round, replay.rounds[round].len()).unwrap();
+        assert_eq!(playback.game().terrain().mask(), game.terrain().mask());
+        assert_eq!(playback.game().result(), game.result());
+        for (a, b) in playback
+            .game()
+            .terrain()
+            .tanks()
+            .iter()
+            .zip(game.terrain().tanks())
+        {
+            assert_eq!(a.health(), b.health());
+            assert_eq!(a.location(), b.location());
+        }
+    }
+
+    // ticking through gets to the same place
+    let mut ticked = Playback::new(replay).unwrap();
+    while ticked.step().unwrap() {}
+    assert!(ticked.is_finished());
+    assert_eq!(
+        (ticked.round(), ticked.turn()),
+        (playback.round(), playback.turn())
+    );
+    assert_eq!(
+        ticked.game().terrain().mask(),
+        playback.game().terrain().mask()
+    );
+}
+
+#[test]
+fn seeking_back_replays_the_round_from_its_start() {
+    let (replay, _) = play_match(5);
+    let mut forward = Playback::new(replay.clone()).unwrap();
+    forward.seek(0, 3).unwrap();
+
+    let mut back = Playback::new(replay).unwrap();
+    back.seek(0, 7).unwrap();
+    back.seek(0, 3).unwrap();
+    assert_eq!(back.turn(), 3);
+    assert_eq!(
+        back.game().terrain().mask(),
+        forward.game().terrain().mask()
+    );
+    assert_eq!(back.game().wind(), forward.game().wind());
+
+    // one turn on is where the next seek would land
+    back.next_turn().unwrap();
+    forward.seek(0, 4).unwrap();
+    assert_eq!(back.turn(), 4);
+    assert_eq!(
+        back.game().terrain().mask(),
+        forward.game().terrain().mask()
+    );
+
+    assert_eq!(
+        back.seek(0, 500).unwrap_err(),
+        ReplayError::NoSuchTurn {
+            round: 0,
+            turn: 500
+        }
+    );
+    assert!(back.seek(2, 0).is_err());
+}
+
+#[test]
+fn moves_are_stored_compactly() {
+    let settings = MatchSettings {
+        max_wind: 0.0,
+        ..MatchSettings::default()
+    };
+    let mut game = Game::new(settings.config().unwrap(), 3);
+    game.fire(PlayerId(0), missile(200.0, 90.0)).unwrap();
+    game.run_to_next_turn();
+    game.skip_turn(PlayerId(1)).unwrap();
+    let mut replay = Replay::new(3, settings, None);
+    replay.record(&game);
+
+    let json = replay.to_json();
+    assert!(
+        json.contains("\"rounds\":[[[0,200.0,90.0,\"missile\"],1]]"),
+        "{}",
+        json
+    );
+    assert_eq!(Replay::from_json(&json), Ok(replay));
+}
+
+#[test]
+fn matches_on_maps_record_the_map() {
+    let map = Map::from_library("Canyon").unwrap();
+    let settings = MatchSettings {
+        width: map.width,
+        height: map.height,
+        ..MatchSettings::default()
+    };
+    let config = settings.config().unwrap();
+    let mut game = Game::with_map(config, &map, 9).unwrap();
+    game.fire(PlayerId(0), missile(300.0, 60.0)).unwrap();
+    game.run_to_next_turn();
+    let mut replay = Replay::new(9, settings, Some(map));
+    replay.record(&game);
+
+    let mut playback = Playback::new(Replay::from_json(&replay.to_json()).unwrap()).unwrap();
+    playback.seek(0, 1).unwrap();
+    assert_eq!(playback.game().terrain().mask(), game.terrain().mask());
+}
+
+#[test]
+fn replays_that_cant_be_played_are_refused() {
+    let (replay, _) = play_match(2);
+    let json = replay.to_json();
+
+    let newer = json.replacen(
+        &format!("\"version\":{}", REPLAY_VERSION),
+        "\"version\":99",
+        1,
+    );
+    assert_eq!(
+        Replay::from_json(&newer),
+        Err(ReplayError::UnsupportedVersion(99))
+    );
+    let unknown = json.replacen("\"missile\"", "\"laser\"", 1);
+    assert!(matches!(
+        Replay::from_json(&unknown),
+        Err(ReplayError::Parse(_))
+    ));
+
+    // a move out of turn means the replay isn't of this match
+    let mut tampered = replay;
+    tampered.rounds[0][1] = Move::Skip {
+        player: PlayerId(0),
+    };
+    let mut playback = Playback::new(tampered).unwrap();
+    let error = playback.seek(0, 3).unwrap_err();
+    assert_eq!(
+        error,
+        ReplayError::Diverged {
+            round: 0,
+            turn: 1,
+            error: GameError::NotYourTurn {
+                active: PlayerId(1)
+            }
+        }
+    );
+    assert_eq!(
+        error.to_string(),
+        "replay diverged at round 1 turn 2: it is player 2's turn"
+    );
+}
@@ -0,0 +1,155 @@
+//! Tests for choosing match settings and loading them from files.
+
+use warbots::sim::{
+    Action, Config, Game, MatchSettings, Noise, PlayerId, SettingsError, TerrainSettings, Weapon,
+};
+
+#[test]
+fn defaults_match_the_classic_game() {
+    let config = MatchSettings::default().config().unwrap();
+    assert_eq!(config.width(), 900.0);
+    assert_eq!(config.height(), 500.0);
+    assert_eq!(config.players(), 2);
+
+    let a = Game::new(config, 9);
+    let b = Game::new(Config::new(), 9);
+    assert_eq!(a.terrain().heights(), b.terrain().heights());
+}
+
+#[test]
+fn toml_only_needs_the_settings_that_change() {
+    let settings = MatchSettings::from_toml(
+        r#"
+        width = 1200
+        tank_health = 50.0
+        rounds = 3
+
+        [loadout]
+        nuke = 4
+        "#,
+    )
+    .unwrap();
+    assert_eq!(settings.width, 1200);
+    assert_eq!(settings.height, MatchSettings::default().height);
+
+    let game = Game::new(settings.config().unwrap(), 1);
+    assert_eq!(game.terrain().width(), 1200);
+    assert_eq!(game.config().rounds(), 3);
+    let tank = game.terrain().tank(PlayerId(1));
+    assert_eq!(tank.max_health(), 50.0);
+    assert_eq!(tank.inventory().count(Weapon::Nuke), Some(4));
+    // the loadout replaces the standard one
+    assert_eq!(tank.inventory().count(Weapon::Mirv), Some(0));
+}
+
+#[test]
+fn json_round_trips() {
+    let settings = MatchSettings {
+        gravity: 0.002,
+        max_wind: 0.0,
+        ..MatchSettings::default()
+    };
+    assert_eq!(
+        MatchSettings::from_json(&settings.to_json()).unwrap(),
+        settings
+    );
+}
+
+#[test]
+fn bad_settings_are_explained() {
+    let error = MatchSettings {
+        width: 50,
+        ..MatchSettings::default()
+    }
+    .config()
+    .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "width must be between 300 and 4000, not 50"
+    );
+
+    let error = MatchSettings {
+        players: 9,
+        ..MatchSettings::default()
+    }
+    .validate()
+    .unwrap_err();
+    assert_eq!(error.to_string(), "players must be between 2 and 8, not 9");
+
+    let error = MatchSettings::from_toml("[loadout]\nlaser = 1")
+        .unwrap()
+        .validate();
+    assert_eq!(
+        error,
+        Err(SettingsError::UnknownWeapon("laser".to_string()))
+    );
+
+    let error = MatchSettings::from_toml("[loadout]\nmissile = 1")
+        .unwrap()
+        .validate();
+    assert_eq!(error, Err(SettingsError::UnlimitedWeapon));
+
+    match MatchSettings::from_toml("widht = 1200") {
+        Err(SettingsError::Parse(message)) => assert!(message.contains("widht")),
+        other => panic!("expected a parse error, got {:?}", other),
+    }
+}
+
+#[test]
+fn gravity_changes_how_far_a_shot_flies() {
+    let range = |gravity| {
+        let settings = MatchSettings {
+            gravity,
+            max_wind: 0.0,
+            ..MatchSettings::default()
+        };
+        let game = Game::new(settings.config().unwrap(), 7);
+        let observation = game.observation(PlayerId(0));
+        let action = Action {
+            power: 100.0,
+            angle: 135.0,
+            weapon: Weapon::Missile,
+        };
+        let own_x = observation.own_tank.location().x();
+        observation
+            .predict_impact(&action)
+            .map(|point| (point.x() - own_x).abs())
+    };
+    assert!(range(0.0005) > range(0.001));
+}
+
+#[test]
+fn terrain_comes_from_the_chosen_generator() {
+    let settings = MatchSettings::from_toml(
+        r#"
+        [terrain]
+        generator = "noise"
+        octaves = 6
+        "#,
+    )
+    .unwrap();
+    assert_eq!(
+        settings.terrain,
+        TerrainSettings::Noise(Noise {
+            octaves: 6,
+            ..Noise::default()
+        })
+    );
+
+    let settings =
+        MatchSettings::from_toml("[terrain]\ngenerator = \"flat\"\nlevel = 0.5").unwrap();
+    let game = Game::new(settings.config().unwrap(), 4);
+    assert!(game.terrain().heights().iter().all(|&y| y == 250.0));
+
+    // parameters belong to their generator
+    assert!(MatchSettings::from_toml("[terrain]\ngenerator = \"flat\"\noctaves = 6").is_err());
+
+    let error = MatchSettings::from_toml("[terrain]\ngenerator = \"islands\"\ncount = 0")
+        .unwrap()
+        .validate()
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "terrain.count must be between 1 and 20, not 0"
+    );
+}
@@ -0,0 +1,368 @@
+//! Native tests for the browser-free game rules.
+
+mod common;
+
+use common::{calm, missile};
+use warbots::sim::{
+    round_seed, Config, DamageCause, Event, Game, GameError, MatchSettings, Phase, PlayerId,
+    ShotUpdate,
+};
+
+/// Fires for the active player and plays on until the next turn starts.
+fn play_shot(game: &mut Game, power: f64, angle: f64) -> ShotUpdate {
+    game.fire(game.active_player(), missile(power, angle))
+        .unwrap();
+    game.run_to_next_turn()
+        .expect("projectile vanished without landing")
+}
+
+fn skip(game: &mut Game) {
+    game.skip_turn(game.active_player()).unwrap();
+    game.run_to_next_turn();
+}
+
+#[test]
+fn shot_lands_and_damages_terrain() {
+    let mut game = Game::new(calm(), 7);
+    let before = game.terrain().heights().to_vec();
+
+    // straight up comes straight back down onto the shooter's side
+    match play_shot(&mut game, 200.0, 90.0) {
+        ShotUpdate::Impact(impact) => {
+            assert!(impact.min_index < impact.max_index);
+            let after = game.terrain().heights();
+            let i = impact.point.x() as usize;
+            assert!(after[i] > before[i], "crater should lower the ground");
+        }
+        other => panic!("expected an impact, got {:?}", other),
+    }
+    assert!(!game.projectile_in_flight());
+}
+
+#[test]
+fn shot_leaving_the_field_is_out_of_bounds() {
+    // steep enough to clear any hill between the tank and the edge
+    let mut game = Game::new(Config::new(), 7);
+    assert_eq!(play_shot(&mut game, 1000.0, 80.0), ShotUpdate::OutOfBounds);
+}
+
+#[test]
+fn skipping_passes_to_the_other_tank() {
+    let mut game = Game::new(Config::new(), 7);
+    assert_eq!(game.active_player(), PlayerId(0));
+    skip(&mut game);
+    assert_eq!(game.active_player(), PlayerId(1));
+    skip(&mut game);
+    assert_eq!(game.active_player(), PlayerId(0));
+}
+
+#[test]
+fn a_shot_moves_through_every_phase_in_order() {
+    let mut game = Game::new(calm(), 7);
+    assert_eq!(game.phase(), Phase::Aiming);
+    game.fire(PlayerId(0), missile(200.0, 90.0)).unwrap();
+    assert_eq!(game.phase(), Phase::InFlight);
+    while game.phase() == Phase::InFlight {
+        game.step();
+    }
+    assert_eq!(game.phase(), Phase::Resolving);
+    // the ground settles for as long as it takes
+    while game.phase() == Phase::Resolving {
+        game.step();
+    }
+    assert_eq!(game.phase(), Phase::TurnTransition);
+    game.step();
+    assert_eq!(game.phase(), Phase::Aiming);
+    assert_eq!(game.active_player(), PlayerId(1));
+
+    let events: Vec<Event> = game
+        .drain_events()
+        .into_iter()
+        .filter(|event| !matches!(event, Event::Landslide { .. } | Event::Settled { .. }))
+        .collect();
+    assert!(matches!(
+        events.as_slice(),
+        [
+            Event::TurnStarted {
+                player: PlayerId(0),
+                ..
+            },
+            Event::ShotFired {
+                player: PlayerId(0),
+                ..
+            },
+            Event::Impact(_),
+            Event::TurnEnded {
+                player: PlayerId(0)
+            },
+            Event::TurnStarted {
+                player: PlayerId(1),
+                ..
+            },
+        ]
+    ));
+    assert!(game.drain_events().is_empty());
+}
+
+#[test]
+fn out_of_turn_actions_are_refused() {
+    let mut game = Game::new(Config::new(), 7);
+    assert_eq!(
+        game.fire(PlayerId(1), missile(200.0, 90.0)),
+        Err(GameError::NotYourTurn {
+            active: PlayerId(0)
+        })
+    );
+    assert_eq!(
+        game.skip_turn(PlayerId(1)),
+        Err(GameError::NotYourTurn {
+            active: PlayerId(0)
+        })
+    );
+
+    game.fire(PlayerId(0), missile(200.0, 90.0)).unwrap();
+    assert_eq!(
+        game.fire(PlayerId(0), missile(200.0, 90.0)),
+        Err(GameError::AlreadyFired)
+    );
+    assert_eq!(game.skip_turn(PlayerId(0)), Err(GameError::AlreadyFired));
+    assert_eq!(game.shots().len(), 1);
+}
+
+#[test]
+fn same_seed_and_inputs_play_out_identically() {
+    let mut a = Game::new(Config::new(), 42);
+    let mut b = Game::new(Config::new(), 42);
+    assert_eq!(a.terrain().heights(), b.terrain().heights());
+    assert_eq!(a.terrain().color_hex(), b.terrain().color_hex());
+
+    for &(power, angle) in [(300.0, 120.0), (450.0, 100.0), (150.0, 60.0)].iter() {
+        assert_eq!(
+            play_shot(&mut a, power, angle),
+            play_shot(&mut b, power, angle)
+        );
+    }
+    assert_eq!(a.terrain().heights(), b.terrain().heights());
+}
+
+#[test]
+fn rounds_of_nearby_seeds_are_all_different() {
+    let mut seen = std::collections::HashSet::new();
+    for seed in 100..110 {
+        assert_eq!(round_seed(seed, 0), seed);
+        for round in 0..5 {
+            assert!(seen.insert(round_seed(seed, round)), "{} {}", seed, round);
+        }
+    }
+}
+
+#[test]
+fn different_seeds_generate_different_terrain() {
+    let a = Game::new(Config::new(), 1);
+    let b = Game::new(Config::new(), 2);
+    assert_ne!(a.terrain().heights(), b.terrain().heights());
+}
+
+#[test]
+fn a_direct_hit_hurts_and_repeated_hits_end_the_match() {
+    let mut game = Game::new(calm(), 7);
+    let full = game.terrain().tank(PlayerId(0)).max_health();
+
+    // straight up comes back down on the shooter
+    match play_shot(&mut game, 200.0, 90.0) {
+        ShotUpdate::Impact(impact) => {
+            assert!(impact
+                .damage
+                .iter()
+                .any(|d| d.player == PlayerId(0) && d.cause == DamageCause::Blast));
+            assert!(impact.damage.iter().all(|d| d.player == PlayerId(0)));
+        }
+        other => panic!("expected an impact, got {:?}", other),
+    }
+    assert!(game.terrain().tank(PlayerId(0)).health() < full);
+    assert_eq!(game.terrain().tank(PlayerId(1)).health(), full);
+    assert!(game.result().is_none());
+
+    // the right tank sits it out while the left keeps hitting itself
+    while !game.is_over() {
+        skip(&mut game);
+        play_shot(&mut game, 200.0, 90.0);
+    }
+    assert!(game.terrain().tank(PlayerId(0)).is_destroyed());
+    assert_eq!(game.result().unwrap().winner(), Some(PlayerId(1)));
+
+    // nothing more can be fired once it is over
+    let shots = game.shots().len();
+    let player = game.active_player();
+    assert_eq!(
+        game.fire(player, missile(200.0, 90.0)),
+        Err(GameError::MatchOver)
+    );
+    assert_eq!(game.skip_turn(player), Err(GameError::MatchOver));
+    assert_eq!(game.shots().len(), shots);
+    assert_eq!(game.phase(), Phase::Over);
+    assert!(matches!(
+        game.drain_events().last(),
+        Some(Event::MatchOver(_))
+    ));
+}
+
+#[test]
+fn tanks_take_fall_damage_when_the_ground_drops() {
+    let mut game = Game::new(calm(), 7);
+    match play_shot(&mut game, 200.0, 90.0) {
+        ShotUpdate::Impact(impact) => assert!(impact
+            .damage
+            .iter()
+            .any(|d| d.player == PlayerId(0) && d.cause == DamageCause::Fall)),
+        other => panic!("expected an impact, got {:?}", other),
+    }
+}
+
+#[test]
+fn wind_changes_every_turn_within_its_limits() {
+    let config = Config::new();
+    let mut game = Game::new(config.clone(), 7);
+    let mut winds = Vec::new();
+    for _ in 0..6 {
+        assert!(game.wind().abs() <= config.max_wind());
+        assert_eq!(game.observation(game.active_player()).wind, game.wind());
+        winds.push(game.wind());
+        skip(&mut game);
+    }
+    winds.dedup();
+    assert_eq!(winds.len(), 6);
+
+    let events = game.drain_events();
+    let announced: Vec<f64> = events
+        .iter()
+        .filter_map(|event| match event {
+            Event::TurnStarted { wind, .. } => Some(*wind),
+            _ => None,
+        })
+        .collect();
+    assert_eq!(announced[..6], winds[..]);
+}
+
+#[test]
+fn wind_carries_shots_downwind() {
+    // straight up lands back on the shooter unless the wind moves it
+    let mut calm_game = Game::new(calm(), 4);
+    let still = match play_shot(&mut calm_game, 200.0, 90.0) {
+        ShotUpdate::Impact(impact) => impact.point.x(),
+        other => panic!("expected an impact, got {:?}", other),
+    };
+
+    let mut game = Game::new(Config::new(), 4);
+    let wind = game.wind();
+    assert!(wind != 0.0);
+    match play_shot(&mut game, 200.0, 90.0) {
+        ShotUpdate::Impact(impact) => {
+            assert_eq!((impact.point.x() - still).signum(), wind.signum())
+        }
+        other => panic!("expected an impact, got {:?}", other),
+    }
+}
+
+fn players(count: u8) -> Config {
+    MatchSettings {
+        players: count,
+        max_wind: 0.0,
+        ..MatchSettings::default()
+    }
+    .config()
+    .unwrap()
+}
+
+#[test]
+fn every_player_gets_a_tank_of_their_own() {
+    for count in 2..=8 {
+        for seed in 0..10 {
+            let game = Game::new(players(count), seed);
+            let tanks = game.terrain().tanks();
+            assert_eq!(tanks.len(), count as usize);
+
+            let mut spans: Vec<(f64, f64)> = tanks
+                .iter()
+                .map(|tank| (tank.location().x(), tank.location().x() + tank.width()))
+                .collect();
+            spans.sort_by(|a, b| a.0.total_cmp(&b.0));
+            assert!(spans[0].0 >= 0.0);
+            assert!(spans[spans.len() - 1].1 <= game.config().width());
+            for pair in spans.windows(2) {
+                assert!(pair[0].1 < pair[1].0, "tanks overlap: {:?}", pair);
+            }
+
+            let mut colors: Vec<&str> = tanks.iter().map(|tank| tank.color_hex()).collect();
+            colors.sort_unstable();
+            colors.dedup();
+            assert_eq!(colors.len(), count as usize);
+        }
+    }
+}
+
+#[test]
+fn spawn_order_changes_with_the_seed() {
+    let leftmost = |seed| {
+        let game = Game::new(players(4), seed);
+        let tanks = game.terrain().tanks();
+        tanks
+            .iter()
+            .min_by(|a, b| a.location().x().total_cmp(&b.location().x()))
+            .unwrap()
+            .player()
+    };
+    let first: Vec<PlayerId> = (0..10).map(leftmost).collect();
+    assert!(first.iter().any(|&player| player != first[0]));
+}
+
+#[test]
+fn turns_skip_players_who_are_knocked_out() {
+    let mut game = Game::new(players(3), 7);
+    let mut order = Vec::new();
+    // the first player shoots straight up at themselves until they are gone
+    while !game.terrain().tank(PlayerId(0)).is_destroyed() {
+        if game.active_player() == PlayerId(0) {
+            play_shot(&mut game, 200.0, 90.0);
+        } else {
+            skip(&mut game);
+        }
+    }
+    assert!(game.result().is_none());
+    for _ in 0..4 {
+        order.push(game.active_player());
+        skip(&mut game);
+    }
+    assert!(order.iter().all(|&player| player != PlayerId(0)));
+    assert_ne!(order[0], order[1]);
+    assert_eq!(order[0], order[2]);
+}
+
+#[test]
+fn earth_slides_until_it_reaches_the_angle_of_repose() {
+    let landslides = |angle_of_repose| {
+        let config = MatchSettings {
+            angle_of_repose,
+            max_wind: 0.0,
+            ..MatchSettings::default()
+        }
+        .config()
+        .unwrap();
+        let mut game = Game::new(config, 7);
+        play_shot(&mut game, 200.0, 90.0);
+        let events = game.drain_events();
+        let settled = events
+            .iter()
+            .position(|event| matches!(event, Event::Settled { .. }));
+        let ended = events
+            .iter()
+            .position(|event| matches!(event, Event::TurnEnded { .. }));
+        assert!(settled < ended);
+        events
+            .iter()
+            .filter(|event| matches!(event, Event::Landslide { .. }))
+            .count()
+    };
+    // a crater's walls are steeper than earth can stand at 45°
+    assert!(landslides(45.0) > landslides(90.0));
+}
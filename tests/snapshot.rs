@@ -0,0 +1,150 @@
+//! Tests for saving matches and carrying on with them.
+
+use warbots::sim::{
+    match_rng, play_bot_turn, Action, Bot, ComputerBot, Difficulty, Game, MatchSettings, PlayerId,
+    Replay, Snapshot, SnapshotError, Stream, Weapon, SNAPSHOT_VERSION,
+};
+
+fn players() -> Vec<String> {
+    vec!["human".to_string(), "medium".to_string()]
+}
+
+fn shot(weapon: Weapon, power: f64, angle: f64) -> Action {
+    Action {
+        power,
+        angle,
+        weapon,
+    }
+}
+
+fn assert_same(a: &Game, b: &Game) {
+    assert_eq!(a.terrain().mask(), b.terrain().mask());
+    assert_eq!(a.terrain().heights(), b.terrain().heights());
+    assert_eq!(a.terrain().hazards(), b.terrain().hazards());
+    for (a, b) in a.terrain().tanks().iter().zip(b.terrain().tanks()) {
+        assert_eq!(a.location(), b.location());
+        assert_eq!(a.facing(), b.facing());
+        assert_eq!(a.health(), b.health());
+        assert_eq!(a.inventory(), b.inventory());
+    }
+    assert_eq!(a.active_player(), b.active_player());
+    assert_eq!(a.wind(), b.wind());
+    assert_eq!(a.phase(), b.phase());
+    assert_eq!(a.result(), b.result());
+    assert_eq!(a.shots(), b.shots());
+    assert_eq!(a.moves(), b.moves());
+}
+
+#[test]
+fn a_saved_match_carries_on_exactly_where_it_left_off() {
+    let settings = MatchSettings {
+        rounds: 3,
+        ..MatchSettings::default()
+    };
+    let mut game = Game::new(settings.config().unwrap(), 4);
+    let mut bot = ComputerBot::new(Difficulty::Medium, match_rng(4, Stream::Player(1)));
+    game.fire(PlayerId(0), shot(Weapon::Nuke, 250.0, 80.0))
+        .unwrap();
+    game.run_to_next_turn();
+    play_bot_turn(&mut game, &mut bot).unwrap();
+    game.skip_turn(PlayerId(0)).unwrap();
+    game.run_to_next_turn();
+
+    let snapshot =
+        Snapshot::new(players(), Replay::new(4, settings, None), vec![0, 1], &game).unwrap();
+    let loaded = Snapshot::from_json(&snapshot.to_json()).unwrap();
+    assert_eq!(loaded, snapshot);
+    assert_eq!(loaded.wins, vec![0, 1]);
+    let mut restored = loaded.game().unwrap();
+    assert_same(&restored, &game);
+
+    // the same shots play out the same, down to the wind each turn draws
+    for _ in 0..3 {
+        let player = game.active_player();
+        game.fire(player, shot(Weapon::Mirv, 300.0, 70.0)).unwrap();
+        restored
+            .fire(player, shot(Weapon::Mirv, 300.0, 70.0))
+            .unwrap();
+        game.run_to_next_turn();
+        restored.run_to_next_turn();
+    }
+    assert_same(&restored, &game);
+}
+
+#[test]
+fn bots_start_afresh_in_a_resumed_match() {
+    // as a front-end makes them, from their seat's stream of the match seed
+    let fresh = || ComputerBot::new(Difficulty::Medium, match_rng(4, Stream::Player(1)));
+    let settings = MatchSettings::default();
+    let mut game = Game::new(settings.config().unwrap(), 4);
+    let mut running = fresh();
+    game.skip_turn(PlayerId(0)).unwrap();
+    game.run_to_next_turn();
+    play_bot_turn(&mut game, &mut running).unwrap();
+    game.skip_turn(PlayerId(0)).unwrap();
+    game.run_to_next_turn();
+
+    let snapshot =
+        Snapshot::new(players(), Replay::new(4, settings, None), vec![0, 0], &game).unwrap();
+    let observation = snapshot.game().unwrap().observation(PlayerId(1));
+    let resumed = fresh().decide(&observation);
+    assert_eq!(resumed, fresh().decide(&observation));
+    // the bot left running has drawn on its stream since it started
+    assert_ne!(resumed, running.decide(&observation));
+}
+
+#[test]
+fn matches_are_only_saved_between_turns() {
+    let settings = MatchSettings::default();
+    let mut game = Game::new(settings.config().unwrap(), 1);
+    game.fire(PlayerId(0), shot(Weapon::Nuke, 200.0, 90.0))
+        .unwrap();
+    let replay = Replay::new(1, settings, None);
+    let error = Snapshot::new(players(), replay.clone(), vec![0, 0], &game).unwrap_err();
+    assert_eq!(error, SnapshotError::MidTurn);
+
+    // straight up comes down on the shooter, which ends the round
+    game.run_to_next_turn();
+    while !game.is_over() {
+        let player = game.active_player();
+        game.fire(player, shot(Weapon::Missile, 200.0, 90.0))
+            .unwrap();
+        game.run_to_next_turn();
+    }
+    let snapshot = Snapshot::new(players(), replay, vec![0, 1], &game).unwrap();
+    assert_same(&snapshot.game().unwrap(), &game);
+}
+
+#[test]
+fn snapshots_that_cant_be_carried_on_are_refused() {
+    let settings = MatchSettings::default();
+    let game = Game::new(settings.config().unwrap(), 1);
+    let snapshot =
+        Snapshot::new(players(), Replay::new(1, settings, None), vec![0, 0], &game).unwrap();
+    let json = snapshot.to_json();
+
+    let newer = json.replacen(
+        &format!("\"version\":{}", SNAPSHOT_VERSION),
+        "\"version\":7",
+        1,
+    );
+    assert_eq!(
+        Snapshot::from_json(&newer),
+        Err(SnapshotError::UnsupportedVersion(7))
+    );
+
+    let mut short = snapshot.clone();
+    short.players.pop();
+    assert!(matches!(short.validate(), Err(SnapshotError::Invalid(_))));
+
+    let mut cheat = snapshot.clone();
+    cheat.round.tanks[0].ammo.insert("missile".to_string(), 3);
+    assert_eq!(
+        cheat.game().unwrap_err().to_string(),
+        "invalid snapshot: \"missile\" isn't a limited weapon"
+    );
+
+    let mut lost = snapshot;
+    lost.round.active_player = 5;
+    assert!(matches!(lost.game(), Err(SnapshotError::Invalid(_))));
+}
@@ -0,0 +1,193 @@
+//! Tests for the destructible ground.
+
+use warbots::sim::{
+    match_rng, Flat, Islands, MatchSettings, MidpointDisplacement, Noise, Plateaus, PlayerId,
+    Point, RandomWalk, Stream, Terrain, TerrainMask, TerrainSettings,
+};
+
+fn flat(surface: f64) -> TerrainMask {
+    TerrainMask::from_heights(&[surface; 100], 100)
+}
+
+#[test]
+fn ground_fills_each_column_from_its_surface_down() {
+    let mask = TerrainMask::from_heights(&[40.0, 60.5], 100);
+    assert_eq!(mask.surface(0), 40.0);
+    // a pixel with any of it underground is solid
+    assert_eq!(mask.surface(1), 60.0);
+    assert!(mask.is_solid(Point::new(0.5, 40.5)));
+    assert!(!mask.is_solid(Point::new(0.5, 39.5)));
+    assert!(!mask.is_solid(Point::new(-1.0, 99.0)));
+    assert_eq!(mask.runs(0), vec![(0, 40, false), (40, 100, true)]);
+}
+
+#[test]
+fn a_buried_blast_leaves_the_ground_above_it_hanging() {
+    let mut mask = flat(30.0);
+    let (min_x, max_x) = mask.fill_circle(Point::new(50.0, 60.0), 10.0, false);
+    assert!(min_x <= 40 && max_x >= 60);
+
+    // the crater is round and under a roof
+    assert_eq!(mask.surface(50), 30.0);
+    assert!(!mask.is_solid(Point::new(50.5, 60.5)));
+    assert!(!mask.is_solid(Point::new(58.5, 60.5)));
+    assert!(mask.is_solid(Point::new(58.5, 67.5)));
+    assert_eq!(
+        mask.runs(50),
+        vec![
+            (0, 30, false),
+            (30, 50, true),
+            (50, 70, false),
+            (70, 100, true)
+        ]
+    );
+
+    // whatever falls through the roof lands on the floor of the cave
+    assert_eq!(mask.ground_below(50, 55.0), 70.0);
+    // and something stuck in the roof climbs out on top
+    assert_eq!(mask.ground_below(50, 45.0), 30.0);
+}
+
+#[test]
+fn tunnels_run_along_a_line() {
+    let mut mask = flat(10.0);
+    mask.fill_line(Point::new(10.0, 50.0), Point::new(90.0, 50.0), 3.0, false);
+    for x in [10.5, 50.5, 89.5] {
+        assert!(!mask.is_solid(Point::new(x, 50.5)), "solid at {}", x);
+        assert!(mask.is_solid(Point::new(x, 45.5)));
+        assert!(mask.is_solid(Point::new(x, 55.5)));
+    }
+    assert!(mask.is_solid(Point::new(95.5, 50.5)));
+}
+
+#[test]
+fn loose_earth_falls_until_it_lands() {
+    let mut mask = flat(80.0);
+    // a block of earth hanging in the air
+    for x in 40..50 {
+        for y in 20..30 {
+            mask.set(x, y, true);
+        }
+    }
+    let mut ticks = 0;
+    while mask.drop_loose().is_some() {
+        ticks += 1;
+    }
+    assert_eq!(ticks, 50);
+    assert_eq!(mask.surface(45), 70.0);
+    assert!(mask.get(45, 79));
+}
+
+#[test]
+fn tanks_stand_on_whatever_ground_is_left_under_them() {
+    let config = MatchSettings {
+        terrain: TerrainSettings::Flat(Flat::default()),
+        ..MatchSettings::default()
+    }
+    .config()
+    .unwrap();
+    let mut terrain = Terrain::new(
+        &config,
+        &mut match_rng(3, Stream::Terrain),
+        &mut match_rng(3, Stream::Spawn),
+    );
+    let tank = terrain.tank(PlayerId(0)).clone();
+    let (x, ground) = (tank.location().x(), tank.location().y());
+    let floor = config.height();
+
+    // a shaft under the left half of the tank leaves it on the right half
+    terrain.dig(Point::new(x, ground), Point::new(x, floor), tank.width());
+    assert_eq!(terrain.tank(PlayerId(0)).location().y(), ground);
+
+    // and with the rest gone too it falls
+    terrain.dig(
+        Point::new(x + tank.width(), ground),
+        Point::new(x + tank.width(), floor),
+        tank.width() * 2.0,
+    );
+    assert!(terrain.tank(PlayerId(0)).location().y() > ground);
+}
+
+#[test]
+fn islands_rise_out_of_the_sea_with_the_tanks_on_dry_land() {
+    let config = MatchSettings {
+        players: 4,
+        terrain: TerrainSettings::Islands(Islands::default()),
+        ..MatchSettings::default()
+    }
+    .config()
+    .unwrap();
+    for seed in 0..20 {
+        let terrain = Terrain::new(
+            &config,
+            &mut match_rng(seed, Stream::Terrain),
+            &mut match_rng(seed, Stream::Spawn),
+        );
+        let sea_level = terrain.sea_level().unwrap();
+        assert!(terrain.heights().iter().any(|&y| y > sea_level));
+        let tanks = terrain.tanks();
+        for tank in tanks {
+            let x = tank.location().x() as usize;
+            let under = &terrain.heights()[x..x + tank.width() as usize];
+            assert!(under.iter().all(|&y| y < sea_level), "seed {}", seed);
+            let apart = tanks.iter().all(|other| {
+                other.player() == tank.player()
+                    || (other.location().x() - tank.location().x()).abs() >= tank.width()
+            });
+            assert!(apart, "seed {}", seed);
+        }
+    }
+
+    let dry = MatchSettings::default().config().unwrap();
+    let terrain = Terrain::new(
+        &dry,
+        &mut match_rng(1, Stream::Terrain),
+        &mut match_rng(1, Stream::Spawn),
+    );
+    assert_eq!(terrain.sea_level(), None);
+}
+
+#[test]
+fn cliffs_slide_down_to_a_stable_slope() {
+    let mut heights = [90.0; 100];
+    heights[..50].fill(20.0);
+    let mut mask = TerrainMask::from_heights(&heights, 100);
+    let solid = |mask: &TerrainMask| (0..100).map(|x| 100.0 - mask.surface(x)).sum::<f64>();
+    let before = solid(&mask);
+
+    while mask.slide((0, 100), 2.0).is_some() {}
+    for x in 0..99 {
+        assert!((mask.surface(x) - mask.surface(x + 1)).abs() <= 2.0);
+    }
+    // no earth is lost on the way down
+    assert_eq!(solid(&mask), before);
+}
+
+#[test]
+fn every_generator_shapes_the_whole_field_from_the_seed() {
+    let generators = [
+        TerrainSettings::RandomWalk(RandomWalk::default()),
+        TerrainSettings::MidpointDisplacement(MidpointDisplacement::default()),
+        TerrainSettings::Noise(Noise::default()),
+        TerrainSettings::Plateaus(Plateaus::default()),
+        TerrainSettings::Islands(Islands::default()),
+        TerrainSettings::Flat(Flat::default()),
+    ];
+    for terrain in generators.iter() {
+        let generate = |seed| {
+            let mut rng = match_rng(seed, Stream::Terrain);
+            terrain.generator().generate(900, 500.0, &mut rng)
+        };
+        let heights = generate(1);
+        assert_eq!(heights.len(), 900, "{:?}", terrain);
+        assert!(
+            heights.iter().all(|&y| y > 0.0 && y < 500.0),
+            "{:?}",
+            terrain
+        );
+        assert_eq!(heights, generate(1), "{:?}", terrain);
+        if !matches!(terrain, TerrainSettings::Flat(_)) {
+            assert_ne!(heights, generate(2), "{:?}", terrain);
+        }
+    }
+}
@@ -0,0 +1,222 @@
+//! Tests for ranking bots in tournaments.
+
+use warbots::sim::{
+    expected_score, BotRegistry, Format, MatchError, MatchSettings, SettingsError, Standings,
+    Tournament, TournamentError, INITIAL_RATING,
+};
+
+fn tournament(format: Format, entrants: &[&str]) -> Tournament {
+    Tournament {
+        format,
+        entrants: entrants.iter().map(|name| name.to_string()).collect(),
+        seeds: vec![4],
+        settings: MatchSettings::default(),
+        map: None,
+    }
+}
+
+fn assert_ratings_add_up(standings: &Standings) {
+    let total: f64 = standings.table.iter().map(|standing| standing.rating).sum();
+    let expected = INITIAL_RATING * standings.table.len() as f64;
+    assert!((total - expected).abs() < 1e-6, "{}", total);
+}
+
+#[test]
+fn round_robins_play_every_pairing_from_both_sides() {
+    let standings = tournament(
+        Format::RoundRobin,
+        &["easy", "medium", "hard", "lobber", "easy"],
+    )
+    .run(&BotRegistry::new())
+    .unwrap();
+    // ten pairings, both ways round
+    assert_eq!(standings.matches.len(), 20);
+    for a in 1..=5 {
+        for b in 1..=5 {
+            let played = standings
+                .matches
+                .iter()
+                .filter(|record| record.seats == [a, b])
+                .count();
+            assert_eq!(played, if a == b { 0 } else { 1 }, "{} v {}", a, b);
+        }
+    }
+    // nobody plays twice in a round
+    for round in 1..=5 {
+        let mut seen = Vec::new();
+        for record in standings
+            .matches
+            .iter()
+            .filter(|record| record.round == round)
+        {
+            for seat in record.seats.iter() {
+                if !seen.contains(seat) {
+                    seen.push(*seat);
+                }
+            }
+        }
+        assert_eq!(seen.len(), 4, "round {}", round);
+    }
+
+    for standing in standings.table.iter() {
+        assert_eq!(standing.played, 8);
+        assert_eq!(standing.wins + standing.draws + standing.losses, 8);
+        assert_eq!(
+            standing.points,
+            standing.wins as f64 + standing.draws as f64 / 2.0
+        );
+    }
+    assert!(standings
+        .table
+        .windows(2)
+        .all(|pair| pair[0].points >= pair[1].points));
+    assert_ratings_add_up(&standings);
+}
+
+#[test]
+fn elimination_brackets_knock_out_all_but_one() {
+    let standings = tournament(
+        Format::Elimination,
+        &["hard", "medium", "easy", "lobber", "easy"],
+    )
+    .run(&BotRegistry::new())
+    .unwrap();
+    // eight places, so three of the five have a bye to the second round
+    assert_eq!(standings.matches.len(), 4 * 2);
+    assert_eq!(standings.table[0].eliminated_in, None);
+    assert!(standings.table[1..]
+        .iter()
+        .all(|standing| standing.eliminated_in.is_some()));
+    assert!(standings
+        .table
+        .windows(2)
+        .all(|pair| pair[0].eliminated_in.unwrap_or(u32::MAX)
+            >= pair[1].eliminated_in.unwrap_or(u32::MAX)));
+    // the first round is 4 v 5, who the byes leave to meet
+    assert!(standings
+        .matches
+        .iter()
+        .filter(|record| record.round == 1)
+        .all(|record| record.seats == [4, 5] || record.seats == [5, 4]));
+    assert_ratings_add_up(&standings);
+}
+
+#[test]
+fn swiss_rounds_avoid_rematches() {
+    let format = Format::swiss(6);
+    assert_eq!(format, Format::Swiss { rounds: 3 });
+    let standings = tournament(
+        format,
+        &["easy", "medium", "hard", "hard", "easy", "medium"],
+    )
+    .run(&BotRegistry::new())
+    .unwrap();
+    assert_eq!(standings.matches.len(), 3 * 3 * 2);
+    let mut pairings = Vec::new();
+    for record in standings.matches.iter() {
+        let mut pairing = record.seats;
+        pairing.sort();
+        if !pairings.contains(&(record.round, pairing)) {
+            assert!(
+                !pairings.iter().any(|(_, met)| *met == pairing),
+                "{:?} met twice",
+                pairing
+            );
+            pairings.push((record.round, pairing));
+        }
+    }
+
+    // an odd one out each round gets a bye worth half a series
+    let standings = tournament(Format::Swiss { rounds: 2 }, &["easy", "hard", "medium"])
+        .run(&BotRegistry::new())
+        .unwrap();
+    let points: f64 = standings.table.iter().map(|standing| standing.points).sum();
+    assert_eq!(points, (standings.matches.len() + 2) as f64);
+}
+
+#[test]
+fn standings_are_reproducible_and_read_back() {
+    let run = || {
+        tournament(Format::RoundRobin, &["medium", "hard", "easy"])
+            .run(&BotRegistry::new())
+            .unwrap()
+    };
+    let standings = run();
+    assert_eq!(standings, run());
+    let json = standings.to_json();
+    assert!(json.contains("\"format\": \"round-robin\""), "{}", json);
+    assert_eq!(serde_json::from_str::<Standings>(&json).unwrap(), standings);
+
+    let table = standings.to_string();
+    let lines: Vec<&str> = table.lines().collect();
+    assert_eq!(lines.len(), 4);
+    assert!(lines[0].contains("rating"));
+    assert!(lines[1].trim_start().starts_with("1  "));
+}
+
+#[test]
+fn ratings_follow_elo() {
+    assert_eq!(expected_score(1500.0, 1500.0), 0.5);
+    assert!((expected_score(1900.0, 1500.0) - 10.0 / 11.0).abs() < 1e-12);
+    assert!((expected_score(1500.0, 1700.0) + expected_score(1700.0, 1500.0) - 1.0).abs() < 1e-12);
+}
+
+#[test]
+fn tournaments_that_cant_be_played_are_refused() {
+    let registry = BotRegistry::new();
+    assert_eq!(
+        tournament(Format::RoundRobin, &["easy"]).run(&registry),
+        Err(TournamentError::Entrants(1))
+    );
+    assert_eq!(
+        tournament(Format::RoundRobin, &["easy", "sniper"]).run(&registry),
+        Err(TournamentError::UnknownBot("sniper".to_string()))
+    );
+
+    let mut unseeded = tournament(Format::Elimination, &["easy", "hard"]);
+    unseeded.seeds.clear();
+    assert_eq!(unseeded.run(&registry), Err(TournamentError::NoSeeds));
+
+    let mut crowded = tournament(Format::Elimination, &["easy", "hard"]);
+    crowded.settings.players = 3;
+    assert_eq!(
+        crowded.run(&registry).unwrap_err().to_string(),
+        "tournament matches are between 2 players, not 3"
+    );
+
+    let mut tiny = tournament(Format::Elimination, &["easy", "hard"]);
+    tiny.settings.width = 10;
+    assert!(matches!(
+        tiny.run(&registry),
+        Err(TournamentError::Match(MatchError::Settings(
+            SettingsError::OutOfRange { .. }
+        )))
+    ));
+}
+
+#[test]
+fn the_binary_writes_standings() {
+    let path = std::env::temp_dir().join(format!("warbots-standings-{}.json", std::process::id()));
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_warbots-sim"))
+        .args(["--tournament", "elimination", "--seed", "2", "--standings"])
+        .arg(&path)
+        .args(["easy", "hard"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("out in"), "{}", stdout);
+
+    let standings: Standings =
+        serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(standings.format, Format::Elimination);
+    assert_eq!(standings.matches.len(), 2);
+    std::fs::remove_file(&path).unwrap();
+
+    // more rounds than fit in a u32 aren't wrapped around to a few
+    let output = std::process::Command::new(env!("CARGO_BIN_EXE_warbots-sim"))
+        .args(["--tournament", "swiss:4294967297", "easy", "hard"])
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(2));
+}
@@ -0,0 +1,135 @@
+//! Tests for the weapons a tank can fire and its inventory.
+
+mod common;
+
+use common::calm;
+use warbots::sim::{Action, DamageCause, Event, Game, GameError, Impact, PlayerId, Weapon};
+
+/// Fires `weapon` for the active player and returns every impact it made.
+fn fire(game: &mut Game, weapon: Weapon, power: f64, angle: f64) -> Vec<Impact> {
+    let action = Action {
+        power,
+        angle,
+        weapon,
+    };
+    game.fire(game.active_player(), action).unwrap();
+    game.run_to_next_turn();
+    game.drain_events()
+        .into_iter()
+        .filter_map(|event| match event {
+            Event::Impact(impact) => Some(impact),
+            _ => None,
+        })
+        .collect()
+}
+
+fn skip(game: &mut Game) {
+    game.skip_turn(game.active_player()).unwrap();
+    game.run_to_next_turn();
+}
+
+#[test]
+fn limited_weapons_run_out() {
+    let mut game = Game::new(calm(), 7);
+    let inventory = game.terrain().tank(PlayerId(0)).inventory().clone();
+    assert_eq!(inventory.count(Weapon::Missile), None);
+    assert_eq!(inventory.count(Weapon::Nuke), Some(1));
+
+    fire(&mut game, Weapon::Nuke, 100.0, 120.0);
+    skip(&mut game);
+    assert_eq!(
+        game.terrain()
+            .tank(PlayerId(0))
+            .inventory()
+            .count(Weapon::Nuke),
+        Some(0)
+    );
+    let nuke = Action {
+        power: 100.0,
+        angle: 120.0,
+        weapon: Weapon::Nuke,
+    };
+    assert_eq!(
+        game.fire(PlayerId(0), nuke),
+        Err(GameError::OutOfAmmo {
+            weapon: Weapon::Nuke
+        })
+    );
+    // the other side still has its own
+    assert_eq!(
+        game.terrain()
+            .tank(PlayerId(1))
+            .inventory()
+            .count(Weapon::Nuke),
+        Some(1)
+    );
+}
+
+#[test]
+fn a_mirv_comes_down_in_pieces() {
+    let mut game = Game::new(calm(), 7);
+    let impacts = fire(&mut game, Weapon::Mirv, 100.0, 120.0);
+    assert_eq!(impacts.len(), 5);
+    let first = impacts.iter().map(|i| i.point.x()).fold(f64::MAX, f64::min);
+    let last = impacts.iter().map(|i| i.point.x()).fold(f64::MIN, f64::max);
+    assert!(last - first > 20.0, "warheads should spread out");
+}
+
+#[test]
+fn a_nuke_blows_a_wider_crater_than_a_missile() {
+    let width = |weapon| {
+        let mut game = Game::new(calm(), 7);
+        let impact = &fire(&mut game, weapon, 100.0, 120.0)[0];
+        impact.max_index - impact.min_index
+    };
+    assert!(width(Weapon::Nuke) > width(Weapon::Missile));
+}
+
+#[test]
+fn a_dirt_bomb_raises_the_ground_and_hurts_nobody() {
+    let mut game = Game::new(calm(), 7);
+    let before = game.terrain().heights().to_vec();
+    let impact = &fire(&mut game, Weapon::DirtBomb, 100.0, 120.0)[0];
+    let i = impact.point.x() as usize;
+    assert!(game.terrain().heights()[i] < before[i]);
+    assert!(impact.damage.is_empty());
+}
+
+#[test]
+fn a_digger_bores_into_the_ground_before_going_off() {
+    let mut game = Game::new(calm(), 7);
+    let before = game.terrain().heights().to_vec();
+    let impact = &fire(&mut game, Weapon::Digger, 100.0, 120.0)[0];
+    let i = impact.point.x() as usize;
+    assert!(impact.point.y() > before[i] + 20.0);
+    // it leaves a hollow under ground it didn't go through
+    let mask = game.terrain().mask();
+    let hollow = (0..mask.width()).any(|x| {
+        let runs = mask.runs(x);
+        runs.len() > 3 && runs[1].2 && !runs[2].2
+    });
+    assert!(hollow);
+}
+
+#[test]
+fn a_roller_runs_downhill_from_where_it_lands() {
+    let mut missile_game = Game::new(calm(), 7);
+    let landed = fire(&mut missile_game, Weapon::Missile, 100.0, 120.0)[0].point;
+
+    let mut game = Game::new(calm(), 7);
+    let stopped = fire(&mut game, Weapon::Roller, 100.0, 120.0)[0].point;
+    assert!((stopped.x() - landed.x()).abs() > 1.0);
+    assert!(stopped.y() >= landed.y() - 2.0);
+}
+
+#[test]
+fn napalm_burns_the_tank_it_lands_on() {
+    let mut game = Game::new(calm(), 7);
+    // straight up comes back down on the shooter
+    let impact = &fire(&mut game, Weapon::Napalm, 200.0, 90.0)[0];
+    assert!(impact
+        .damage
+        .iter()
+        .any(|d| d.player == PlayerId(0) && d.cause == DamageCause::Fire));
+    assert!(impact.max_index - impact.min_index > 10);
+}
@@ -1,54 +1,214 @@
-import { Config, start, player_fire } from "warbots";
-
-const config = Config.new();
-const canvas = document.getElementById("warbots-canvas");
-canvas.height = config.height();
-canvas.width = config.width();
-
-// tell Rust-WASM code to start the game
-start();
-
-const powerBox = document.getElementById("power-box");
-const angleBox = document.getElementById("angle-box");
-window.addEventListener("keydown", (e) => {
-	// disable scrolling for space and arrow keys
-	if([32, 37, 38, 39, 40].indexOf(e.keyCode) > -1) {
-			e.preventDefault();
-	}
-
-  switch (e.keyCode) {
-    case 37:
-      // left key
-      incrementAngle(-1);
-      break;
-    case 38:
-      // up key
-      incrementPower(1);
-      break;
-    case 39:
-      // right key
-      incrementAngle(1);
-      break;
-    case 40:
-      // down key
-      incrementPower(-1);
-      break;
-  }
-});
-
-const maxAngle = config.max_angle();
-const minAngle = config.min_angle();
-const maxPower = config.max_power();
-const minPower = config.min_power();
-function incrementAngle(diff) {
-  let value = parseInt(angleBox.value) + diff;
-  if (minAngle <= value && value <= maxAngle) {
-    angleBox.value = value;
-  }
-}
-function incrementPower(diff) {
-  let value = parseInt(powerBox.value) + diff;
-  if (minPower <= value && value <= maxPower) {
-    powerBox.value = value;
+import {
+  aim_changed,
+  bot_names,
+  editor_colors,
+  editor_tool,
+  editor_tools,
+  export_editor_map,
+  export_replay,
+  join_online,
+  keys,
+  leave_online,
+  load_plugin,
+  player_colors,
+  resume_saved_match,
+  replay_seek,
+  replay_speed,
+  set_keys,
+  sky_colors,
+  start,
+  start_editor,
+  start_hot_seat,
+  start_match_with_settings,
+  start_replay,
+  terrain_colors,
+  watch_online,
+  weapon_names,
+} from "warbots";
+
+function fillSelect(select, names) {
+  for (const name of names) {
+    const option = document.createElement("option");
+    option.value = name;
+    option.textContent = name;
+    select.appendChild(option);
+  }
+}
+
+fillSelect(document.getElementById("weapon-box"), weapon_names());
+// the turret and aim preview follow what is typed into the boxes
+for (const id of ["power-box", "angle-box", "weapon-box"]) {
+  document.getElementById(id).addEventListener("input", () => aim_changed());
+}
+
+// typing in a box shouldn't press the game's keys
+function keepKeys(id) {
+  for (const type of ["keydown", "keyup"]) {
+    document.getElementById(id).addEventListener(type, (e) => e.stopPropagation());
+  }
+}
+
+// playing against any bot, including ones loaded as WebAssembly plugins, or
+// another human at this keyboard
+const opponentBox = document.getElementById("opponent-box");
+const fillOpponents = () => {
+  const chosen = opponentBox.value || "medium";
+  opponentBox.replaceChildren();
+  fillSelect(opponentBox, ["human", ...bot_names()]);
+  opponentBox.value = chosen;
+};
+fillOpponents();
+// practice matches show where each shot will go before it is fired
+document.getElementById("new-match-button").addEventListener("click", () => {
+  const seed = BigInt(Math.floor(Math.random() * 2 ** 32));
+  const settings = { aim_preview: document.getElementById("preview-box").checked };
+  start_match_with_settings(seed, ["human", opponentBox.value], JSON.stringify(settings));
+});
+// the bot is named after its file
+document.getElementById("plugin-file").addEventListener("change", async (e) => {
+  const file = e.target.files[0];
+  if (!file) {
+    return;
   }
+  const name = file.name.replace(/\.wasm$/, "");
+  try {
+    load_plugin(name, new Uint8Array(await file.arrayBuffer()));
+    fillOpponents();
+    opponentBox.value = name;
+  } catch (error) {
+    console.log(`warbots: can't load ${file.name}: ${error}`);
+  }
+});
+
+// several humans taking turns at this keyboard, each with their own keys
+const hotSeatStatus = document.getElementById("hot-seat-status");
+const split = (text) => text.split(",").map((part) => part.trim()).filter((part) => part);
+document.getElementById("colors-box").placeholder = player_colors().join(", ");
+document.getElementById("hot-seat-button").addEventListener("click", () => {
+  const seed = BigInt(Math.floor(Math.random() * 2 ** 32));
+  const names = split(document.getElementById("names-box").value);
+  try {
+    start_hot_seat(seed, names, split(document.getElementById("colors-box").value));
+    hotSeatStatus.textContent = "";
+  } catch (error) {
+    hotSeatStatus.textContent = `${error}`;
+  }
+});
+const keysPlayerBox = document.getElementById("keys-player-box");
+const keysBox = document.getElementById("keys-box");
+player_colors().forEach((_, seat) => {
+  const option = document.createElement("option");
+  option.value = seat;
+  option.textContent = `Player ${seat + 1}`;
+  keysPlayerBox.appendChild(option);
+});
+const showKeys = () => {
+  keysBox.value = keys(parseInt(keysPlayerBox.value));
+};
+showKeys();
+keysPlayerBox.addEventListener("change", showKeys);
+document.getElementById("set-keys-button").addEventListener("click", () => {
+  try {
+    set_keys(parseInt(keysPlayerBox.value), keysBox.value.trim());
+    hotSeatStatus.textContent = `keys set for Player ${parseInt(keysPlayerBox.value) + 1}`;
+  } catch (error) {
+    hotSeatStatus.textContent = `${error}`;
+  }
+});
+for (const id of ["names-box", "colors-box", "keys-box"]) {
+  keepKeys(id);
+}
+
+// playing another browser through a warbots-server relay; the first to
+// join a room waits there for the second
+const relayBox = document.getElementById("relay-box");
+const roomBox = document.getElementById("room-box");
+document.getElementById("join-button").addEventListener("click", () => {
+  try {
+    join_online(relayBox.value.trim(), roomBox.value.trim());
+  } catch (error) {
+    document.getElementById("online-status").textContent = `${error}`;
+  }
+});
+// or watching a room's match from wherever it has got to
+document.getElementById("watch-button").addEventListener("click", () => {
+  try {
+    watch_online(relayBox.value.trim(), roomBox.value.trim());
+  } catch (error) {
+    document.getElementById("online-status").textContent = `${error}`;
+  }
+});
+document.getElementById("leave-button").addEventListener("click", () => leave_online());
+for (const id of ["relay-box", "room-box"]) {
+  keepKeys(id);
+}
+
+// the map editor
+const toolBox = document.getElementById("tool-box");
+const colorBox = document.getElementById("color-box");
+const skyBox = document.getElementById("sky-box");
+const mapJson = document.getElementById("map-json");
+fillSelect(toolBox, editor_tools());
+fillSelect(colorBox, terrain_colors());
+fillSelect(skyBox, sky_colors());
+document.getElementById("edit-button").addEventListener("click", () => {
+  const seed = BigInt(Math.floor(Math.random() * 2 ** 32));
+  start_editor(seed, mapJson.value.trim() || undefined);
+});
+toolBox.addEventListener("change", () => editor_tool(toolBox.value));
+const recolor = () => editor_colors(colorBox.value, skyBox.value);
+colorBox.addEventListener("change", recolor);
+skyBox.addEventListener("change", recolor);
+document.getElementById("export-button").addEventListener("click", () => {
+  try {
+    mapJson.value = export_editor_map("Custom") || "";
+  } catch (error) {
+    console.log(`warbots: can't export the map: ${error}`);
+  }
+});
+// typing a map in shouldn't press the editor's keys
+keepKeys("map-json");
+
+// replays
+const replayJson = document.getElementById("replay-json");
+const speedBox = document.getElementById("speed-box");
+fillSelect(speedBox, ["0.25", "0.5", "1", "2", "4", "8", "16"]);
+speedBox.value = "1";
+document.getElementById("save-replay-button").addEventListener("click", () => {
+  const replay = export_replay();
+  if (replay) {
+    replayJson.value = replay;
+    // also offer it as a file, e.g. to attach to a bug report
+    const link = document.createElement("a");
+    link.href = URL.createObjectURL(new Blob([replay], { type: "application/json" }));
+    link.download = "warbots-replay.json";
+    link.click();
+    URL.revokeObjectURL(link.href);
+  }
+});
+document.getElementById("play-replay-button").addEventListener("click", () => {
+  start_replay(replayJson.value.trim());
+  replay_speed(parseFloat(speedBox.value));
+});
+speedBox.addEventListener("change", () => replay_speed(parseFloat(speedBox.value)));
+// rounds and turns are shown counting from 1
+document.getElementById("seek-button").addEventListener("click", () => {
+  const round = parseInt(document.getElementById("round-box").value) - 1;
+  const turn = parseInt(document.getElementById("turn-box").value) - 1;
+  replay_seek(round, turn);
+});
+for (const id of ["replay-json", "round-box", "turn-box"]) {
+  keepKeys(id);
+}
+
+// carry on with the match from before the page was reloaded, if there was
+// one, or else tell Rust-WASM code to start the game
+let resumed = false;
+try {
+  resumed = resume_saved_match();
+} catch (error) {
+  console.log(`warbots: can't resume the saved match: ${error}`);
+}
+if (!resumed) {
+  start();
 }
@@ -6,6 +6,17 @@ export function get_angle() {
   return Number(document.getElementById("angle-box").value);
 }
 
+export function get_weapon() {
+  return document.getElementById("weapon-box").value;
+}
+
+// shows where the player whose turn it is is aiming
+export function set_aim(power, angle, weapon) {
+  document.getElementById("power-box").value = power;
+  document.getElementById("angle-box").value = angle;
+  document.getElementById("weapon-box").value = weapon;
+}
+
 
 export class UserInput {
   constructor() {